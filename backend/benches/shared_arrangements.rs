@@ -0,0 +1,133 @@
+//! Compares the built-in dataflows sharing one `SharedArrangements` against every
+//! dataflow building its own copy of the arrangements it uses.
+//!
+//! cargo bench --bench shared_arrangements
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use df_forum_backend::dataflows::SharedArrangements;
+use df_forum_backend::forum_minimal::{OutputScopeCollection, Persisted, PersistedInputSession};
+use df_forum_backend::registry::DataflowRegistry;
+use differential_dataflow::input::InputSession;
+use timely::communication::allocator::thread::Thread;
+use timely::dataflow::ProbeHandle;
+use timely::worker::Worker;
+use timely::WorkerConfig;
+
+const SESSIONS: u64 = 20;
+const TRANSACTIONS: u64 = 100;
+
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+struct Measurement {
+    elapsed: Duration,
+    retained_bytes: usize,
+}
+
+fn run(shared_between_dataflows: bool) -> Measurement {
+    let registry = DataflowRegistry::default();
+    let mut worker = Worker::new(WorkerConfig::default(), Thread::new());
+    let mut probe = ProbeHandle::new();
+
+    let allocated_before = ALLOCATED.load(Ordering::Relaxed);
+
+    let mut input: PersistedInputSession = worker.dataflow(|scope| {
+        let mut input: PersistedInputSession = InputSession::new();
+        let collection = input.to_collection(scope);
+        // dropped at the end of the closure, windows are not part of the bench
+        let mut recent_input: PersistedInputSession = InputSession::new();
+        let recent = recent_input.to_collection(scope);
+        let shared = SharedArrangements::new(&collection, &recent);
+
+        let results: OutputScopeCollection = if shared_between_dataflows {
+            registry.build(&shared)
+        } else {
+            let mut results = collection.flat_map(|_| Vec::new());
+            for module in registry.enabled_modules() {
+                // arrangements are built on first use, so a module only builds the ones it uses
+                let own = SharedArrangements::new(&collection, &recent);
+                results = results.concat(&(module.dataflow)(&own));
+            }
+            results
+        };
+
+        results.probe_with(&mut probe);
+        input
+    });
+
+    let started = Instant::now();
+
+    for time in 0..TRANSACTIONS {
+        let session = time % SESSIONS;
+        let addr: SocketAddr = format!("127.0.0.1:{}", 9000 + session).parse().unwrap();
+        let post_id = 1000 + time;
+
+        let items = if time < SESSIONS {
+            vec![
+                (session, Persisted::Session),
+                (session, Persisted::ViewPostsPage(0)),
+            ]
+        } else if time % 3 == 0 {
+            vec![(session, Persisted::PostLike(1000 + time / 2, true))]
+        } else {
+            vec![
+                (post_id, Persisted::Post),
+                (post_id, Persisted::PostTitle(format!("title {}", time))),
+                (
+                    post_id,
+                    Persisted::PostBody(format!("body of post {}", time)),
+                ),
+            ]
+        };
+
+        for (id, persisted) in items {
+            input.insert((addr, (id, persisted)));
+        }
+
+        input.advance_to(time + 1);
+        input.flush();
+        worker.step_while(|| probe.less_than(input.time()));
+    }
+
+    let elapsed = started.elapsed();
+    let retained_bytes = ALLOCATED.load(Ordering::Relaxed) - allocated_before;
+
+    Measurement {
+        elapsed,
+        retained_bytes,
+    }
+}
+
+fn main() {
+    for (label, shared) in [("per dataflow", false), ("shared", true)] {
+        let measurement = run(shared);
+
+        println!(
+            "{:>12}: {:>8.2} ms total, {:>6.1} us per transaction, {:>7} KiB retained",
+            label,
+            measurement.elapsed.as_secs_f64() * 1000.0,
+            measurement.elapsed.as_secs_f64() * 1_000_000.0 / TRANSACTIONS as f64,
+            measurement.retained_bytes / 1024,
+        );
+    }
+}
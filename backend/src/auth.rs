@@ -0,0 +1,172 @@
+use crate::forum_minimal::{Persisted, PersistedItems, TransactionError};
+use df_forum_frontend::df_tuple_items::Id;
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// The query parameter of the websocket url the client sends its token in, ie. `/?token=...`
+pub const TOKEN_QUERY_PARAMETER: &str = "token";
+/// File the tokens of the admins in `DF_FORUM_ADMINS` are written to at startup
+pub const ADMIN_TOKENS_FILE_ENV_VAR: &str = "DF_FORUM_ADMIN_TOKENS_FILE";
+pub const DEFAULT_ADMIN_TOKENS_FILE: &str = "df_forum_admin_tokens";
+
+/// Binds connections to users
+///
+/// There are no accounts: a connection proves to be a user with the token issued for it,
+/// a connection without a valid token becomes a new user and receives a token of its own.
+/// Tokens live as long as the backend, like everything else it keeps.
+#[derive(Default)]
+pub struct UserTokens {
+    users: HashMap<String, Id>,
+    user_ids: HashSet<Id>,
+}
+
+impl UserTokens {
+    /// Issues a new token for the user, earlier tokens of the user stay valid
+    pub fn issue(&mut self, user_id: Id) -> String {
+        let token = random_token();
+
+        self.users.insert(token.clone(), user_id);
+        self.user_ids.insert(user_id);
+        token
+    }
+
+    pub fn user_id(&self, token: &str) -> Option<Id> {
+        self.users.get(token).copied()
+    }
+
+    /// The user of a valid token together with the token,
+    /// otherwise a new user with a new token
+    pub fn authenticate(&mut self, token: Option<&str>) -> (Id, String) {
+        if let Some((token, user_id)) = token.and_then(|token| self.users.get_key_value(token)) {
+            return (*user_id, token.clone());
+        }
+
+        let user_id = loop {
+            let user_id = random_u64();
+            if !self.user_ids.contains(&user_id) {
+                break user_id;
+            }
+        };
+
+        (user_id, self.issue(user_id))
+    }
+}
+
+/// Writes a `user_id token` line per admin, replacing the file
+///
+/// Only the user running the backend can read the file, the tokens never show up in logs.
+pub fn write_admin_tokens(path: &Path, admin_tokens: &[(Id, String)]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    // the mode only applies to new files, an existing file could be readable by others
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+    for (user_id, token) in admin_tokens {
+        writeln!(file, "{} {}", user_id, token)?;
+    }
+    Ok(())
+}
+
+/// The token in the query of the websocket url, see `TOKEN_QUERY_PARAMETER`
+pub fn token_from_query(query: Option<&str>) -> Option<&str> {
+    query?.split('&').find_map(|parameter| {
+        parameter
+            .strip_prefix(TOKEN_QUERY_PARAMETER)?
+            .strip_prefix('=')
+            .filter(|token| !token.is_empty())
+    })
+}
+
+/// Checks that a transaction only starts or ends the session of the connection's user
+pub fn check_sessions(user_id: Id, items: &PersistedItems) -> Result<(), TransactionError> {
+    if items
+        .iter()
+        .any(|(id, persisted, _diff)| matches!(persisted, Persisted::Session) && *id != user_id)
+    {
+        return Err(TransactionError::Unauthorized(
+            "sessions are bound to the user of the connection".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes).expect("no source of randomness");
+    u64::from_be_bytes(bytes)
+}
+
+// 128 random bits as hex, safe to put into a url as is
+fn random_token() -> String {
+    format!("{:016x}{:016x}", random_u64(), random_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_authenticate() {
+        let mut user_tokens = UserTokens::default();
+
+        let admin_token = user_tokens.issue(1);
+        assert_eq!(user_tokens.authenticate(Some(&admin_token)), (1, admin_token.clone()));
+
+        // unknown tokens are a new user, not the user they claim to be
+        let (user_id, token) = user_tokens.authenticate(Some("1"));
+        assert_ne!(user_id, 1);
+        assert_ne!(token, admin_token);
+        assert_eq!(user_tokens.user_id(&token), Some(user_id));
+        assert_eq!(user_tokens.authenticate(Some(&token)), (user_id, token));
+
+        let (other_user_id, _token) = user_tokens.authenticate(None);
+        assert_ne!(other_user_id, user_id);
+    }
+
+    #[test]
+    pub fn test_write_admin_tokens() {
+        let path = std::env::temp_dir().join(format!("df_forum_admin_tokens_{}", random_u64()));
+
+        write_admin_tokens(&path, &[(1, "ab12".into()), (2, "cd34".into())]).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1 ab12\n2 cd34\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_token_from_query() {
+        assert_eq!(token_from_query(Some("token=ab12")), Some("ab12"));
+        assert_eq!(token_from_query(Some("page=2&token=ab12")), Some("ab12"));
+        assert_eq!(token_from_query(Some("tokens=ab12")), None);
+        assert_eq!(token_from_query(Some("token=")), None);
+        assert_eq!(token_from_query(None), None);
+    }
+
+    #[test]
+    pub fn test_check_sessions() {
+        assert_eq!(
+            check_sessions(55, &vec![(55, Persisted::Session, 1), (5, Persisted::Post, 1)]),
+            Ok(())
+        );
+        assert_eq!(
+            check_sessions(55, &vec![(1, Persisted::Session, 1)]),
+            Err(TransactionError::Unauthorized(
+                "sessions are bound to the user of the connection".into()
+            ))
+        );
+    }
+}
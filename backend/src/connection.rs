@@ -1,20 +1,33 @@
-use df_forum_backend::forum_minimal::{ForumMinimal, PersistedItems};
+use df_forum_backend::auth::{
+    check_sessions, token_from_query, write_admin_tokens, UserTokens, ADMIN_TOKENS_FILE_ENV_VAR,
+    DEFAULT_ADMIN_TOKENS_FILE,
+};
+use df_forum_backend::forum_minimal::{
+    parse_user_ids, ForumMinimal, OutcomeSender, QueryResultSender, Role,
+    ServerMessage, Transaction, TransactionOutcome, ADMINS_ENV_VAR,
+};
+use df_forum_backend::rate_limit::{RateLimiter, RateLimits, RATE_LIMITS_ENV_VAR};
+use df_forum_backend::registry::{DataflowRegistry, DATAFLOWS_ENV_VAR};
+use df_forum_backend::session_state::SessionRecords;
 use std::net::SocketAddr;
-use log::debug;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use log::{debug, error};
 
 use futures_channel::mpsc::unbounded;
 use futures_util::{future, pin_mut, StreamExt};
 
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
 use tokio_tungstenite::tungstenite::protocol::Message;
 
 // use df_forum_frontend::persisted::Persisted;
-use df_forum_frontend::query_result::QueryResult;
 
 #[derive(Debug)]
 pub enum HandlerError {
     Handshake,
+    Configuration,
     // PeerMapLock,
     // FailedSocketBind,
 }
@@ -22,47 +35,128 @@ pub enum HandlerError {
 async fn handle_connection(
     raw_stream: TcpStream,
     addr: SocketAddr,
-    persisted_sender: broadcast::Sender<(SocketAddr, PersistedItems)>,
-    query_result_sender: broadcast::Sender<(SocketAddr, Vec<QueryResult>)>,
+    persisted_sender: broadcast::Sender<(SocketAddr, Transaction)>,
+    query_result_sender: QueryResultSender,
+    outcome_sender: OutcomeSender,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    user_tokens: Arc<Mutex<UserTokens>>,
 ) -> Result<(), HandlerError> {
-    let ws_stream = tokio_tungstenite::accept_async(raw_stream)
+    // the connection is bound to the user of the token in the url, or to a new user
+    let mut token = None;
+    // the error response of the callback is the one tungstenite defines
+    #[allow(clippy::result_large_err)]
+    let read_token = |request: &Request, response: Response| {
+        token = token_from_query(request.uri().query()).map(str::to_string);
+        Ok(response)
+    };
+    let ws_stream = tokio_tungstenite::accept_hdr_async(raw_stream, read_token)
         .await
         .map_err(|_err| HandlerError::Handshake)?;
 
+    let (user_id, token) = user_tokens.lock().unwrap().authenticate(token.as_deref());
+    debug!("{} authenticated as user {}", addr, user_id);
+
     let (tx, rx) = unbounded();
 
-    let (outgoing, incoming) = ws_stream.split();
+    let authenticated = ServerMessage::Authenticated(user_id, token);
+    let msg = serde_json::to_string(&authenticated).unwrap();
+    if tx.unbounded_send(Message::Text(msg)).is_err() {
+        debug!("could not send to address {}", addr);
+    }
 
-    let mut incoming_strings =
-        incoming.map(|msg| msg.unwrap().to_text().unwrap_or("[]").to_string());
+    let (outgoing, mut incoming) = ws_stream.split();
 
     let mut query_result_receiver = query_result_sender.subscribe();
+    let mut outcome_receiver = outcome_sender.subscribe();
+
+    let rejection_tx = tx.clone();
+    let connection_rate_limiter = rate_limiter.clone();
+    let incoming_persisted_sender = persisted_sender.clone();
+
+    // the session, its page views, searches, rooms etc. are retracted on disconnection
+    let session_records: Arc<Mutex<SessionRecords>> = Arc::default();
+    let connection_session_records = session_records.clone();
 
     let broadcast_incoming = tokio::spawn(async move {
-        while let Some(msg) = incoming_strings.next().await {
+        while let Some(msg) = incoming.next().await {
+            // a broken connection ends like a closed one
+            let msg = match msg {
+                Ok(msg) => msg.to_text().unwrap_or("[]").to_string(),
+                Err(err) => {
+                    debug!("could not receive from address {}: {}", addr, err);
+                    break;
+                }
+            };
             debug!("got msg: {}", msg);
 
-            let parsed_msg: PersistedItems = serde_json::from_str(&msg).unwrap_or(vec![]);
-            // .expect("Could not parse PersistedItems from Websocket Message");
-            persisted_sender.send((addr, parsed_msg)).unwrap();
+            let parsed_msg: Transaction =
+                serde_json::from_str(&msg).unwrap_or(Transaction::from(vec![]));
+            // .expect("Could not parse Transaction from Websocket Message");
+
+            // the writes of a user share one rate limit on all of its connections
+            let checked = check_sessions(user_id, &parsed_msg.items).and_then(|()| {
+                connection_rate_limiter.lock().unwrap().check(
+                    addr,
+                    user_id,
+                    &parsed_msg.items,
+                    Instant::now(),
+                )
+            });
+
+            match (checked, parsed_msg.id) {
+                (Ok(()), _) => {
+                    connection_session_records
+                        .lock()
+                        .unwrap()
+                        .track(&parsed_msg.items);
+
+                    incoming_persisted_sender.send((addr, parsed_msg)).unwrap();
+                }
+                (Err(error), Some(transaction_id)) => {
+                    debug!("rejected {}: {:?}", addr, error);
+
+                    // the transaction never reaches the dataflows
+                    let outcome = TransactionOutcome::Rejected(transaction_id, error);
+                    let output_payload =
+                        serde_json::to_string(&ServerMessage::Outcome(outcome)).unwrap();
+
+                    if rejection_tx
+                        .unbounded_send(Message::Text(output_payload))
+                        .is_err()
+                    {
+                        debug!("could not send to address {}", addr);
+                    }
+                }
+                (Err(error), None) => debug!("rejected {}: {:?}", addr, error),
+            }
         }
     });
 
     tokio::spawn(async move {
         loop {
-            // TODO: security risk
-            // an attacker can just connect to another port and hijack the session running there
-            // a security token is needed
-            let (viewer_addr, query_results) = query_result_receiver.recv().await.unwrap();
+            // the frame of a time is sent before the outcomes of the transaction applied at it,
+            // `biased` takes frames first whenever both are ready
+            let (viewer_addr, server_message) = tokio::select! {
+                biased;
+                frame = query_result_receiver.recv() => {
+                    let (viewer_addr, frame) = frame.unwrap();
+                    (viewer_addr, ServerMessage::Frame(frame))
+                }
+                outcome = outcome_receiver.recv() => {
+                    let (viewer_addr, outcome) = outcome.unwrap();
+                    (viewer_addr, ServerMessage::Outcome(outcome))
+                }
+            };
+
             if viewer_addr == addr {
                 debug!(
-                    "query_results: {:?}, (viewer_addr = {:?})",
-                    query_results, viewer_addr
+                    "server message: {:?}, (viewer_addr = {:?})",
+                    server_message, viewer_addr
                 );
 
-                let output_payload = serde_json::to_string(&query_results.clone()).unwrap();
+                let output_payload = serde_json::to_string(&server_message).unwrap();
 
-                if let Err(_) = tx.unbounded_send(Message::Text(output_payload)) {
+                if tx.unbounded_send(Message::Text(output_payload)).is_err() {
                     debug!("could not send to address {}", viewer_addr);
                 }
             }
@@ -73,13 +167,28 @@ async fn handle_connection(
     pin_mut!(broadcast_incoming, recieve_from_others);
     future::select(broadcast_incoming, recieve_from_others).await;
 
+    let retractions = session_records.lock().unwrap().retractions();
+    if !retractions.is_empty() {
+        persisted_sender
+            .send((addr, Transaction::from(retractions)))
+            .unwrap();
+    }
+
+    rate_limiter
+        .lock()
+        .unwrap()
+        .remove_connection(addr, Instant::now());
+
     Ok(())
 }
 
 async fn loop_check_for_connections(
     addr: String,
-    persisted_sender: broadcast::Sender<(SocketAddr, PersistedItems)>,
-    query_result_sender: broadcast::Sender<(SocketAddr, Vec<QueryResult>)>,
+    persisted_sender: broadcast::Sender<(SocketAddr, Transaction)>,
+    query_result_sender: QueryResultSender,
+    outcome_sender: OutcomeSender,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    user_tokens: Arc<Mutex<UserTokens>>,
 ) {
     let try_socket = TcpListener::bind(&addr).await;
     let listener = try_socket.unwrap();
@@ -92,6 +201,9 @@ async fn loop_check_for_connections(
                 addr,
                 persisted_sender.clone(),
                 query_result_sender.clone(),
+                outcome_sender.clone(),
+                rate_limiter.clone(),
+                user_tokens.clone(),
             ));
         }
     }
@@ -101,11 +213,61 @@ pub async fn establish(addr: String) -> Result<(), HandlerError> {
     let (query_result_sender, _query_result_receiver) = broadcast::channel(64);
     let (persisted_sender, _persisted_receiver) = broadcast::channel(64);
 
-    let mut forum_minimal =
-        ForumMinimal::new(persisted_sender.clone(), query_result_sender.clone());
+    let mut registry = DataflowRegistry::default();
+    registry.configure_from_env().map_err(|err| {
+        error!("invalid {}: {:?}", DATAFLOWS_ENV_VAR, err);
+        HandlerError::Configuration
+    })?;
+
+    let mut forum_minimal = ForumMinimal::new_with_registry(
+        persisted_sender.clone(),
+        query_result_sender.clone(),
+        registry,
+    );
+
+    let mut user_tokens = UserTokens::default();
+
+    if let Ok(admins) = std::env::var(ADMINS_ENV_VAR) {
+        let admins = parse_user_ids(&admins).map_err(|err| {
+            error!("invalid {}: {:?}", ADMINS_ENV_VAR, err);
+            HandlerError::Configuration
+        })?;
+
+        // admins open the frontend once with `?token=...` to become the admin user
+        let admin_tokens: Vec<_> = admins
+            .into_iter()
+            .map(|user_id| {
+                forum_minimal.grant_role(user_id, Role::Admin);
+                (user_id, user_tokens.issue(user_id))
+            })
+            .collect();
+
+        let path = std::env::var(ADMIN_TOKENS_FILE_ENV_VAR)
+            .unwrap_or_else(|_| DEFAULT_ADMIN_TOKENS_FILE.to_string());
+        write_admin_tokens(path.as_ref(), &admin_tokens).map_err(|err| {
+            error!("could not write the admin tokens to {}: {:?}", path, err);
+            HandlerError::Configuration
+        })?;
+        println!("tokens of the admins written to {}", path);
+    }
+
+    let rate_limits = RateLimits::from_env().map_err(|err| {
+        error!("invalid {}: {:?}", RATE_LIMITS_ENV_VAR, err);
+        HandlerError::Configuration
+    })?;
+    let rate_limiter = Arc::new(Mutex::new(RateLimiter::new(rate_limits)));
+
+    let outcome_sender = forum_minimal.outcome_sender.clone();
 
     tokio::join!(
-        loop_check_for_connections(addr, persisted_sender, query_result_sender),
+        loop_check_for_connections(
+            addr,
+            persisted_sender,
+            query_result_sender,
+            outcome_sender,
+            rate_limiter,
+            Arc::new(Mutex::new(user_tokens)),
+        ),
         forum_minimal.loop_advance_dataflow_computation(),
     );
 
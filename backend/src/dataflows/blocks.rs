@@ -0,0 +1,171 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Threshold;
+use log::debug;
+
+use crate::dataflows::SharedArrangements;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "blocks",
+    consumes: &["Session", "Block", "Mute"],
+    produces: &["BlockedUser", "MutedUser"],
+    dataflow: blocks_dataflow,
+};
+
+/// Sends every session the users its user blocked or muted
+///
+/// What the viewer no longer sees is left out by the dataflows of the pages,
+/// see `visible_to_viewer` and `session_post_like_counts`.
+pub fn blocks_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    shared
+        .collection
+        .flat_map(|(_addr, (viewer, persisted))| match persisted {
+            Persisted::Block(user_id) => vec![(viewer, QueryResult::BlockedUser(user_id))],
+            Persisted::Mute(user_id) => vec![(viewer, QueryResult::MutedUser(user_id))],
+            _ => vec![],
+        })
+        .distinct()
+        .inspect(|v| debug!("blocks -- {:?}", v))
+        .join_core(shared.user_sessions(), |_viewer, query_result, addr| {
+            Some(vec![(*addr, query_result.clone())])
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflows::page_post_ids::posts_post_ids_dataflow;
+    use crate::dataflows::post_aggr::post_aggr_dataflow;
+    use crate::dataflows::post_total_likes::post_total_likes_dataflow;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    fn blocks_and_pages_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+        blocks_dataflow(shared)
+            .concat(&posts_post_ids_dataflow(shared))
+            .concat(&post_aggr_dataflow(shared))
+            .concat(&post_total_likes_dataflow(shared))
+    }
+
+    #[tokio::test]
+    pub async fn test_blocks() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            blocks_and_pages_dataflow,
+        );
+
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (6, Persisted::Post, 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (56, Persisted::Session, 1),
+                    (7, Persisted::Post, 1),
+                    (56, Persisted::PostLike(5, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::ViewPostsPage(0), 1)].into()))
+            .unwrap();
+
+        for _ in 0..3 {
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostAggregates(3, 2), 1),
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                        (QueryResult::PagePost(7, 0, 1), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                        (QueryResult::PostCreator(7, "56".into()), 1),
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                        (QueryResult::PostTotalLikes(7, 0), 1),
+                    ]
+                )
+            ))
+        );
+
+        // the viewer gets full pages without the posts and likes of the muted user
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::Mute(56), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    3,
+                    vec![
+                        (QueryResult::PostAggregates(3, 2), -1),
+                        (QueryResult::PagePost(7, 0, 1), -1),
+                        (QueryResult::PostCreator(7, "56".into()), -1),
+                        (QueryResult::PostTotalLikes(7, 0), -1),
+                        (QueryResult::PostAggregates(2, 1), 1),
+                        (QueryResult::PagePost(5, 0, 0), 1),
+                        (QueryResult::PostCreator(5, "55".into()), 1),
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::MutedUser(56), 1),
+                    ]
+                )
+            ))
+        );
+
+        // everyone else still sees every post
+        persisted_sender
+            .send((addr1, vec![(56, Persisted::ViewPostsPage(0), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    4,
+                    vec![
+                        (QueryResult::PostAggregates(3, 2), 1),
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                        (QueryResult::PagePost(7, 0, 1), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                        (QueryResult::PostCreator(7, "56".into()), 1),
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                        (QueryResult::PostTotalLikes(7, 0), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::operators::Threshold;
+use differential_dataflow::AsCollection;
+use log::debug;
+use timely::dataflow::operators::{Filter, Map};
+
+use crate::dataflows::SharedArrangements;
+use crate::operators::count_with_zeros::CountWithZeros;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "direct_messages",
+    consumes: &["Session", "Conversation", "DirectMessage", "ConversationRead"],
+    produces: &["Conversation", "DirectMessage", "ConversationUnread", "ConversationReadBy"],
+    dataflow: direct_messages_dataflow,
+};
+
+/// Sends conversations, their messages, unread counts and read receipts to the sessions of their participants
+///
+/// The sender of a message is recorded with it, `ForumMinimal::authorize_transaction` checks
+/// that it is the user of the session that sent it.
+/// A message is unread by a participant until they send a `ConversationRead` with a time
+/// at or after the time of the message, their own messages are never unread.
+pub fn direct_messages_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    let conversations = collection.flat_map(|(_addr, (conversation_id, persisted))| {
+        if let Persisted::Conversation(participants) = persisted {
+            vec![(conversation_id, participants)]
+        } else {
+            vec![]
+        }
+    });
+
+    // (conversation id, user id) of every participant
+    let participants = conversations
+        .flat_map(|(conversation_id, participants)| {
+            participants
+                .into_iter()
+                .map(move |user_id| (conversation_id, user_id))
+        })
+        .distinct();
+
+    // (conversation id, (user id, addr)) of every session of a participant
+    let participant_sessions = participants
+        .map(|(conversation_id, user_id)| (user_id, conversation_id))
+        .join_core(shared.user_sessions(), |user_id, conversation_id, addr| {
+            Some((*conversation_id, (*user_id, *addr)))
+        });
+
+    // (conversation id, (message id, sender, time, body)), messages are never removed
+    let messages = collection
+        .flat_map(|(_addr, (message_id, persisted))| {
+            if let Persisted::DirectMessage(conversation_id, sender, body) = persisted {
+                vec![(conversation_id, (message_id, sender, body))]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .filter(|(_, _time, diff)| *diff > 0)
+        .map(|((conversation_id, (message_id, sender, body)), time, diff)| {
+            ((conversation_id, (message_id, sender, time, body)), time, diff)
+        })
+        .as_collection()
+        .inspect(|v| debug!("direct messages -- {:?}", v));
+
+    // ((conversation id, user id), time) of the latest read receipt of every participant
+    let read_times = collection
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::ConversationRead(conversation_id, time) = persisted {
+                vec![((conversation_id, user_id), time)]
+            } else {
+                vec![]
+            }
+        })
+        .reduce(|_key, inputs, outputs| {
+            if let Some((time, _diff)) = inputs.iter().rev().find(|(_time, diff)| *diff > 0) {
+                outputs.push((**time, 1));
+            }
+        });
+
+    // a message is read if its time is at or before the read receipt
+    let unread_counts = messages
+        .join(&participants)
+        .filter(|(_conversation_id, ((_message_id, sender, _time, _body), user_id))| {
+            sender != user_id
+        })
+        .map(|(conversation_id, ((_message_id, _sender, time, _body), user_id))| {
+            ((conversation_id, user_id), (time, false))
+        })
+        .concat(&read_times.map(|(key, time)| (key, (time, true))))
+        .reduce(|_key, inputs, outputs| {
+            let read_time = inputs
+                .iter()
+                .filter(|((_time, is_read), diff)| *is_read && *diff > 0)
+                .map(|((time, _is_read), _diff)| *time)
+                .max();
+
+            for ((time, is_read), diff) in inputs {
+                if !is_read && read_time.is_none_or(|read_time| *time > read_time) {
+                    outputs.push(((), *diff));
+                }
+            }
+        })
+        .map(|(key, ())| key)
+        .count_with_zeros(&participants);
+
+    let conversation_results = conversations.join(&participant_sessions).map(
+        |(conversation_id, (participants, (_user_id, addr)))| {
+            vec![(addr, QueryResult::Conversation(conversation_id, participants))]
+        },
+    );
+
+    let message_results = messages.join(&participant_sessions).map(
+        |(conversation_id, ((message_id, sender, time, body), (_user_id, addr)))| {
+            vec![(
+                addr,
+                QueryResult::DirectMessage(conversation_id, message_id, sender, time, body),
+            )]
+        },
+    );
+
+    let unread_results = unread_counts
+        .map(|((conversation_id, user_id), count)| (user_id, (conversation_id, count)))
+        .join_core(
+            shared.user_sessions(),
+            |_user_id, (conversation_id, count), addr| {
+                Some(vec![(
+                    *addr,
+                    QueryResult::ConversationUnread(*conversation_id, *count),
+                )])
+            },
+        );
+
+    let read_receipt_results = read_times
+        .map(|((conversation_id, user_id), time)| (conversation_id, (user_id, time)))
+        .join(&participant_sessions)
+        .map(|(conversation_id, ((user_id, time), (_viewer, addr)))| {
+            vec![(
+                addr,
+                QueryResult::ConversationReadBy(conversation_id, user_id, time),
+            )]
+        });
+
+    conversation_results
+        .concat(&message_results)
+        .concat(&unread_results)
+        .concat(&read_receipt_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_direct_messages() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            direct_messages_dataflow,
+        );
+
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr1, vec![(56, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr2, vec![(57, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (7, Persisted::Conversation(vec![55, 56]), 1),
+                    (8, Persisted::DirectMessage(7, 55, "gg".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        for _ in 0..4 {
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        // only the participants receive the conversation, the sender has nothing unread
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    3,
+                    vec![
+                        (QueryResult::Conversation(7, vec![55, 56]), 1),
+                        (QueryResult::DirectMessage(7, 8, 55, 3, "gg".into()), 1),
+                        (QueryResult::ConversationUnread(7, 0), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    3,
+                    vec![
+                        (QueryResult::Conversation(7, vec![55, 56]), 1),
+                        (QueryResult::DirectMessage(7, 8, 55, 3, "gg".into()), 1),
+                        (QueryResult::ConversationUnread(7, 1), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // reading the conversation clears the unread count and is shown to both participants
+        persisted_sender
+            .send((addr1, vec![(56, Persisted::ConversationRead(7, 3), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (4, vec![(QueryResult::ConversationReadBy(7, 56, 3), 1)])
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    4,
+                    vec![
+                        (QueryResult::ConversationUnread(7, 1), -1),
+                        (QueryResult::ConversationUnread(7, 0), 1),
+                        (QueryResult::ConversationReadBy(7, 56, 3), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // a later message is unread again
+        persisted_sender
+            .send((addr0, vec![(9, Persisted::DirectMessage(7, 55, "wp".into()), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (5, vec![(QueryResult::DirectMessage(7, 9, 55, 5, "wp".into()), 1)])
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    5,
+                    vec![
+                        (QueryResult::ConversationUnread(7, 0), -1),
+                        (QueryResult::DirectMessage(7, 9, 55, 5, "wp".into()), 1),
+                        (QueryResult::ConversationUnread(7, 1), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // the messages keep their sender after the session of the sender ends
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::Session, -1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv().map(|(addr, _results)| addr),
+            Ok(addr0)
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, HOT_HALF_LIFE};
+use differential_dataflow::operators::JoinCore;
+use log::debug;
+
+use crate::dataflows::{ranked_post_page_results, ranked_post_scores, SharedArrangements};
+use crate::registry::DataflowModule;
+
+/// Likes decayed by age, without having to rescore every post when time advances:
+/// instead of old posts losing weight, newer posts get a head start of `HOT_HALF_LIFE`
+/// per doubling of likes, which results in the same order.
+pub fn hot_score(likes: u64, created: u64) -> u64 {
+    created + HOT_HALF_LIFE * (likes + 1).ilog2() as u64
+}
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "hot_posts",
+    consumes: &[
+        "ViewHotPostsPage", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostTitle", "PostBody", "PostLike", "Session", "Block", "Mute",
+    ],
+    produces: &["HotPagePost", "PostTitle", "PostBody", "PostTotalLikes", "PostMention"],
+    dataflow: hot_posts_dataflow,
+};
+
+pub fn hot_posts_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let post_scores = shared
+        .post_like_counts()
+        .join_core(shared.post_creation_times(), |post_id, likes, created| {
+            Some((*post_id, (hot_score(*likes, *created), *created)))
+        });
+    let ranked_posts =
+        ranked_post_scores(&post_scores).inspect(|v| debug!("hot posts -- {:?}", v));
+
+    ranked_post_page_results(
+        shared,
+        &ranked_posts,
+        |persisted| {
+            if let Persisted::ViewHotPostsPage(page) = persisted {
+                Some(*page)
+            } else {
+                None
+            }
+        },
+        QueryResult::HotPagePost,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_hot_posts() {
+        crate::init_logger();
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            hot_posts_dataflow,
+        );
+
+        // every user likes as the user of its own session
+        for (addr, user_id) in [(addr1, 56), (addr2, 57)] {
+            persisted_sender
+                .send((
+                    addr,
+                    vec![
+                        (user_id, Persisted::Session, 1),
+                        (user_id, Persisted::PostLike(5, true), 1),
+                    ].into(),
+                ))
+                .unwrap();
+        }
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (55, Persisted::ViewHotPostsPage(0), 1),
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (55, Persisted::PostLike(5, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostTotalLikes(5, 3), 1),
+                        (QueryResult::HotPagePost(5, 0, 0), 1),
+                    ]
+                )
+            ))
+        );
+
+        // a newer post without likes stays below the liked one
+        persisted_sender
+            .send((addr, vec![(6, Persisted::Post, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    3,
+                    vec![
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                        (QueryResult::HotPagePost(6, 0, 1), 1),
+                    ]
+                )
+            ))
+        );
+
+        // the liked post ages and is overtaken by a newer one
+        for _ in 0..HOT_HALF_LIFE * 2 {
+            persisted_sender.send((addr, vec![].into())).unwrap();
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+        persisted_sender
+            .send((addr, vec![(7, Persisted::Post, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    20,
+                    vec![
+                        (QueryResult::PostTotalLikes(6, 0), -1),
+                        (QueryResult::HotPagePost(5, 0, 0), -1),
+                        (QueryResult::HotPagePost(6, 0, 1), -1),
+                        (QueryResult::PostTotalLikes(7, 0), 1),
+                        (QueryResult::HotPagePost(5, 0, 1), 1),
+                        (QueryResult::HotPagePost(7, 0, 0), 1),
+                    ]
+                )
+            ))
+        );
+    }
+}
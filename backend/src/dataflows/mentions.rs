@@ -0,0 +1,164 @@
+use crate::forum_minimal::{OutputScopeCollection, QueryResult, MENTION_FEED_LENGTH};
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Threshold;
+use log::debug;
+
+use crate::dataflows::{session_post_field_results, SharedArrangements};
+use crate::operators::top_k::TopK;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "mentions",
+    consumes: &[
+        "Session", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostTitle", "PostBody", "PostLike", "Block", "Mute",
+    ],
+    produces: &["MentionFeedPost", "PostTitle", "PostBody", "PostTotalLikes", "PostMention"],
+    dataflow: mentions_dataflow,
+};
+
+// start of the span `markdown::render` writes around every `@name`
+const MENTION_START: &str = "<span data-mention=\"";
+
+/// Names of the mentions in the html of a post body
+///
+/// Only the spans of `markdown::render` are mentions, it escapes every `<` and `"` of the
+/// markdown and does not look for mentions in code, email addresses or urls.
+pub fn parse_mentions(html: &str) -> Vec<String> {
+    html.match_indices(MENTION_START)
+        .filter_map(|(index, _)| {
+            let name = &html[index + MENTION_START.len()..];
+            Some(name[..name.find('"')?].to_string())
+        })
+        .collect()
+}
+
+/// Sends every session the newest `MENTION_FEED_LENGTH` posts that mention its user
+///
+/// Mentions are resolved in `shared_post_mentions`, users are not fed their own posts.
+pub fn mentions_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    // (user id, (creation time, post id)) of the posts that mention a user
+    let mentioning_posts = shared
+        .post_creation_times()
+        .join_core(shared.post_mentions(), |post_id, created, user_id| {
+            Some((*post_id, (*user_id, *created)))
+        })
+        .join_core(shared.post_creators(), |post_id, (user_id, created), creator| {
+            (user_id != creator).then_some((*user_id, (*created, *post_id)))
+        })
+        .distinct()
+        .top_k(MENTION_FEED_LENGTH)
+        .map(|(user_id, (_rank, (created, post_id)))| (user_id, (created, post_id)))
+        .inspect(|v| debug!("mention feed -- {:?}", v));
+
+    let session_feed_posts = mentioning_posts
+        .join_core(shared.user_sessions(), |_user_id, (created, post_id), addr| {
+            Some((*addr, *created, *post_id))
+        });
+
+    let feed_results = session_feed_posts.map(|(addr, created, post_id)| {
+        vec![(addr, QueryResult::MentionFeedPost(post_id, created))]
+    });
+
+    let session_post_ids = session_feed_posts.map(|(addr, _created, post_id)| (post_id, addr));
+
+    feed_results.concat(&session_post_field_results(shared, &session_post_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::{ForumMinimal, Persisted};
+    use df_forum_frontend::markdown;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[test]
+    pub fn test_parse_mentions() {
+        assert_eq!(
+            parse_mentions(&markdown::render("@55 and @zerg_rush, not a@56 or @ or @@57")),
+            vec!["55", "zerg_rush", "57"]
+        );
+        // code is not rendered as a mention
+        assert_eq!(
+            parse_mentions(&markdown::render(
+                "`@55` and\n```\n@56\n```\n[@57](https://zerg.gg) @58"
+            )),
+            vec!["57", "58"]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_mentions() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            mentions_dataflow,
+        );
+
+        persisted_sender
+            .send((addr1, vec![(56, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostTitle("Zerg".into()), 1),
+                    (5, Persisted::PostBody("gg @56 and @57".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // only users that exist are mentioned
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), 1),
+                        (
+                            QueryResult::PostBody(
+                                5,
+                                "<p>gg <span data-mention=\"56\">@56</span> and \
+                                 <span data-mention=\"57\">@57</span></p>"
+                                    .into()
+                            ),
+                            1
+                        ),
+                        (QueryResult::PostMention(5, 56), 1),
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::MentionFeedPost(5, 1), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // deleted posts leave the feed
+        persisted_sender
+            .send((addr0, vec![(5, Persisted::PostDeleted, 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        let (addr, (_time, results)) = query_result_receiver.try_recv().unwrap();
+        assert_eq!(addr, addr1);
+        assert!(results.contains(&(QueryResult::MentionFeedPost(5, 1), -1)));
+        assert!(results.iter().all(|(_query_result, diff)| *diff < 0));
+    }
+}
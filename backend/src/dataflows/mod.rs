@@ -1,63 +1,801 @@
+pub mod blocks;
+pub mod direct_messages;
+pub mod hot_posts;
+pub mod mentions;
+pub mod moderation;
+pub mod moderation_queue;
+pub mod notifications;
 pub mod page_post_ids;
 pub mod post_aggr;
 pub mod post_liked_by_user;
+pub mod post_query;
 pub mod post_total_likes;
+pub mod rooms;
+pub mod search;
+pub mod spam;
+pub mod top_posts;
 pub mod user_post_count;
 pub mod user_like_count;
 
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::operators::Consolidate;
+use differential_dataflow::operators::Count;
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::JoinCore;
 use differential_dataflow::operators::Reduce;
+use differential_dataflow::operators::Threshold;
 use differential_dataflow::AsCollection;
+use differential_dataflow::ExchangeData;
+use df_forum_frontend::markdown;
+use std::cell::OnceCell;
 use std::net::SocketAddr;
+use timely::dataflow::operators::Filter;
 use timely::dataflow::operators::Map;
 
-use crate::forum_minimal::{Collection, InputFormat, Persisted, POSTS_PER_PAGE};
+use crate::operators::count_with_zeros::CountWithZeros;
+use crate::operators::only_latest::OnlyLatest;
+use crate::operators::top_k::TopK;
+use crate::forum_minimal::{
+    Arrangement, Collection, InputFormat, OutputScopeCollection, Persisted, QueryResult, Role,
+    ScopeCollection, POSTS_PER_PAGE, RANKED_POSTS_LIMIT, RANK_BUCKETS, REPORT_HIDE_THRESHOLD,
+};
 use log::debug;
 
-pub fn shared_post_pages<'a>(
+/// Projections of the input that several dataflows need, built and indexed once per worker
+///
+/// Every dataflow gets the same `SharedArrangements`, so joining against one of the
+/// arrangements reuses its index instead of building another copy of it.
+/// An arrangement is built the first time a dataflow asks for it,
+/// the ones no enabled dataflow uses are never built.
+pub struct SharedArrangements<'a> {
+    pub collection: ScopeCollection<'a>,
+    /// the inputs that are still within their wall-clock window,
+    /// see `forum_minimal::recent_window` and `ForumMinimal::expire_recent_records`
+    pub recent: ScopeCollection<'a>,
+    fields: OnceCell<Arrangement<'a, u64, Persisted>>,
+    session_user_ids: OnceCell<Collection<'a, (SocketAddr, u64)>>,
+    sessions: OnceCell<Arrangement<'a, SocketAddr, u64>>,
+    user_sessions: OnceCell<Arrangement<'a, u64, SocketAddr>>,
+    hidden_users: OnceCell<Collection<'a, (u64, u64)>>,
+    post_ranks: OnceCell<Collection<'a, (u64, u64)>>,
+    viewer_hidden_posts: OnceCell<Collection<'a, (u64, u64)>>,
+    post_creation_time_collection: OnceCell<Collection<'a, (u64, u64)>>,
+    post_creation_times: OnceCell<Arrangement<'a, u64, u64>>,
+    post_creator_collection: OnceCell<Collection<'a, (u64, u64)>>,
+    post_creators: OnceCell<Arrangement<'a, u64, u64>>,
+    post_like_counts: OnceCell<Arrangement<'a, u64, u64>>,
+    viewer_hidden_likes: OnceCell<Collection<'a, (u64, u64)>>,
+    post_report_count_collection: OnceCell<Collection<'a, (u64, u64)>>,
+    post_report_counts: OnceCell<Arrangement<'a, u64, u64>>,
+    post_mentions: OnceCell<Arrangement<'a, u64, u64>>,
+    post_body_collection: OnceCell<Collection<'a, (u64, String)>>,
+    post_bodies: OnceCell<Arrangement<'a, u64, String>>,
+}
+
+impl<'a> SharedArrangements<'a> {
+    pub fn new(collection: &ScopeCollection<'a>, recent: &ScopeCollection<'a>) -> Self {
+        SharedArrangements {
+            collection: collection.clone(),
+            recent: recent.clone(),
+            fields: OnceCell::new(),
+            session_user_ids: OnceCell::new(),
+            sessions: OnceCell::new(),
+            user_sessions: OnceCell::new(),
+            hidden_users: OnceCell::new(),
+            post_ranks: OnceCell::new(),
+            viewer_hidden_posts: OnceCell::new(),
+            post_creation_time_collection: OnceCell::new(),
+            post_creation_times: OnceCell::new(),
+            post_creator_collection: OnceCell::new(),
+            post_creators: OnceCell::new(),
+            post_like_counts: OnceCell::new(),
+            viewer_hidden_likes: OnceCell::new(),
+            post_report_count_collection: OnceCell::new(),
+            post_report_counts: OnceCell::new(),
+            post_mentions: OnceCell::new(),
+            post_body_collection: OnceCell::new(),
+            post_bodies: OnceCell::new(),
+        }
+    }
+
+    /// (id, persisted) of every input, e.g. the title and body of a post by post id
+    pub fn fields(&self) -> &Arrangement<'a, u64, Persisted> {
+        self.fields.get_or_init(|| {
+            self.collection
+                .map(|(_addr, (id, persisted))| (id, persisted))
+                .arrange_by_key()
+        })
+    }
+
+    fn session_user_ids(&self) -> &Collection<'a, (SocketAddr, u64)> {
+        self.session_user_ids.get_or_init(|| {
+            self.collection.flat_map(|(addr, (user_id, persisted))| {
+                if let Persisted::Session = persisted {
+                    vec![(addr, user_id)]
+                } else {
+                    vec![]
+                }
+            })
+        })
+    }
+
+    /// (session addr, user id)
+    pub fn sessions(&self) -> &Arrangement<'a, SocketAddr, u64> {
+        self.sessions
+            .get_or_init(|| self.session_user_ids().arrange_by_key())
+    }
+
+    /// (user id, session addr)
+    pub fn user_sessions(&self) -> &Arrangement<'a, u64, SocketAddr> {
+        self.user_sessions.get_or_init(|| {
+            self.session_user_ids()
+                .map(|(addr, user_id)| (user_id, addr))
+                .arrange_by_key()
+        })
+    }
+
+    /// (viewer, user id) of every user the viewer blocked or muted, see `shared_hidden_users`
+    pub fn hidden_users(&self) -> &Collection<'a, (u64, u64)> {
+        self.hidden_users
+            .get_or_init(|| shared_hidden_users(&self.collection))
+    }
+
+    /// (post id, rank) of the posts on the pages, newest first, see `shared_post_ranks`
+    pub fn post_ranks(&self) -> &Collection<'a, (u64, u64)> {
+        self.post_ranks
+            .get_or_init(|| shared_post_ranks(self.post_creation_time_collection()))
+    }
+
+    /// (viewer, post id) of the posts of every user the viewer blocked or muted,
+    /// see `shared_viewer_hidden_posts`
+    pub fn viewer_hidden_posts(&self) -> &Collection<'a, (u64, u64)> {
+        self.viewer_hidden_posts.get_or_init(|| {
+            shared_viewer_hidden_posts(self.post_creator_collection(), self.hidden_users())
+        })
+    }
+
+    fn post_creation_time_collection(&self) -> &Collection<'a, (u64, u64)> {
+        self.post_creation_time_collection.get_or_init(|| {
+            let removed_post_ids =
+                shared_removed_post_ids(&self.collection, self.post_report_count_collection());
+            shared_post_creation_times(&self.collection, &removed_post_ids)
+        })
+    }
+
+    /// (post id, creation time), see `shared_post_creation_times`
+    pub fn post_creation_times(&self) -> &Arrangement<'a, u64, u64> {
+        self.post_creation_times
+            .get_or_init(|| self.post_creation_time_collection().arrange_by_key())
+    }
+
+    fn post_creator_collection(&self) -> &Collection<'a, (u64, u64)> {
+        self.post_creator_collection
+            .get_or_init(|| shared_post_creators(&self.collection, self.sessions()))
+    }
+
+    /// (post id, user id), see `shared_post_creators`
+    pub fn post_creators(&self) -> &Arrangement<'a, u64, u64> {
+        self.post_creators
+            .get_or_init(|| self.post_creator_collection().arrange_by_key())
+    }
+
+    /// (post id, like count), see `shared_post_like_counts`
+    pub fn post_like_counts(&self) -> &Arrangement<'a, u64, u64> {
+        self.post_like_counts
+            .get_or_init(|| shared_post_like_counts(&self.collection).arrange_by_key())
+    }
+
+    /// (viewer, post id) of every like by a user the viewer blocked or muted,
+    /// see `shared_viewer_hidden_likes`
+    pub fn viewer_hidden_likes(&self) -> &Collection<'a, (u64, u64)> {
+        self.viewer_hidden_likes.get_or_init(|| {
+            shared_viewer_hidden_likes(&self.collection, self.hidden_users())
+        })
+    }
+
+    fn post_report_count_collection(&self) -> &Collection<'a, (u64, u64)> {
+        self.post_report_count_collection
+            .get_or_init(|| shared_post_report_counts(&self.collection))
+    }
+
+    /// (post id, report count), see `shared_post_report_counts`
+    pub fn post_report_counts(&self) -> &Arrangement<'a, u64, u64> {
+        self.post_report_counts
+            .get_or_init(|| self.post_report_count_collection().arrange_by_key())
+    }
+
+    /// (post id, user id), see `shared_post_mentions`
+    pub fn post_mentions(&self) -> &Arrangement<'a, u64, u64> {
+        self.post_mentions.get_or_init(|| {
+            shared_post_mentions(&self.collection, self.post_body_collection()).arrange_by_key()
+        })
+    }
+
+    fn post_body_collection(&self) -> &Collection<'a, (u64, String)> {
+        self.post_body_collection
+            .get_or_init(|| shared_post_bodies(&self.collection))
+    }
+
+    /// (post id, body rendered to html), see `shared_post_bodies`
+    pub fn post_bodies(&self) -> &Arrangement<'a, u64, String> {
+        self.post_bodies
+            .get_or_init(|| self.post_body_collection().arrange_by_key())
+    }
+
+    /// Names of the shared arrangements that were built so far
+    pub fn built(&self) -> Vec<&'static str> {
+        [
+            ("fields", self.fields.get().is_some()),
+            ("sessions", self.sessions.get().is_some()),
+            ("user_sessions", self.user_sessions.get().is_some()),
+            ("hidden_users", self.hidden_users.get().is_some()),
+            ("post_ranks", self.post_ranks.get().is_some()),
+            ("viewer_hidden_posts", self.viewer_hidden_posts.get().is_some()),
+            ("post_creation_times", self.post_creation_times.get().is_some()),
+            ("post_creators", self.post_creators.get().is_some()),
+            ("post_like_counts", self.post_like_counts.get().is_some()),
+            ("viewer_hidden_likes", self.viewer_hidden_likes.get().is_some()),
+            ("post_report_counts", self.post_report_counts.get().is_some()),
+            ("post_mentions", self.post_mentions.get().is_some()),
+            ("post_bodies", self.post_bodies.get().is_some()),
+        ]
+        .into_iter()
+        .filter(|(_name, is_built)| *is_built)
+        .map(|(name, _is_built)| name)
+        .collect()
+    }
+}
+
+/// (post id, number of users that reported it) of every post a moderator has not reviewed yet
+pub fn shared_post_report_counts<'a>(
+    collection: &Collection<'a, InputFormat>,
+) -> Collection<'a, (u64, u64)> {
+    let reviewed_post_ids = collection
+        .flat_map(|(_addr, (post_id, persisted))| {
+            if let Persisted::PostReviewed = persisted {
+                vec![post_id]
+            } else {
+                vec![]
+            }
+        })
+        .distinct();
+
+    collection
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::Report(post_id, _reason) = persisted {
+                vec![(post_id, user_id)]
+            } else {
+                vec![]
+            }
+        })
+        .distinct()
+        .antijoin(&reviewed_post_ids)
+        .map(|(post_id, _user_id)| post_id)
+        .count()
+        .map(|(post_id, count)| (post_id, count as u64))
+}
+
+/// The number of reports that hides a post, `REPORT_HIDE_THRESHOLD` until an admin changes it
+///
+/// The threshold set last applies, earlier ones do not have to be retracted.
+pub fn shared_report_hide_threshold<'a>(
+    collection: &Collection<'a, InputFormat>,
+) -> Collection<'a, u64> {
+    let configured = collection
+        .flat_map(|(_addr, (_id, persisted))| {
+            if let Persisted::ReportHideThreshold(threshold) = persisted {
+                vec![((), threshold)]
+            } else {
+                vec![]
+            }
+        })
+        .only_latest();
+
+    let default = collection
+        .map(|_| ((), REPORT_HIDE_THRESHOLD))
+        .distinct()
+        .antijoin(&configured.map(|((), _threshold)| ()).distinct());
+
+    configured
+        .concat(&default)
+        .map(|((), threshold)| threshold)
+}
+
+/// Ids of the posts that are soft deleted, hidden by a moderator
+/// or reported by at least as many users as the report threshold
+pub fn shared_removed_post_ids<'a>(
     collection: &Collection<'a, InputFormat>,
-) -> Collection<'a, (SocketAddr, u64, u64, u64)> {
-    let result = collection
-        .flat_map(|(addr, (post_id, persisted))| {
+    post_report_counts: &Collection<'a, (u64, u64)>,
+) -> Collection<'a, u64> {
+    let reported_post_ids = post_report_counts
+        .map(|(post_id, count)| ((), (post_id, count)))
+        .join(&shared_report_hide_threshold(collection).map(|threshold| ((), threshold)))
+        .flat_map(|((), ((post_id, count), threshold))| {
+            if count >= threshold {
+                vec![post_id]
+            } else {
+                vec![]
+            }
+        });
+
+    collection
+        .flat_map(|(_addr, (post_id, persisted))| match persisted {
+            Persisted::PostDeleted | Persisted::PostHidden(_) => vec![post_id],
+            _ => vec![],
+        })
+        .concat(&reported_post_ids)
+        .distinct()
+}
+
+/// (viewer, user id) of every user the viewer blocked or muted
+pub fn shared_hidden_users<'a>(
+    collection: &Collection<'a, InputFormat>,
+) -> Collection<'a, (u64, u64)> {
+    collection
+        .flat_map(|(_addr, (viewer, persisted))| match persisted {
+            Persisted::Block(user_id) | Persisted::Mute(user_id) => vec![(viewer, user_id)],
+            _ => vec![],
+        })
+        .distinct()
+}
+
+/// User ids of the viewers that blocked or muted anyone
+pub fn viewer_ids<'a>(shared: &SharedArrangements<'a>) -> Collection<'a, u64> {
+    shared
+        .hidden_users()
+        .map(|(viewer, _user_id)| viewer)
+        .distinct()
+}
+
+/// Adds the viewer to every value of a session: the user id if the user of the session
+/// blocked or muted anyone, `None` for sessions that see what everyone sees
+pub fn session_viewers<'a, D: ExchangeData>(
+    shared: &SharedArrangements<'a>,
+    session_values: &Collection<'a, (SocketAddr, D)>,
+) -> Collection<'a, (SocketAddr, (D, Option<u64>))> {
+    let hiding = session_values
+        .join_core(shared.sessions(), |addr, value, user_id| {
+            Some((*user_id, (*addr, value.clone())))
+        })
+        .semijoin(&viewer_ids(shared))
+        .map(|(user_id, (addr, value))| (addr, (value, Some(user_id))));
+
+    let others = session_values
+        .antijoin(&hiding.map(|(addr, _value)| addr).distinct())
+        .map(|(addr, value)| (addr, (value, None)));
+
+    hiding.concat(&others)
+}
+
+/// (post id, rank) of every post that is not deleted or hidden, the newest post has rank 0
+///
+/// The creation time is kept when a post is restored, so it is back at its old position.
+pub fn shared_post_ranks<'a>(
+    post_creation_times: &Collection<'a, (u64, u64)>,
+) -> Collection<'a, (u64, u64)> {
+    post_creation_times
+        .map(|(post_id, created)| ((), (created, post_id)))
+        .top_k(usize::MAX)
+        .map(|((), (rank, (_created, post_id)))| (post_id, rank))
+        .inspect(|v| debug!("post ranks -- {:?}", v))
+}
+
+/// (viewer, post id) of the posts of every user the viewer blocked or muted
+/// (post id, rank) of the `RANKED_POSTS_LIMIT` posts with the highest (score, creation time)
+///
+/// One `top_k` over every post would sort all of them again whenever a score changes,
+/// instead the posts are ranked within `RANK_BUCKETS` buckets first: a change sorts one bucket
+/// and the final ranking sorts at most `RANK_BUCKETS * RANKED_POSTS_LIMIT` posts.
+pub fn ranked_post_scores<'a>(
+    post_scores: &Collection<'a, (u64, (u64, u64))>,
+) -> Collection<'a, (u64, u64)> {
+    post_scores
+        .map(|(post_id, (score, created))| (post_id % RANK_BUCKETS, (score, created, post_id)))
+        .top_k(RANKED_POSTS_LIMIT)
+        .map(|(_bucket, (_bucket_rank, scored_post))| ((), scored_post))
+        .top_k(RANKED_POSTS_LIMIT)
+        .map(|((), (rank, (_score, _created, post_id)))| (post_id, rank))
+}
+
+pub fn shared_viewer_hidden_posts<'a>(
+    post_creators: &Collection<'a, (u64, u64)>,
+    hidden_users: &Collection<'a, (u64, u64)>,
+) -> Collection<'a, (u64, u64)> {
+    post_creators
+        .map(|(post_id, user_id)| (user_id, post_id))
+        .join(&hidden_users.map(|(viewer, user_id)| (user_id, viewer)))
+        .map(|(_user_id, (post_id, viewer))| (viewer, post_id))
+        .distinct()
+}
+
+/// The ranks on a page of a listing for a viewer that hid the posts with `hidden_ranks`
+/// (ascending), with the rank of every post on the viewer's page
+///
+/// The hidden posts are skipped, so the pages of the viewer stay full.
+pub fn viewer_page_ranks(page: u64, hidden_ranks: &[u64]) -> Vec<(u64, u64)> {
+    let first = page * POSTS_PER_PAGE as u64;
+    let mut hidden = hidden_ranks.iter().peekable();
+    let mut rank = first;
+
+    // every hidden post up to the first post of the page moves it one rank further
+    while hidden.next_if(|hidden_rank| **hidden_rank <= rank).is_some() {
+        rank += 1;
+    }
+
+    let mut ranks = Vec::new();
+    while ranks.len() < POSTS_PER_PAGE {
+        if hidden.next_if_eq(&&rank).is_none() {
+            ranks.push((rank, first + ranks.len() as u64));
+        }
+        rank += 1;
+    }
+
+    ranks
+}
+
+/// (session addr, (page, post id, rank)) of the posts on the pages sessions view
+/// (session addr, page) of a ranked listing (post id, rank), rank 0 comes first
+///
+/// Sessions whose user blocked or muted nobody read the pages of the listing.
+/// Viewers that hid posts get pages of their own without them, the ranks of their hidden posts
+/// are enough to find the posts on a page (see `viewer_page_ranks`),
+/// so the pages of a viewer cost as much as the posts the viewer hid.
+pub fn session_listing_pages<'a>(
+    shared: &SharedArrangements<'a>,
+    ranked_posts: &Collection<'a, (u64, u64)>,
+    session_pages: &Collection<'a, (SocketAddr, u64)>,
+) -> Collection<'a, (SocketAddr, (u64, u64, u64))> {
+    let viewer_pages = session_viewers(shared, session_pages);
+    let posts_by_rank = ranked_posts.map(|(post_id, rank)| (rank, post_id));
+
+    let shared_page_posts = viewer_pages
+        .flat_map(|(addr, (page, viewer))| viewer.is_none().then_some((page, addr)))
+        .join(&posts_by_rank.map(|(rank, post_id)| {
+            (rank / POSTS_PER_PAGE as u64, (post_id, rank))
+        }))
+        .map(|(page, (addr, (post_id, rank)))| (addr, (page, post_id, rank)));
+
+    // ((viewer, page), session addr) of the sessions of viewers that hid posts
+    let hiding_pages = viewer_pages
+        .flat_map(|(addr, (page, viewer))| viewer.map(|viewer| ((viewer, page), addr)));
+    let viewed_pages = hiding_pages.map(|(viewer_page, _addr)| viewer_page).distinct();
+
+    // (viewer, rank) of the hidden posts of the listing
+    let hidden_ranks = shared
+        .viewer_hidden_posts()
+        .semijoin(&viewed_pages.map(|(viewer, _page)| viewer).distinct())
+        .map(|(viewer, post_id)| (post_id, viewer))
+        .join(ranked_posts)
+        .map(|(_post_id, (viewer, rank))| (viewer, rank));
+
+    // pages without hidden posts on or before them are ranked too
+    let viewer_page_posts = viewed_pages
+        .map(|viewer_page| (viewer_page, None))
+        .concat(
+            &viewed_pages
+                .join(&hidden_ranks)
+                .map(|(viewer, (page, rank))| ((viewer, page), Some(rank))),
+        )
+        .reduce(|(_viewer, page), inputs, outputs| {
+            // inputs are sorted, `None` first and the hidden ranks ascending
+            let hidden_ranks: Vec<u64> =
+                inputs.iter().filter_map(|(rank, _diff)| **rank).collect();
+
+            for rank in viewer_page_ranks(*page, &hidden_ranks) {
+                outputs.push((rank, 1));
+            }
+        })
+        .map(|(viewer_page, (rank, viewer_rank))| (rank, (viewer_page, viewer_rank)))
+        .join(&posts_by_rank)
+        .map(|(_rank, ((viewer_page, viewer_rank), post_id))| {
+            (viewer_page, (post_id, viewer_rank))
+        })
+        .join(&hiding_pages)
+        .map(|((_viewer, page), ((post_id, rank), addr))| (addr, (page, post_id, rank)));
+
+    shared_page_posts.concat(&viewer_page_posts)
+}
+
+/// Leaves out the posts the viewer hid by blocking or muting their creators,
+/// viewer `None` sees every post
+pub fn visible_to_viewer<'a, D: ExchangeData>(
+    shared: &SharedArrangements<'a>,
+    viewer_posts: &Collection<'a, ((Option<u64>, u64), D)>,
+) -> Collection<'a, ((Option<u64>, u64), D)> {
+    viewer_posts.antijoin(
+        &shared
+            .viewer_hidden_posts()
+            .map(|(viewer, post_id)| (Some(viewer), post_id)),
+    )
+}
+
+/// (post id, creation time) of every post that was not deleted, soft deleted or hidden
+pub fn shared_post_creation_times<'a>(
+    collection: &Collection<'a, InputFormat>,
+    removed_post_ids: &Collection<'a, u64>,
+) -> Collection<'a, (u64, u64)> {
+    collection
+        .flat_map(|(_addr, (post_id, persisted))| {
             if let Persisted::Post = persisted {
-                vec![(post_id, addr)]
+                vec![post_id]
             } else {
                 vec![]
             }
         })
         .inner
-        .map(|((post_id, addr), time, diff)| (((), (time, addr, post_id)), time, diff))
+        .map(|(post_id, time, diff)| ((post_id, time), time, diff))
         .as_collection()
-        // reduce will automatically order by time (left-most value in the tuple)
-        .reduce(|(), inputs, outputs| {
-            debug!("inputs: {:?}", inputs);
+        .antijoin(removed_post_ids)
+        .reduce(|_post_id, inputs, outputs| {
+            let total: isize = inputs.iter().map(|(_time, diff)| diff).sum();
+
+            if total > 0 {
+                if let Some((time, _diff)) = inputs.iter().find(|(_time, diff)| *diff > 0) {
+                    outputs.push((**time, 1));
+                }
+            }
+        })
+}
 
-            let mut visible = Vec::new();
+/// (post id, user id) of every post whose creator had a session when creating it
+pub fn shared_post_creators<'a>(
+    collection: &Collection<'a, InputFormat>,
+    sessions: &Arrangement<'a, SocketAddr, u64>,
+) -> Collection<'a, (u64, u64)> {
+    let post_creator_addrs = collection
+        .flat_map(|(creator_addr, (post_id, persisted))| {
+            if let Persisted::Post = persisted {
+                vec![(creator_addr, post_id)]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .filter(|(_, _time, diff)| *diff > 0)
+        .as_collection();
 
-            for ((time, addr, add_post_id), diff) in inputs {
-                if *diff > 0 {
-                    let mut found_removal = false;
+    post_creator_addrs.join_core(sessions, |_creator_addr, post_id, user_id| {
+        Some((*post_id, *user_id))
+    })
+}
 
-                    for ((_time, _, rem_post_id), diff) in inputs {
-                        if *diff < 0 && add_post_id == rem_post_id {
-                            found_removal = true;
-                        }
-                    }
+/// (post id, like count) of every post that was not deleted, posts without likes count 0
+pub fn shared_post_like_counts<'a>(
+    collection: &Collection<'a, InputFormat>,
+) -> Collection<'a, (u64, u64)> {
+    let posts = collection.flat_map(|(_addr, (post_id, persisted))| {
+        if let Persisted::Post = persisted {
+            vec![post_id]
+        } else {
+            vec![]
+        }
+    });
+
+    let likes = collection.flat_map(|(_addr, (_user_id, persisted))| {
+        if let Persisted::PostLike(post_id, true) = persisted {
+            vec![post_id]
+        } else {
+            vec![]
+        }
+    });
+
+    // unliking is sent as an additional `PostLike(post_id, false)`
+    let unlikes = collection.flat_map(|(_addr, (_user_id, persisted))| {
+        if let Persisted::PostLike(post_id, false) = persisted {
+            vec![post_id]
+        } else {
+            vec![]
+        }
+    });
+
+    likes.concat(&unlikes.negate()).count_with_zeros(&posts)
+}
+
+/// (viewer, post id) of every like by a user the viewer blocked or muted
+pub fn shared_viewer_hidden_likes<'a>(
+    collection: &Collection<'a, InputFormat>,
+    hidden_users: &Collection<'a, (u64, u64)>,
+) -> Collection<'a, (u64, u64)> {
+    // (user id, post id), unliking is sent as an additional `PostLike(post_id, false)`
+    let likes = collection.flat_map(|(_addr, (user_id, persisted))| {
+        if let Persisted::PostLike(post_id, true) = persisted {
+            vec![(user_id, post_id)]
+        } else {
+            vec![]
+        }
+    });
+    let unlikes = collection.flat_map(|(_addr, (user_id, persisted))| {
+        if let Persisted::PostLike(post_id, false) = persisted {
+            vec![(user_id, post_id)]
+        } else {
+            vec![]
+        }
+    });
+
+    likes
+        .concat(&unlikes.negate())
+        .join(&hidden_users.map(|(viewer, user_id)| (user_id, viewer)))
+        .map(|(_user_id, (post_id, viewer))| (viewer, post_id))
+}
 
-                    if !found_removal {
-                        visible.push((addr, add_post_id, time));
-                    }
+/// (session addr, post id, like count) of the posts a session sees (post id, session addr)
+///
+/// The likes of the users the viewer blocked or muted are not counted.
+pub fn session_post_like_counts<'a>(
+    shared: &SharedArrangements<'a>,
+    session_post_ids: &Collection<'a, (u64, SocketAddr)>,
+) -> Collection<'a, (SocketAddr, u64, u64)> {
+    let viewer_posts = session_viewers(
+        shared,
+        &session_post_ids.map(|(post_id, session_addr)| (session_addr, post_id)),
+    )
+    .map(|(session_addr, (post_id, viewer))| ((viewer, post_id), session_addr));
+
+    let hidden_like_counts = shared
+        .viewer_hidden_likes()
+        .map(|(viewer, post_id)| (Some(viewer), post_id))
+        .count_with_zeros(&viewer_posts.map(|(viewer_post, _session_addr)| viewer_post));
+
+    viewer_posts
+        .join(&hidden_like_counts)
+        .map(|((_viewer, post_id), (session_addr, hidden))| (post_id, (session_addr, hidden)))
+        .join_core(shared.post_like_counts(), |post_id, (session_addr, hidden), likes| {
+            Some((*session_addr, *post_id, likes.saturating_sub(*hidden)))
+        })
+}
+
+/// Sends the posts of a ranked listing (post id, rank) to the sessions viewing one of its pages
+///
+/// `view_page` picks the page out of the listing's subscription record,
+/// `page_post` builds the result that places a post on the page.
+/// Posts moving within a page are retracted and sent again with their new rank,
+/// posts leaving the page are retracted together with their fields.
+pub fn ranked_post_page_results<'a>(
+    shared: &SharedArrangements<'a>,
+    ranked_posts: &Collection<'a, (u64, u64)>,
+    view_page: fn(&Persisted) -> Option<u64>,
+    page_post: fn(u64, u64, u64) -> QueryResult,
+) -> OutputScopeCollection<'a> {
+    let session_pages = shared
+        .collection
+        .flat_map(move |(addr, (_id, persisted))| {
+            view_page(&persisted)
+                .map(|page| vec![(addr, page)])
+                .unwrap_or_default()
+        })
+        .reduce(|_addr, inputs, outputs| {
+            let mut page = None;
+
+            for (view_page, diff) in inputs {
+                if *diff > 0 {
+                    page = Some(**view_page);
                 }
             }
 
-            for (index, (addr, post_id, time)) in visible.into_iter().rev().enumerate() {
-                let page = (index / POSTS_PER_PAGE) as u64;
-                outputs.push(((*addr, *post_id, page, *time), 1));
+            if let Some(page) = page {
+                outputs.push((page, 1));
+            }
+        });
+
+    // viewers that blocked or muted users get pages without their posts
+    let session_posts =
+        session_listing_pages(shared, ranked_posts, &session_pages).consolidate();
+
+    let session_post_results = session_posts.map(move |(session_addr, (page, post_id, rank))| {
+        vec![(session_addr, page_post(post_id, page, rank))]
+    });
+
+    // only changes when a post enters or leaves the page, not when its rank changes
+    let session_post_ids = session_posts
+        .map(|(session_addr, (_page, post_id, _rank))| (post_id, session_addr))
+        .consolidate();
+
+    session_post_results.concat(&session_post_field_results(shared, &session_post_ids))
+}
+
+/// (session addr, role) of every session whose user has a role
+pub fn session_roles<'a>(shared: &SharedArrangements<'a>) -> Collection<'a, (SocketAddr, Role)> {
+    shared
+        .collection
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::UserRole(role) = persisted {
+                vec![(user_id, role)]
+            } else {
+                vec![]
             }
         })
-        .map(|((), val)| val)
-        .inspect(|v| debug!("post pages -- {:?}", v));
+        .distinct()
+        .join_core(shared.user_sessions(), |_user_id, role, addr| {
+            Some((*addr, *role))
+        })
+}
 
-    result
+/// (post id, user id) of every user a post body mentions with an `@name`,
+/// see `mentions::parse_mentions`
+///
+/// Users have no names apart from their id yet, a name is the id of a user that had a session.
+pub fn shared_post_mentions<'a>(
+    collection: &Collection<'a, InputFormat>,
+    post_bodies: &Collection<'a, (u64, String)>,
+) -> Collection<'a, (u64, u64)> {
+    let user_names = collection
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::Session = persisted {
+                vec![(user_id.to_string(), user_id)]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .filter(|(_, _time, diff)| *diff > 0)
+        .as_collection()
+        .distinct();
+
+    post_bodies
+        .flat_map(|(post_id, html)| {
+            mentions::parse_mentions(&html)
+                .into_iter()
+                .map(move |name| (name, post_id))
+        })
+        .join(&user_names)
+        .map(|(_name, (post_id, user_id))| (post_id, user_id))
+        // a user mentioned twice is mentioned once
+        .distinct()
+}
+
+/// (post id, html) of every post body, the markdown of the body is rendered once for all sessions
+///
+/// The html is sanitized by `markdown::render`, sessions can set it as the inner html of the body.
+pub fn shared_post_bodies<'a>(
+    collection: &Collection<'a, InputFormat>,
+) -> Collection<'a, (u64, String)> {
+    collection.flat_map(|(_addr, (post_id, persisted))| {
+        if let Persisted::PostBody(body) = persisted {
+            vec![(post_id, markdown::render(&body))]
+        } else {
+            vec![]
+        }
+    })
+}
+
+/// Title, body and like count of every post a session sees (post id, session addr)
+pub fn session_post_field_results<'a>(
+    shared: &SharedArrangements<'a>,
+    session_post_ids: &Collection<'a, (u64, SocketAddr)>,
+) -> OutputScopeCollection<'a> {
+    let session_post_field_results = session_post_ids.join_core(
+        shared.fields(),
+        |id, session_addr, persisted| match persisted {
+            Persisted::PostTitle(title) => Some(vec![(
+                *session_addr,
+                QueryResult::PostTitle(*id, title.clone()),
+            )]),
+            _ => None,
+        },
+    );
+
+    let session_post_body_results =
+        session_post_ids.join_core(shared.post_bodies(), |id, session_addr, body| {
+            Some(vec![(*session_addr, QueryResult::PostBody(*id, body.clone()))])
+        });
+
+    let session_post_like_results = session_post_like_counts(shared, session_post_ids).map(
+        |(session_addr, post_id, likes)| {
+            vec![(session_addr, QueryResult::PostTotalLikes(post_id, likes))]
+        },
+    );
+
+    let session_post_mention_results = session_post_ids.join_core(
+        shared.post_mentions(),
+        |post_id, session_addr, user_id| {
+            Some(vec![(*session_addr, QueryResult::PostMention(*post_id, *user_id))])
+        },
+    );
+
+    session_post_field_results
+        .concat(&session_post_body_results)
+        .concat(&session_post_like_results)
+        .concat(&session_post_mention_results)
 }
 
 #[cfg(test)]
@@ -66,7 +804,7 @@ mod tests {
     use timely::dataflow::operators::ToStream;
 
     #[tokio::test]
-    pub async fn test_shared_post_pages() {
+    pub async fn test_shared_post_ranks() {
         crate::init_logger();
         let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
 
@@ -80,18 +818,62 @@ mod tests {
             .to_stream(scope)
             .as_collection();
 
-            shared_post_pages(&stream).inspect_batch(move |_time, v| {
-                assert_eq!(
-                    v,
-                    vec![
-                        ((addr0, 5, 1, 0), 0, 1),
-                        ((addr0, 5, 1, 0), 1, -1),
-                        ((addr0, 6, 0, 0), 0, 1),
-                        ((addr0, 7, 0, 0), 0, 1)
-                    ]
-                );
-                debug!("got val {:?}", v);
+            let removed_post_ids =
+                shared_removed_post_ids(&stream, &shared_post_report_counts(&stream));
+
+            let post_creation_times = shared_post_creation_times(&stream, &removed_post_ids);
+
+            shared_post_ranks(&post_creation_times)
+                .inspect_batch(move |_time, v| {
+                    assert_eq!(
+                        v,
+                        vec![
+                            ((7, 0), 0, 1),
+                            ((6, 1), 0, 1),
+                            ((5, 2), 0, 1),
+                            ((5, 2), 1, -1)
+                        ]
+                    );
+                    debug!("got val {:?}", v);
+                });
+        });
+    }
+
+    #[test]
+    pub fn test_ranked_post_scores() {
+        let post_scores: Vec<_> = (0..400)
+            .map(|post_id| (post_id, ((post_id * 37) % 101, post_id)))
+            .collect();
+
+        // every post sorted at once
+        let mut expected: Vec<_> = post_scores.clone();
+        expected.sort_by_key(|(post_id, (score, created))| {
+            std::cmp::Reverse((*score, *created, *post_id))
+        });
+        let expected: Vec<_> = expected
+            .into_iter()
+            .take(RANKED_POSTS_LIMIT)
+            .enumerate()
+            .map(|(rank, (post_id, _score))| (post_id, rank as u64))
+            .collect();
+
+        let ranked = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ranked0 = ranked.clone();
+        timely::example(move |scope| {
+            let post_scores = post_scores
+                .into_iter()
+                .map(|post_score| (post_score, 0, 1))
+                .to_stream(scope)
+                .as_collection();
+
+            ranked_post_scores(&post_scores).inspect(move |(post_rank, _time, diff)| {
+                assert_eq!(*diff, 1);
+                ranked0.lock().unwrap().push(*post_rank);
             });
         });
+
+        let mut ranked = ranked.lock().unwrap().clone();
+        ranked.sort_by_key(|(_post_id, rank)| *rank);
+        assert_eq!(ranked, expected);
     }
 }
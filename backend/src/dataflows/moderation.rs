@@ -0,0 +1,163 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::Threshold;
+use log::debug;
+
+use crate::dataflows::{session_post_field_results, session_roles, SharedArrangements};
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "moderation",
+    consumes: &[
+        "Session", "UserRole", "Post", "PostDeleted", "PostHidden", "PostTitle", "PostBody",
+        "PostLike", "Block", "Mute",
+    ],
+    produces: &["UserRole", "HiddenPost", "PostTitle", "PostBody", "PostTotalLikes", "PostMention"],
+    dataflow: moderation_dataflow,
+};
+
+/// Sends every session the roles of its user,
+/// and moderators the posts that are soft deleted or hidden together with the reason
+pub fn moderation_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    let session_roles = session_roles(shared);
+
+    let session_role_results =
+        session_roles.map(|(addr, role)| vec![(addr, QueryResult::UserRole(role))]);
+
+    let moderator_sessions = session_roles
+        .filter(|(_addr, role)| role.can_moderate())
+        .map(|(addr, _role)| ((), addr))
+        .distinct();
+
+    let posts = collection
+        .flat_map(|(_addr, (post_id, persisted))| {
+            if let Persisted::Post = persisted {
+                vec![post_id]
+            } else {
+                vec![]
+            }
+        })
+        .distinct();
+
+    // reasons of removed posts that still exist
+    let post_removals = collection
+        .flat_map(|(_addr, (post_id, persisted))| match persisted {
+            Persisted::PostDeleted => vec![(post_id, None)],
+            Persisted::PostHidden(reason) => vec![(post_id, Some(reason))],
+            _ => vec![],
+        })
+        .distinct()
+        .semijoin(&posts)
+        .inspect(|v| debug!("post removals -- {:?}", v));
+
+    let moderator_removals = post_removals
+        .map(|removal| ((), removal))
+        .join(&moderator_sessions)
+        .map(|((), ((post_id, reason), addr))| (post_id, reason, addr));
+
+    let hidden_post_results = moderator_removals
+        .map(|(post_id, reason, addr)| vec![(addr, QueryResult::HiddenPost(post_id, reason))]);
+
+    let moderator_post_ids = moderator_removals
+        .map(|(post_id, _reason, addr)| (post_id, addr))
+        .distinct();
+
+    session_role_results
+        .concat(&hidden_post_results)
+        .concat(&session_post_field_results(shared, &moderator_post_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::{ForumMinimal, Role};
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_moderation() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            moderation_dataflow,
+        );
+        forum_minimal.grant_role(1, Role::Moderator);
+
+        persisted_sender
+            .send((addr0, vec![(1, Persisted::Session, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr0, (0, vec![(QueryResult::UserRole(Role::Moderator), 1)]))),
+        );
+
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostTitle("Zerg".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((addr0, vec![(5, Persisted::PostHidden("spam".into()), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // only the moderator sees the hidden post
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), 1),
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::HiddenPost(5, Some("spam".into())), 1),
+                    ]
+                )
+            )),
+        );
+
+        persisted_sender
+            .send((addr0, vec![(5, Persisted::PostHidden("spam".into()), -1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    3,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), -1),
+                        (QueryResult::PostTotalLikes(5, 0), -1),
+                        (QueryResult::HiddenPost(5, Some("spam".into())), -1),
+                    ]
+                )
+            )),
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, RANKED_POSTS_LIMIT};
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::operators::Threshold;
+use differential_dataflow::AsCollection;
+use log::debug;
+use timely::dataflow::operators::Map;
+
+use crate::dataflows::{session_post_field_results, session_roles, SharedArrangements};
+use crate::operators::top_k::TopK;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "moderation_queue",
+    consumes: &[
+        "ViewModerationQueue", "Session", "UserRole", "Post", "PostDeleted", "PostHidden",
+        "PostReviewed", "Report", "PostTitle", "PostBody", "PostLike", "Block", "Mute",
+    ],
+    produces: &[
+        "ModerationQueuePost", "PostReport", "PostTitle", "PostBody", "PostTotalLikes",
+        "PostMention",
+    ],
+    dataflow: moderation_queue_dataflow,
+};
+
+/// Sends moderators that view the moderation queue every reported post that is not resolved yet,
+/// most reports first and recently reported posts first among those, together with the reasons
+///
+/// A post leaves the queue once a moderator reviews or hides it, or it is deleted.
+pub fn moderation_queue_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    let moderator_sessions = session_roles(shared)
+        .filter(|(_addr, role)| role.can_moderate())
+        .map(|(addr, _role)| (addr, ()));
+
+    let queue_sessions = collection
+        .flat_map(|(addr, (_id, persisted))| {
+            if let Persisted::ViewModerationQueue = persisted {
+                vec![(addr, ())]
+            } else {
+                vec![]
+            }
+        })
+        .distinct()
+        .semijoin(&moderator_sessions.distinct().map(|(addr, ())| addr))
+        .map(|(addr, ())| ((), addr));
+
+    let posts = collection
+        .flat_map(|(_addr, (post_id, persisted))| {
+            if let Persisted::Post = persisted {
+                vec![post_id]
+            } else {
+                vec![]
+            }
+        })
+        .distinct();
+
+    let resolved_post_ids = collection
+        .flat_map(|(_addr, (post_id, persisted))| match persisted {
+            Persisted::PostDeleted | Persisted::PostHidden(_) => vec![post_id],
+            _ => vec![],
+        })
+        .distinct();
+
+    // (post id, dataflow time of its latest report)
+    let latest_reports = collection
+        .flat_map(|(_addr, (_user_id, persisted))| {
+            if let Persisted::Report(post_id, _reason) = persisted {
+                vec![post_id]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .map(|(post_id, time, diff)| ((post_id, time), time, diff))
+        .as_collection()
+        .reduce(|_post_id, inputs, outputs| {
+            // inputs are sorted by time
+            if let Some((time, _diff)) = inputs.iter().rev().find(|(_time, diff)| *diff > 0) {
+                outputs.push((**time, 1));
+            }
+        });
+
+    // (post id, report count, rank)
+    let queued_posts = shared
+        .post_report_counts()
+        .as_collection(|post_id, count| (*post_id, *count))
+        .join(&latest_reports)
+        .semijoin(&posts)
+        .antijoin(&resolved_post_ids)
+        .map(|(post_id, (count, latest))| ((), (count, latest, post_id)))
+        .top_k(RANKED_POSTS_LIMIT)
+        .map(|((), (rank, (count, _latest, post_id)))| (post_id, count, rank))
+        .inspect(|v| debug!("moderation queue -- {:?}", v));
+
+    let session_queued_posts = queued_posts
+        .map(|queued_post| ((), queued_post))
+        .join(&queue_sessions)
+        .map(|((), ((post_id, count, rank), addr))| (post_id, count, rank, addr));
+
+    let queued_post_results = session_queued_posts.map(|(post_id, count, rank, addr)| {
+        vec![(addr, QueryResult::ModerationQueuePost(post_id, count, rank))]
+    });
+
+    // only changes when a post enters or leaves the queue
+    let session_post_ids = session_queued_posts
+        .map(|(post_id, _count, _rank, addr)| (post_id, addr))
+        .distinct();
+
+    let report_reasons = collection
+        .flat_map(|(_addr, (_user_id, persisted))| {
+            if let Persisted::Report(post_id, reason) = persisted {
+                vec![(post_id, reason)]
+            } else {
+                vec![]
+            }
+        })
+        .distinct();
+
+    let report_results = session_post_ids
+        .join(&report_reasons)
+        .map(|(post_id, (addr, reason))| vec![(addr, QueryResult::PostReport(post_id, reason))]);
+
+    queued_post_results
+        .concat(&report_results)
+        .concat(&session_post_field_results(shared, &session_post_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflows::page_post_ids::posts_post_ids_dataflow;
+    use crate::forum_minimal::{ForumMinimal, Role};
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    fn queue_and_pages_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+        moderation_queue_dataflow(shared).concat(&posts_post_ids_dataflow(shared))
+    }
+
+    #[tokio::test]
+    pub async fn test_moderation_queue() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            queue_and_pages_dataflow,
+        );
+        forum_minimal.grant_role(1, Role::Admin);
+
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (1, Persisted::Session, 1),
+                    (1, Persisted::ViewModerationQueue, 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (55, Persisted::ViewPostsPage(0), 1),
+                    (5, Persisted::Post, 1),
+                    (6, Persisted::Post, 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr2,
+                vec![(56, Persisted::Session, 1)].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr2,
+                vec![(56, Persisted::Report(5, "spam".into()), 1)].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr1,
+                vec![(55, Persisted::Report(6, "rude".into()), 1)].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr2,
+                vec![(56, Persisted::Report(6, "spam".into()), 1)].into(),
+            ))
+            .unwrap();
+
+        for _ in 0..6 {
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 1), 1),
+                        (QueryResult::PagePost(6, 0, 1), 1),
+                        (QueryResult::PostCreator(5, "55".into()), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    3,
+                    vec![
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::ModerationQueuePost(5, 1, 0), 1),
+                        (QueryResult::PostReport(5, "spam".into()), 1),
+                    ]
+                )
+            ))
+        );
+        // the most recently reported post comes first among posts with as many reports
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    4,
+                    vec![
+                        (QueryResult::ModerationQueuePost(5, 1, 0), -1),
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                        (QueryResult::ModerationQueuePost(5, 1, 1), 1),
+                        (QueryResult::ModerationQueuePost(6, 1, 0), 1),
+                        (QueryResult::PostReport(6, "rude".into()), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    5,
+                    vec![
+                        (QueryResult::ModerationQueuePost(6, 1, 0), -1),
+                        (QueryResult::ModerationQueuePost(6, 2, 0), 1),
+                        (QueryResult::PostReport(6, "spam".into()), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // two reports hide a post
+        persisted_sender
+            .send((addr0, vec![(0, Persisted::ReportHideThreshold(2), 1)].into()))
+            .unwrap();
+        // the moderator keeps the post
+        persisted_sender
+            .send((addr0, vec![(6, Persisted::PostReviewed, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // hidden by its reports
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    6,
+                    vec![
+                        (QueryResult::PagePost(6, 0, 1), -1),
+                        (QueryResult::PostCreator(6, "55".into()), -1),
+                    ]
+                )
+            ))
+        );
+        // reviewed, it leaves the queue and is back on the page
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    7,
+                    vec![
+                        (QueryResult::PostTotalLikes(6, 0), -1),
+                        (QueryResult::ModerationQueuePost(5, 1, 1), -1),
+                        (QueryResult::ModerationQueuePost(6, 2, 0), -1),
+                        (QueryResult::PostReport(6, "rude".into()), -1),
+                        (QueryResult::PostReport(6, "spam".into()), -1),
+                        (QueryResult::ModerationQueuePost(5, 1, 0), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    7,
+                    vec![
+                        (QueryResult::PagePost(6, 0, 1), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                    ]
+                )
+            ))
+        );
+
+        // the threshold set last applies, even when it is lower
+        persisted_sender
+            .send((addr0, vec![(0, Persisted::ReportHideThreshold(4), 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr0, vec![(0, Persisted::ReportHideThreshold(1), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // post 5 has one report
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    9,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 1), -1),
+                        (QueryResult::PostCreator(5, "55".into()), -1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+    }
+}
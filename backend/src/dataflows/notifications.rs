@@ -0,0 +1,275 @@
+use crate::forum_minimal::{
+    Notification, OutputScopeCollection, Persisted, QueryResult, NOTIFICATION_BACKLOG,
+};
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::operators::Threshold;
+use differential_dataflow::AsCollection;
+use log::debug;
+use timely::dataflow::operators::{Filter, Map};
+
+use crate::dataflows::{session_post_field_results, SharedArrangements};
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "notifications",
+    consumes: &[
+        "Session", "Post", "NotificationsRead", "PostTitle", "PostBody", "PostLike", "Block",
+        "Mute",
+    ],
+    produces: &[
+        "Notification", "NotificationsReadUntil", "PostTitle", "PostBody", "PostTotalLikes",
+        "PostMention",
+    ],
+    dataflow: notifications_dataflow,
+};
+
+/// Sends every session the newest `NOTIFICATION_BACKLOG` notifications of its user
+///
+/// A notification is unread until the user sends a `NotificationsRead` with a time
+/// at or after the time of the notification.
+/// The posts of the notifications are sent along, they are not on the session's page.
+pub fn notifications_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    // ((post id, user id), (time, like)), unliking is an additional `PostLike(post_id, false)`
+    let like_events = collection
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::PostLike(post_id, like) = persisted {
+                vec![((post_id, user_id), like)]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .filter(|(_, _time, diff)| *diff > 0)
+        .map(|((key, like), time, diff)| ((key, (time, like)), time, diff))
+        .as_collection();
+
+    // (post id, (user id, time of the latest like)) of the users that like a post
+    let likes = like_events.reduce(|_key, inputs, outputs| {
+        let net: isize = inputs
+            .iter()
+            .map(|((_time, like), diff)| if *like { *diff } else { -*diff })
+            .sum();
+
+        if net > 0 {
+            let latest_like = inputs.iter().rev().find(|((_time, like), _diff)| *like);
+
+            if let Some(((time, _like), _diff)) = latest_like {
+                outputs.push((*time, 1));
+            }
+        }
+    });
+
+    // (user id, (time, notification)) of every like of a post by someone else
+    let like_notifications = likes
+        .map(|((post_id, user_id), time)| (post_id, (user_id, time)))
+        .join_core(shared.post_creators(), |post_id, (user_id, time), creator| {
+            (user_id != creator)
+                .then_some((*creator, (*time, Notification::PostLiked(*post_id, *user_id))))
+        });
+
+    // (user id, (time, notification)) of every post that mentions someone else,
+    // the time is the creation time of the post
+    let mention_notifications = shared
+        .post_creation_times()
+        .join_core(shared.post_mentions(), |post_id, created, user_id| {
+            Some((*post_id, (*user_id, *created)))
+        })
+        .join_core(shared.post_creators(), |post_id, (user_id, created), creator| {
+            (user_id != creator)
+                .then_some((*user_id, (*created, Notification::Mentioned(*post_id, *creator))))
+        })
+        .distinct();
+
+    let notifications = like_notifications
+        .concat(&mention_notifications)
+        .inspect(|v| debug!("notifications -- {:?}", v));
+
+    // (user id, time) of the latest read marker of every user
+    let read_times = collection
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::NotificationsRead(time) = persisted {
+                vec![(user_id, time)]
+            } else {
+                vec![]
+            }
+        })
+        .reduce(|_user_id, inputs, outputs| {
+            if let Some((time, _diff)) = inputs.iter().rev().find(|(_time, diff)| *diff > 0) {
+                outputs.push((**time, 1));
+            }
+        });
+
+    // (user id, (notification, time, unread)), read markers are the `None` values
+    let user_notifications = notifications
+        .map(|(user_id, (time, notification))| (user_id, (time, Some(notification))))
+        .concat(&read_times.map(|(user_id, time)| (user_id, (time, None))))
+        .reduce(|_user_id, inputs, outputs| {
+            let read_time = inputs
+                .iter()
+                .filter(|((_time, notification), _diff)| notification.is_none())
+                .map(|((time, _notification), _diff)| *time)
+                .max();
+
+            let newest = inputs
+                .iter()
+                .rev()
+                .filter_map(|((time, notification), _diff)| Some((*time, notification.clone()?)))
+                .take(NOTIFICATION_BACKLOG);
+
+            for (time, notification) in newest {
+                let unread = read_time.is_none_or(|read_time| time > read_time);
+                outputs.push(((notification, time, unread), 1));
+            }
+        });
+
+    let notification_results = user_notifications.join_core(
+        shared.user_sessions(),
+        |_user_id, (notification, time, unread), addr| {
+            Some(vec![(
+                *addr,
+                QueryResult::Notification(notification.clone(), *time, *unread),
+            )])
+        },
+    );
+
+    let read_time_results = read_times.join_core(shared.user_sessions(), |_user_id, time, addr| {
+        Some(vec![(*addr, QueryResult::NotificationsReadUntil(*time))])
+    });
+
+    let session_post_ids = user_notifications
+        .flat_map(|(user_id, (notification, _time, _unread))| match notification {
+            Notification::PostLiked(post_id, _user_id) => vec![(user_id, post_id)],
+            Notification::Mentioned(post_id, _creator) => vec![(user_id, post_id)],
+        })
+        .distinct()
+        .join_core(shared.user_sessions(), |_user_id, post_id, addr| {
+            Some((*post_id, *addr))
+        });
+
+    notification_results
+        .concat(&read_time_results)
+        .concat(&session_post_field_results(shared, &session_post_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_notifications() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            notifications_dataflow,
+        );
+
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostTitle("Zerg".into()), 1),
+                    (55, Persisted::PostLike(5, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((addr1, vec![(55, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr2, vec![(56, Persisted::Session, 1)].into()))
+            .unwrap();
+
+        for _ in 0..3 {
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        // liking your own post is no notification
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // every session of the creator is notified
+        persisted_sender
+            .send((addr2, vec![(56, Persisted::PostLike(5, true), 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        for addr in [addr0, addr1] {
+            assert_eq!(
+                query_result_receiver.try_recv(),
+                Ok((
+                    addr,
+                    (
+                        3,
+                        vec![
+                            (QueryResult::PostTitle(5, "Zerg".into()), 1),
+                            (QueryResult::PostTotalLikes(5, 2), 1),
+                            (
+                                QueryResult::Notification(Notification::PostLiked(5, 56), 3, true),
+                                1
+                            ),
+                        ]
+                    )
+                ))
+            );
+        }
+
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::NotificationsRead(3), 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        for addr in [addr0, addr1] {
+            assert_eq!(
+                query_result_receiver.try_recv(),
+                Ok((
+                    addr,
+                    (
+                        4,
+                        vec![
+                            (
+                                QueryResult::Notification(Notification::PostLiked(5, 56), 3, true),
+                                -1
+                            ),
+                            (
+                                QueryResult::Notification(Notification::PostLiked(5, 56), 3, false),
+                                1
+                            ),
+                            (QueryResult::NotificationsReadUntil(3), 1),
+                        ]
+                    )
+                ))
+            );
+        }
+
+        // unliking removes the notification
+        persisted_sender
+            .send((addr2, vec![(56, Persisted::PostLike(5, false), 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        let (addr, (time, results)) = query_result_receiver.try_recv().unwrap();
+        assert_eq!((addr, time), (addr0, 5));
+        assert!(results.contains(&(
+            QueryResult::Notification(Notification::PostLiked(5, 56), 3, false),
+            -1
+        )));
+        assert!(results.iter().all(|(_query_result, diff)| *diff < 0));
+    }
+}
@@ -1,19 +1,28 @@
-use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, ScopeCollection};
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
 use log::debug;
 
-use timely::dataflow::operators::Filter;
-use timely::dataflow::operators::Map;
 
 // use differential_dataflow::operators::Consolidate;
 // use differential_dataflow::operators::Count;
-use differential_dataflow::operators::Join;
+use differential_dataflow::operators::JoinCore;
 use differential_dataflow::operators::Reduce;
-use differential_dataflow::AsCollection;
 
-use crate::dataflows::shared_post_pages;
-
-pub fn posts_post_ids_dataflow<'a>(collection: &ScopeCollection<'a>) -> OutputScopeCollection<'a> {
-    let session_pages = collection
+use crate::dataflows::{session_listing_pages, SharedArrangements};
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "page_post_ids",
+    consumes: &[
+        "ViewPostsPage", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostTitle", "PostBody", "PostLike", "Session", "Block", "Mute",
+    ],
+    produces: &["PagePost", "PostCreator", "PostTitle", "PostBody"],
+    dataflow: posts_post_ids_dataflow,
+};
+
+pub fn posts_post_ids_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let session_pages = shared
+        .collection
         .flat_map(|(addr, (_id, persisted))| {
             if let Persisted::ViewPostsPage(view_page) = persisted {
                 vec![(addr, view_page)]
@@ -41,100 +50,47 @@ pub fn posts_post_ids_dataflow<'a>(collection: &ScopeCollection<'a>) -> OutputSc
         })
         .inspect(|v| debug!("session pages -- {:?}", v));
 
-    let page_posts = shared_post_pages(&collection)
-        .map(|(addr, post_id, page, position)| (page, (addr, post_id, position)));
-
-    let session_posts = session_pages
-        .map(|(addr, page)| (page, addr))
-        .join::<_, isize>(&page_posts);
-
-    let session_post_results = session_posts
-        .inner
-        .map(
-            move |((page, (session_addr, (_addr, id, creation_time))), time, diff)| {
-                let query_result = if diff > 0 {
-                    vec![(session_addr, QueryResult::PagePost(id, page, creation_time))]
-                } else {
-                    vec![(session_addr, QueryResult::DeletePost(id))]
-                };
-                debug!("session posts -- {:?}", query_result);
-
-                (query_result, time, diff)
-            },
-        )
-        .as_collection();
+    // viewers that blocked or muted users get pages without their posts
+    let session_post_pages = session_listing_pages(shared, shared.post_ranks(), &session_pages)
+        .map(|(session_addr, (page, id, _rank))| (id, (session_addr, page)));
+
+    let session_post_results = session_post_pages
+        .join_core(shared.post_creation_times(), |id, (session_addr, page), creation_time| {
+            Some(vec![(*session_addr, QueryResult::PagePost(*id, *page, *creation_time))])
+        })
+        .inspect(|v| debug!("session posts -- {:?}", v));
 
     let session_post_ids =
-        session_posts.map(|(_page, (session_addr, (_addr, id, _time)))| (id, session_addr));
-
-    let session_post_field_results = collection
-        .map(|(creator_addr, (id, persisted))| (id, (creator_addr, persisted)))
-        .join::<_, isize>(&session_post_ids)
-        .inner
-        .map(
-            move |((id, ((_creator_addr, persisted), session_addr)), time, diff)| {
-                if diff > 0 {
-                    let query_result = match persisted {
-                        Persisted::PostTitle(title) => {
-                            vec![(session_addr, QueryResult::PostTitle(id, title.clone()))]
-                        }
-                        Persisted::PostBody(body) => {
-                            vec![(session_addr, QueryResult::PostBody(id, body.clone()))]
-                        }
-                        _ => vec![],
-                    };
-
-                    (query_result, time, diff)
-                } else {
-                    (vec![], time, diff)
-                }
+        session_post_pages.map(|(id, (session_addr, _page))| (id, session_addr));
+
+    let session_post_field_results = session_post_ids
+        .join_core(
+            shared.fields(),
+            |id, session_addr, persisted| match persisted {
+                Persisted::PostTitle(title) => Some(vec![(
+                    *session_addr,
+                    QueryResult::PostTitle(*id, title.clone()),
+                )]),
+                _ => None,
             },
         )
-        .as_collection()
         .inspect(|v| debug!("session post fields -- {:?}", v));
 
-    let post_creator_addrs = collection
-        .flat_map(|(creator_addr, (post_id, persisted))| {
-            if let Persisted::Post = persisted {
-                vec![(creator_addr, post_id)]
-            } else {
-                vec![]
-            }
-        })
-        .inner
-        .filter(|(_, _time, diff)| *diff > 0)
-        .as_collection();
-
-    let user_id_addrs = collection.flat_map(|(creator_addr, (user_id, persisted))| {
-        if let Persisted::Session = persisted {
-            vec![(creator_addr, user_id)]
-        } else {
-            vec![]
-        }
-    });
-
-    let post_creator_names_results = post_creator_addrs
-        .join(&user_id_addrs)
-        .map(|(creator_addr, (post_id, user_id))| (post_id, (creator_addr, user_id)))
-        .join(&session_post_ids)
-        .inner
-        .map(
-            |((post_id, ((_creator_addr, user_id), session_addr)), time, diff)| {
-                let result = if diff > 0 {
-                    vec![(
-                        session_addr,
-                        QueryResult::PostCreator(post_id, user_id.to_string()),
-                    )]
-                } else {
-                    vec![]
-                };
-
-                (result, time, diff)
-            },
-        )
-        .as_collection();
+    let session_post_body_results =
+        session_post_ids.join_core(shared.post_bodies(), |id, session_addr, body| {
+            Some(vec![(*session_addr, QueryResult::PostBody(*id, body.clone()))])
+        });
+
+    let post_creator_names_results =
+        session_post_ids.join_core(shared.post_creators(), |post_id, session_addr, user_id| {
+            Some(vec![(
+                *session_addr,
+                QueryResult::PostCreator(*post_id, user_id.to_string()),
+            )])
+        });
 
     session_post_field_results
+        .concat(&session_post_body_results)
         .concat(&session_post_results)
         .concat(&post_creator_names_results)
 }
@@ -173,7 +129,7 @@ mod tests {
                     (7, Persisted::Post, 1),
                     (7, Persisted::PostTitle("Protoss".into()), 1),
                     (7, Persisted::PostBody("Protoss Info".into()), 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -183,12 +139,15 @@ mod tests {
             query_result_receiver.try_recv(),
             Ok((
                 addr,
-                vec![
-                    QueryResult::PagePost(5, 1, 0),
-                    QueryResult::PostTitle(5, "Zerg".into()),
-                    QueryResult::PostBody(5, "Zerg Info".into()),
-                    // QueryResult::PostTotalLikes(7, 0),
-                ]
+                (
+                    0,
+                    vec![
+                        (QueryResult::PagePost(5, 1, 0), 1),
+                        (QueryResult::PostTitle(5, "Zerg".into()), 1),
+                        (QueryResult::PostBody(5, "<p>Zerg Info</p>".into()), 1),
+                        // (QueryResult::PostTotalLikes(7, 0), 1),
+                    ]
+                )
             ))
         );
 
@@ -198,7 +157,7 @@ mod tests {
                 vec![
                     (55, Persisted::ViewPostsPage(1), -1),
                     (55, Persisted::ViewPostsPage(0), 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -208,17 +167,20 @@ mod tests {
             query_result_receiver.try_recv(),
             Ok((
                 addr,
-                vec![
-                    QueryResult::DeletePost(5),
-                    QueryResult::PagePost(6, 0, 0),
-                    QueryResult::PagePost(7, 0, 0),
-                    QueryResult::PostTitle(6, "Terran".into()),
-                    QueryResult::PostTitle(7, "Protoss".into()),
-                    QueryResult::PostBody(6, "Terran Info".into()),
-                    QueryResult::PostBody(7, "Protoss Info".into()),
-                    // QueryResult::PostTotalLikes(5, 0),
-                    // QueryResult::PostTotalLikes(6, 0),
-                ]
+                (
+                    1,
+                    vec![
+                        (QueryResult::PagePost(5, 1, 0), -1),
+                        (QueryResult::PostTitle(5, "Zerg".into()), -1),
+                        (QueryResult::PostBody(5, "<p>Zerg Info</p>".into()), -1),
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                        (QueryResult::PagePost(7, 0, 0), 1),
+                        (QueryResult::PostTitle(6, "Terran".into()), 1),
+                        (QueryResult::PostTitle(7, "Protoss".into()), 1),
+                        (QueryResult::PostBody(6, "<p>Terran Info</p>".into()), 1),
+                        (QueryResult::PostBody(7, "<p>Protoss Info</p>".into()), 1),
+                    ]
+                )
             ))
         );
     }
@@ -241,10 +203,11 @@ mod tests {
                 addr,
                 vec![
                     (55, Persisted::ViewPostsPage(0), 1),
+                    (55, Persisted::Session, 1),
                     (5, Persisted::Post, 1),
                     (6, Persisted::Post, 1),
                     (7, Persisted::Post, 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -254,17 +217,22 @@ mod tests {
             query_result_receiver.try_recv(),
             Ok((
                 addr,
-                vec![
-                    QueryResult::PagePost(6, 0, 0),
-                    QueryResult::PagePost(7, 0, 0),
-                    // QueryResult::PostTotalLikes(5, 0),
-                    // QueryResult::PostTotalLikes(6, 0),
-                ]
+                (
+                    0,
+                    vec![
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                        (QueryResult::PagePost(7, 0, 0), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                        (QueryResult::PostCreator(7, "55".into()), 1),
+                        // (QueryResult::PostTotalLikes(5, 0), 1),
+                        // (QueryResult::PostTotalLikes(6, 0), 1),
+                    ]
+                )
             ))
         );
 
         persisted_sender
-            .send((addr, vec![(6, Persisted::Post, -1)]))
+            .send((addr, vec![(6, Persisted::Post, -1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
@@ -273,11 +241,15 @@ mod tests {
             query_result_receiver.try_recv(),
             Ok((
                 addr,
-                vec![
-                    QueryResult::DeletePost(6),
-                    QueryResult::PagePost(5, 0, 0),
-                    // QueryResult::PostTotalLikes(7, 0),
-                ]
+                (
+                    1,
+                    vec![
+                        (QueryResult::PagePost(6, 0, 0), -1),
+                        (QueryResult::PostCreator(6, "55".into()), -1),
+                        (QueryResult::PagePost(5, 0, 0), 1),
+                        (QueryResult::PostCreator(5, "55".into()), 1),
+                    ]
+                )
             ))
         );
     }
@@ -303,7 +275,7 @@ mod tests {
                     (55, Persisted::ViewPostsPage(0), 1),
                     (55, Persisted::Session, 1),
                     (5, Persisted::Post, 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -315,7 +287,7 @@ mod tests {
                 vec![
                     (56, Persisted::ViewPostsPage(0), 1),
                     (56, Persisted::Session, 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -325,11 +297,14 @@ mod tests {
             query_result_receiver.try_recv(),
             Ok((
                 addr0,
-                vec![
-                    QueryResult::PagePost(5, 0, 0),
-                    QueryResult::PostCreator(5, "55".to_string()),
-                    // QueryResult::PostTotalLikes(5, 0),
-                ]
+                (
+                    0,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 0), 1),
+                        (QueryResult::PostCreator(5, "55".to_string()), 1),
+                        // (QueryResult::PostTotalLikes(5, 0), 1),
+                    ]
+                )
             ))
         );
 
@@ -337,29 +312,129 @@ mod tests {
             query_result_receiver.try_recv(),
             Ok((
                 addr1,
-                vec![
-                    QueryResult::PagePost(5, 0, 0),
-                    QueryResult::PostCreator(5, "55".to_string()),
-                    // QueryResult::PostTotalLikes(5, 0),
-                ]
+                (
+                    1,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 0), 1),
+                        (QueryResult::PostCreator(5, "55".to_string()), 1),
+                        // (QueryResult::PostTotalLikes(5, 0), 1),
+                    ]
+                )
             ))
         );
 
+        // only the creator can delete the post
         persisted_sender
-            .send((addr1, vec![(5, Persisted::Post, -1)]))
+            .send((addr0, vec![(5, Persisted::Post, -1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr0, vec![QueryResult::DeletePost(5),]))
+            Ok((
+                addr0,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 0), -1),
+                        (QueryResult::PostCreator(5, "55".to_string()), -1),
+                    ]
+                )
+            ))
         );
 
-        // Warning - FLAKY
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr1, vec![QueryResult::DeletePost(5),]))
+            Ok((
+                addr1,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 0), -1),
+                        (QueryResult::PostCreator(5, "55".to_string()), -1),
+                    ]
+                )
+            ))
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_soft_delete() {
+        crate::init_logger();
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            posts_post_ids_dataflow,
+        );
+
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (55, Persisted::ViewPostsPage(0), 1),
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (6, Persisted::Post, 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((addr, vec![(6, Persisted::PostDeleted, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr, vec![(6, Persisted::PostDeleted, -1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    0,
+                    vec![
+                        (QueryResult::PagePost(5, 0, 0), 1),
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                        (QueryResult::PostCreator(5, "55".into()), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PagePost(6, 0, 0), -1),
+                        (QueryResult::PostCreator(6, "55".into()), -1),
+                    ]
+                )
+            ))
+        );
+        // restored at its old position
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                        (QueryResult::PostCreator(6, "55".into()), 1),
+                    ]
+                )
+            ))
         );
     }
 }
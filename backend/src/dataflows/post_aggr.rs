@@ -1,63 +1,65 @@
 use crate::forum_minimal::{
-    OutputScopeCollection, Persisted, QueryResult, ScopeCollection, POSTS_PER_PAGE,
+    OutputScopeCollection, Persisted, QueryResult, POSTS_PER_PAGE,
 };
 use differential_dataflow::operators::Consolidate;
-use differential_dataflow::operators::Count;
 use differential_dataflow::operators::Join;
-use differential_dataflow::AsCollection;
 use log::debug;
-use timely::dataflow::operators::Map;
+use crate::dataflows::{session_viewers, SharedArrangements};
+use crate::operators::count_with_zeros::CountWithZeros;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "post_aggr",
+    consumes: &[
+        "ViewPostsPage", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "Session", "Block", "Mute",
+    ],
+    produces: &["PostAggregates"],
+    dataflow: post_aggr_dataflow,
+};
 
-pub fn post_aggr_dataflow<'a>(manages_sess: &ScopeCollection<'a>) -> OutputScopeCollection<'a> {
-    let manages = manages_sess.map(|(_addr, (id, persisted))| (id, persisted));
+pub fn post_aggr_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let manages_sess = &shared.collection;
 
-    let sessions_with_zero = manages_sess
-        .filter(|(_addr, (_, persisted))| {
-            if let Persisted::ViewPostsPage(_) = persisted {
-                true
-            } else {
-                false
-            }
-        })
-        .map(|(addr, _)| (0, addr))
+    let page_sessions = manages_sess
+        .filter(|(_addr, (_, persisted))| matches!(persisted, Persisted::ViewPostsPage(_)))
+        .map(|(addr, _)| (addr, ()))
         .consolidate();
 
-    let post_aggregates_result = manages
-        .flat_map(|(_id, persisted)| {
-            if let Persisted::Post = persisted {
-                vec![0]
-            } else if Persisted::PlusOneDummy == persisted {
-                // add an additional count so that counting to zero is possible
-                vec![0]
-            } else {
-                vec![]
-            }
-        })
-        .count()
+    // viewers that blocked or muted users do not count their posts
+    let session_viewers =
+        session_viewers(shared, &page_sessions).map(|(addr, ((), viewer))| (viewer, addr));
+
+    // soft deleted and hidden posts are not counted, they are not on any page
+    let page_post_ids = shared.post_ranks().map(|(post_id, _rank)| post_id);
+
+    let post_count = page_post_ids
+        .map(|_post_id| ())
+        .count_with_zeros(&session_viewers.map(|(_viewer, _addr)| ()));
+
+    let hidden_counts = shared
+        .viewer_hidden_posts()
+        .map(|(viewer, post_id)| (post_id, Some(viewer)))
+        .semijoin(&page_post_ids)
+        .map(|(_post_id, viewer)| viewer)
+        .count_with_zeros(&session_viewers.map(|(viewer, _addr)| viewer));
+
+    // a viewer's pages are full pages of the posts the viewer did not hide
+    let post_aggregates_result = session_viewers
+        .join(&hidden_counts)
+        .map(|(_viewer, (addr, hidden))| ((), (addr, hidden)))
+        .join(&post_count)
         .inspect(|v| debug!("val {:?}", v))
-        .map(|v| (0, v))
-        .join(&sessions_with_zero)
-        .inner
-        .map(|((_zero, ((_, count), addr)), time, diff)| {
-            debug!("count {:?}", count);
-            let actual_count = count - 1;
-            let mut page_count = ((actual_count as f64) / (POSTS_PER_PAGE as f64)).ceil() as u64;
+        .map(|((), ((addr, hidden), total))| {
+            debug!("count {:?}", total);
+            let count = total.saturating_sub(hidden);
+            let mut page_count = ((count as f64) / (POSTS_PER_PAGE as f64)).ceil() as u64;
             if page_count < 1 {
                 page_count = 1;
             }
 
-            let result = if diff > 0 {
-                vec![(
-                    addr,
-                    QueryResult::PostAggregates(actual_count as u64, page_count as u64),
-                )]
-            } else {
-                vec![]
-            };
-
-            (result, time, diff)
-        })
-        .as_collection();
+            vec![(addr, QueryResult::PostAggregates(count, page_count))]
+        });
 
     // .inspect_batch(move |_time, items| {
     //     let mut addrs = Vec::new();
@@ -114,10 +116,11 @@ mod tests {
                 addr,
                 vec![
                     (55, Persisted::ViewPostsPage(0), 1),
+                    (55, Persisted::Session, 1),
                     (5, Persisted::Post, 1),
                     (6, Persisted::Post, 1),
                     (7, Persisted::Post, 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -125,24 +128,33 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr, vec![QueryResult::PostAggregates(3, 2)]))
+            Ok((addr, (0, vec![(QueryResult::PostAggregates(3, 2), 1)])))
         );
 
         persisted_sender
-            .send((addr, vec![(5, Persisted::Post, -1)]))
+            .send((addr, vec![(5, Persisted::Post, -1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr, vec![QueryResult::PostAggregates(2, 1)]))
+            Ok((
+                addr,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PostAggregates(3, 2), -1),
+                        (QueryResult::PostAggregates(2, 1), 1),
+                    ]
+                )
+            ))
         );
 
         persisted_sender
             .send((
                 addr,
-                vec![(6, Persisted::Post, -1), (7, Persisted::Post, -1)],
+                vec![(6, Persisted::Post, -1), (7, Persisted::Post, -1)].into(),
             ))
             .unwrap();
 
@@ -150,7 +162,16 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr, vec![QueryResult::PostAggregates(0, 1)]))
+            Ok((
+                addr,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostAggregates(2, 1), -1),
+                        (QueryResult::PostAggregates(0, 1), 1),
+                    ]
+                )
+            ))
         );
     }
 
@@ -183,7 +204,7 @@ mod tests {
 
     //     assert_eq!(
     //         query_result_receiver.try_recv(),
-    //         Ok((addr, vec![QueryResult::PostAggregates(3, 2)]))
+    //         Ok((addr, vec![(QueryResult::PostAggregates(3, 2), 1)]))
     //     );
     // }
 }
@@ -1,15 +1,28 @@
-use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, ScopeCollection};
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
 use differential_dataflow::operators::Join;
+use differential_dataflow::operators::Threshold;
 use differential_dataflow::AsCollection;
 use timely::dataflow::operators::Filter;
-use timely::dataflow::operators::Map;
 
-use crate::dataflows::shared_post_pages;
+use crate::dataflows::{session_listing_pages, SharedArrangements};
 use log::debug;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "post_liked_by_user",
+    consumes: &[
+        "ViewPostsPage", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostLike", "Session", "Block", "Mute",
+    ],
+    produces: &["PostLikedByUser"],
+    dataflow: post_liked_by_user_dataflow,
+};
 
 pub fn post_liked_by_user_dataflow<'a>(
-    collection: &ScopeCollection<'a>,
+    shared: &SharedArrangements<'a>,
 ) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
     let user_id_to_page_addr = collection
         .flat_map(|(addr, (user_id, persisted))| {
             if let Persisted::ViewPostsPage(page) = persisted {
@@ -21,6 +34,7 @@ pub fn post_liked_by_user_dataflow<'a>(
         .inner
         .filter(|(_, _time, diff)| *diff > 0)
         .as_collection()
+        .distinct()
         .inspect(|v| debug!("current page -- {:?}", v));
 
     let post_likes = collection.flat_map(|(_addr, (user_id, persisted))| {
@@ -35,41 +49,25 @@ pub fn post_liked_by_user_dataflow<'a>(
     // .join(&posts)
     // .map(|(post_id, ((user_id, like), ()))| (user_id, (post_id, like)));
 
-    let post_pages = shared_post_pages(&collection)
-        .map(|(_addr, post_id, page, _position)| (post_id, page))
-        .inspect(|((post_id, page), _, _)| debug!("post: {:?}, page: {:?}", post_id, page));
+    // a user that blocked or muted the creator of a post does not see it on the page
+    let session_post_ids = session_listing_pages(
+        shared,
+        shared.post_ranks(),
+        &user_id_to_page_addr.map(|(_user_id, (page, addr))| (addr, page)),
+    )
+    .map(|(session_addr, (_page, post_id, _rank))| (session_addr, post_id))
+    .inspect(|v| debug!("session post ids -- {:?}", v));
 
     let result = user_id_to_page_addr
-        .join(&post_likes)
-        .map(
-            |(_user_id, ((visible_page, session_addr), (post_id, like)))| {
-                (post_id, (session_addr, visible_page, like))
-            },
-        )
-        .inspect(|v| debug!("map -- {:?}", v))
-        .join(&post_pages)
-        .inspect(|v| debug!("id and page -- {:?}", v))
-        .filter(
-            |(_post_id, ((_session_addr, visible_page, _like), post_page))| {
-                *visible_page == *post_page as u64
-            },
-        )
-        .inspect(|v| debug!("filtered id and page -- {:?}", v))
-        // .join(&posts)
-        // .map(|(post_id, (vals, ()))| (post_id, vals))
-        .inner
-        .map(
-            |((post_id, ((session_addr, _visible_page, like), _post_page)), time, diff)| {
-                let result = if diff > 0 {
-                    vec![(session_addr, QueryResult::PostLikedByUser(post_id, like))]
-                } else {
-                    vec![]
-                };
-
-                (result, time, diff)
-            },
-        )
-        .as_collection();
+        .map(|(user_id, (_page, session_addr))| (session_addr, user_id))
+        .distinct()
+        .join(&session_post_ids)
+        .map(|(session_addr, (user_id, post_id))| ((user_id, post_id), session_addr))
+        .join(&post_likes.map(|(user_id, (post_id, like))| ((user_id, post_id), like)))
+        .inspect(|v| debug!("liked posts on the page -- {:?}", v))
+        .map(|((_user_id, post_id), (session_addr, like))| {
+            vec![(session_addr, QueryResult::PostLikedByUser(post_id, like))]
+        });
 
     result
 }
@@ -96,7 +94,7 @@ mod tests {
         );
 
         persisted_sender
-            .send((addr0, vec![(55, Persisted::ViewPostsPage(0), 1)]))
+            .send((addr0, vec![(55, Persisted::ViewPostsPage(0), 1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
@@ -115,7 +113,7 @@ mod tests {
                     (55, Persisted::ViewPostsPage(0), 1),
                     (5, Persisted::Post, 1),
                     (55, Persisted::PostLike(5, true), 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -123,7 +121,7 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr0, vec![QueryResult::PostLikedByUser(5, true)]))
+            Ok((addr0, (1, vec![(QueryResult::PostLikedByUser(5, true), 1)])))
         );
 
         persisted_sender
@@ -132,7 +130,7 @@ mod tests {
                 vec![
                     (55, Persisted::Session, 1),
                     (55, Persisted::ViewPostsPage(0), 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -140,18 +138,28 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr1, vec![QueryResult::PostLikedByUser(5, true)])),
+            Ok((addr1, (2, vec![(QueryResult::PostLikedByUser(5, true), 1)]))),
         );
 
         persisted_sender
-            .send((addr1, vec![(5, Persisted::Post, -1)]))
+            .send((addr1, vec![(5, Persisted::Post, -1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Err(broadcast::error::TryRecvError::Empty),
+            Ok((
+                addr0,
+                (3, vec![(QueryResult::PostLikedByUser(5, true), -1)])
+            )),
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (3, vec![(QueryResult::PostLikedByUser(5, true), -1)])
+            )),
         );
     }
 }
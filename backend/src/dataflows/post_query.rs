@@ -0,0 +1,404 @@
+use crate::forum_minimal::{
+    OutputScopeCollection, Persisted, PostQuery, PostSort, QueryResult, POSTS_PER_PAGE,
+};
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::operators::Threshold;
+use log::debug;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::dataflows::hot_posts::hot_score;
+use crate::dataflows::{
+    session_post_field_results, session_viewers, visible_to_viewer, SharedArrangements,
+};
+use crate::registry::DataflowModule;
+
+/// Everything a `PostQuery` can filter or sort by
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PostFacts {
+    pub created: u64,
+    pub creator: Option<u64>,
+    pub board: Option<u64>,
+    pub likes: u64,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum PostFact {
+    Created(u64),
+    Creator(u64),
+    Board(u64),
+    Likes(u64),
+}
+
+/// The key a query is joined with the posts on
+///
+/// Queries filtered by author or board only meet the posts with that author or board,
+/// only unfiltered queries meet every post.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum JoinKey {
+    All,
+    Author(u64),
+    Board(u64),
+}
+
+impl JoinKey {
+    fn of_query(query: &PostQuery) -> Self {
+        match (query.author, query.board) {
+            (Some(author), _) => JoinKey::Author(author),
+            (None, Some(board)) => JoinKey::Board(board),
+            (None, None) => JoinKey::All,
+        }
+    }
+
+    /// Every key a query that may match the post is joined on
+    fn of_post(facts: &PostFacts) -> Vec<Self> {
+        let mut keys = vec![JoinKey::All];
+        keys.extend(facts.creator.map(JoinKey::Author));
+        keys.extend(facts.board.map(JoinKey::Board));
+        keys
+    }
+}
+
+pub fn query_matches(query: &PostQuery, facts: &PostFacts) -> bool {
+    query
+        .author
+        .is_none_or(|author| facts.creator == Some(author))
+        && query.board.is_none_or(|board| facts.board == Some(board))
+        && query.created_from.is_none_or(|from| facts.created >= from)
+        && query
+            .created_until
+            .is_none_or(|until| facts.created <= until)
+}
+
+/// Posts are listed with the largest key first
+pub fn sort_key(sort: PostSort, facts: &PostFacts) -> (u64, u64) {
+    match sort {
+        PostSort::Newest => (facts.created, 0),
+        PostSort::Oldest => (u64::MAX - facts.created, 0),
+        PostSort::Top => (facts.likes, facts.created),
+        PostSort::Hot => (hot_score(facts.likes, facts.created), facts.created),
+    }
+}
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "post_query",
+    consumes: &[
+        "Query", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostBoard", "PostTitle", "PostBody", "PostLike", "Session", "Block",
+        "Mute",
+    ],
+    produces: &["QueryPost", "PostTitle", "PostBody", "PostTotalLikes", "PostMention"],
+    dataflow: post_query_dataflow,
+};
+
+/// Serves every `Persisted::Query` subscription from one dataflow:
+/// the active query descriptors are joined against the facts of the posts
+/// with their author or board (see `JoinKey`),
+/// so a new kind of listing only needs a new field in `PostQuery`.
+pub fn post_query_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    let post_boards = collection.flat_map(|(_addr, (post_id, persisted))| {
+        if let Persisted::PostBoard(board) = persisted {
+            vec![(post_id, PostFact::Board(board))]
+        } else {
+            vec![]
+        }
+    });
+
+    let post_facts = shared
+        .post_creation_times()
+        .as_collection(|post_id, created| (*post_id, PostFact::Created(*created)))
+        .concat(
+            &shared
+                .post_creators()
+                .as_collection(|post_id, user_id| (*post_id, PostFact::Creator(*user_id))),
+        )
+        .concat(
+            &shared
+                .post_like_counts()
+                .as_collection(|post_id, likes| (*post_id, PostFact::Likes(*likes))),
+        )
+        .concat(&post_boards)
+        .reduce(|_post_id, inputs, outputs| {
+            let mut facts = PostFacts::default();
+            let mut exists = false;
+
+            for (fact, diff) in inputs {
+                if *diff > 0 {
+                    match fact {
+                        PostFact::Created(created) => {
+                            exists = true;
+                            facts.created = *created;
+                        }
+                        PostFact::Creator(user_id) => facts.creator = Some(*user_id),
+                        PostFact::Board(board) => facts.board = Some(*board),
+                        PostFact::Likes(likes) => facts.likes = *likes,
+                    }
+                }
+            }
+
+            // fields of deleted posts are left behind, but the post itself is gone
+            if exists {
+                outputs.push((facts, 1));
+            }
+        });
+
+    let session_queries = collection.flat_map(|(addr, (query_id, persisted))| {
+        if let Persisted::Query(query) = persisted {
+            vec![(JoinKey::of_query(&query), (addr, query_id, query))]
+        } else {
+            vec![]
+        }
+    });
+
+    let matching_posts = session_queries
+        .join(&post_facts.flat_map(|(post_id, facts)| {
+            JoinKey::of_post(&facts)
+                .into_iter()
+                .map(move |key| (key, (post_id, facts.clone())))
+        }))
+        .flat_map(|(_key, ((addr, query_id, query), (post_id, facts)))| {
+            if query_matches(&query, &facts) {
+                let key = sort_key(query.sort, &facts);
+                vec![(addr, (query_id, query, key, post_id))]
+            } else {
+                vec![]
+            }
+        });
+
+    // posts of users the viewer blocked or muted are left out before they are ranked
+    let visible_posts = visible_to_viewer(
+        shared,
+        &session_viewers(shared, &matching_posts).map(
+            |(addr, ((query_id, query, key, post_id), viewer))| {
+                ((viewer, post_id), (addr, query_id, query, key))
+            },
+        ),
+    );
+
+    // (session addr, query id, post id, rank)
+    let session_query_posts = visible_posts
+        .map(|((_viewer, post_id), (addr, query_id, query, key))| {
+            ((addr, query_id, query), (key, post_id))
+        })
+        .reduce(|(_addr, _query_id, query), inputs, outputs| {
+            let first = query.page as usize * POSTS_PER_PAGE;
+
+            // inputs are sorted by key, smallest first
+            for (rank, ((_key, post_id), _diff)) in inputs
+                .iter()
+                .rev()
+                .enumerate()
+                .skip(first)
+                .take(POSTS_PER_PAGE)
+            {
+                outputs.push(((*post_id, rank as u64), 1));
+            }
+        })
+        .map(|((addr, query_id, _query), (post_id, rank))| (addr, query_id, post_id, rank))
+        .inspect(|v| debug!("query posts -- {:?}", v));
+
+    let query_post_results = session_query_posts.map(|(addr, query_id, post_id, rank)| {
+        vec![(addr, QueryResult::QueryPost(query_id, post_id, rank))]
+    });
+
+    let session_post_ids = session_query_posts
+        .map(|(addr, _query_id, post_id, _rank)| (post_id, addr))
+        .distinct();
+
+    query_post_results.concat(&session_post_field_results(shared, &session_post_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    fn query(sort: PostSort) -> PostQuery {
+        PostQuery {
+            author: None,
+            board: None,
+            created_from: None,
+            created_until: None,
+            sort,
+            page: 0,
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_post_query_filters() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            post_query_dataflow,
+        );
+
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostTitle("Zerg".into()), 1),
+                    (5, Persisted::PostBoard(1), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (56, Persisted::Session, 1),
+                    (6, Persisted::Post, 1),
+                    (6, Persisted::PostTitle("Terran".into()), 1),
+                    (6, Persisted::PostBoard(2), 1),
+                    (
+                        90,
+                        Persisted::Query(PostQuery {
+                            author: Some(55),
+                            ..query(PostSort::Newest)
+                        }),
+                        1,
+                    ),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), 1),
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::QueryPost(90, 5, 0), 1),
+                    ]
+                )
+            ))
+        );
+
+        // the same session switches to a different filter
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (
+                        90,
+                        Persisted::Query(PostQuery {
+                            author: Some(55),
+                            ..query(PostSort::Newest)
+                        }),
+                        -1,
+                    ),
+                    (
+                        90,
+                        Persisted::Query(PostQuery {
+                            board: Some(2),
+                            ..query(PostSort::Newest)
+                        }),
+                        1,
+                    ),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), -1),
+                        (QueryResult::PostTotalLikes(5, 0), -1),
+                        (QueryResult::QueryPost(90, 5, 0), -1),
+                        (QueryResult::PostTitle(6, "Terran".into()), 1),
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                        (QueryResult::QueryPost(90, 6, 0), 1),
+                    ]
+                )
+            ))
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_post_query_sort_and_pages() {
+        crate::init_logger();
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            post_query_dataflow,
+        );
+
+        for post_id in 5..8 {
+            persisted_sender
+                .send((addr, vec![(post_id, Persisted::Post, 1)].into()))
+                .unwrap();
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (90, Persisted::Query(query(PostSort::Oldest)), 1),
+                    (
+                        91,
+                        Persisted::Query(PostQuery {
+                            page: 1,
+                            ..query(PostSort::Newest)
+                        }),
+                        1,
+                    ),
+                    (92, Persisted::Query(query(PostSort::Top)), 1),
+                    (55, Persisted::Session, 1),
+                    (55, Persisted::PostLike(6, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    3,
+                    vec![
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::PostTotalLikes(6, 1), 1),
+                        (QueryResult::PostTotalLikes(7, 0), 1),
+                        (QueryResult::QueryPost(90, 5, 0), 1),
+                        (QueryResult::QueryPost(90, 6, 1), 1),
+                        (QueryResult::QueryPost(91, 5, 2), 1),
+                        (QueryResult::QueryPost(92, 6, 0), 1),
+                        (QueryResult::QueryPost(92, 7, 1), 1),
+                    ]
+                )
+            ))
+        );
+    }
+}
@@ -1,18 +1,25 @@
-use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, ScopeCollection};
-use differential_dataflow::operators::Join;
-use differential_dataflow::operators::Reduce;
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
 use differential_dataflow::AsCollection;
 use timely::dataflow::operators::Filter;
-use timely::dataflow::operators::Map;
 
-use crate::dataflows::shared_post_pages;
+use crate::dataflows::{session_listing_pages, session_post_like_counts, SharedArrangements};
 use log::debug;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "post_total_likes",
+    consumes: &[
+        "ViewPostsPage", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostLike", "Session", "Block", "Mute",
+    ],
+    produces: &["PostTotalLikes"],
+    dataflow: post_total_likes_dataflow,
+};
 
 pub fn post_total_likes_dataflow<'a>(
-    collection: &ScopeCollection<'a>,
+    shared: &SharedArrangements<'a>,
 ) -> OutputScopeCollection<'a> {
-    let post_pages = shared_post_pages(&collection)
-        .map(|(_creator_addr, post_id, page, _position)| (page, post_id));
+    let collection = &shared.collection;
 
     let page_to_viewer_addr = collection
         .flat_map(|(viewer_addr, (_user_id, persisted))| {
@@ -27,53 +34,17 @@ pub fn post_total_likes_dataflow<'a>(
         .as_collection()
         .inspect(|v| debug!("current page -- {:?}", v));
 
-    let posts = collection.flat_map(|(_addr, (post_id, persisted))| {
-        if Persisted::Post == persisted {
-            vec![(post_id, ())]
-        } else {
-            vec![]
-        }
-    });
-
-    let post_like_counts = collection
-        .flat_map(|(_addr, (user_or_post_id, persisted))| {
-            if let Persisted::PostLike(post_id, val) = persisted {
-                vec![(post_id, val)]
-            // add an additional count so that counting to zero is possible
-            } else if Persisted::Post == persisted {
-                vec![(user_or_post_id, true)]
-            } else {
-                vec![]
-            }
-        })
-        .join(&posts)
-        .reduce(|post_id, inputs, outputs| {
-            debug!("post_id: {}, inputs: {:?}", post_id, inputs);
-
-            if inputs.len() == 1 {
-                outputs.push((inputs[0].1 - 1, 1));
-            } else {
-                outputs.push((inputs[1].1 - inputs[0].1 - 1, 1));
-            }
-        })
-        .inspect(|v| debug!("like_counts -- {:?}", v));
+    let session_post_ids = session_listing_pages(
+        shared,
+        shared.post_ranks(),
+        &page_to_viewer_addr.map(|(page, viewer_addr)| (viewer_addr, page)),
+    )
+    .map(|(viewer_addr, (_page, post_id, _rank))| (post_id, viewer_addr))
+    .inspect(|v| debug!("map -- {:?}", v));
 
-    let result = post_pages
-        .join(&page_to_viewer_addr)
-        .map(|(page, (post_id, viewer_addr))| (post_id, (page, viewer_addr)))
-        .inspect(|v| debug!("map -- {:?}", v))
-        .join(&post_like_counts)
-        .inner
-        .map(|((post_id, ((_page, addr), count)), time, diff)| {
-            let result = if diff > 0 {
-                vec![(addr, QueryResult::PostTotalLikes(post_id, count as u64))]
-            } else {
-                vec![]
-            };
-
-            (result, time, diff)
-        })
-        .as_collection();
+    let result = session_post_like_counts(shared, &session_post_ids).map(
+        |(addr, post_id, count)| vec![(addr, QueryResult::PostTotalLikes(post_id, count))],
+    );
 
     result
 }
@@ -89,7 +60,8 @@ mod tests {
     pub async fn test_post_total_likes() {
         crate::init_logger();
         let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        // let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
         let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
         let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
 
@@ -99,44 +71,60 @@ mod tests {
             post_total_likes_dataflow,
         );
 
+        // every user likes as the user of its own session
+        for (addr, user_id) in [(addr1, 56), (addr2, 57)] {
+            persisted_sender
+                .send((
+                    addr,
+                    vec![
+                        (user_id, Persisted::Session, 1),
+                        (user_id, Persisted::PostLike(5, true), 1),
+                    ].into(),
+                ))
+                .unwrap();
+        }
         persisted_sender
             .send((
                 addr0,
                 vec![
                     (55, Persisted::ViewPostsPage(0), 1),
+                    (55, Persisted::Session, 1),
                     (5, Persisted::Post, 1),
                     (6, Persisted::Post, 1),
                     (55, Persisted::PostLike(5, true), 1),
-                    (56, Persisted::PostLike(5, true), 1),
-                    (57, Persisted::PostLike(5, true), 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
 
         assert_eq!(
             query_result_receiver.try_recv(),
             Ok((
                 addr0,
-                vec![
-                    QueryResult::PostTotalLikes(5, 3),
-                    QueryResult::PostTotalLikes(6, 0),
-                ]
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostTotalLikes(5, 3), 1),
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                    ]
+                )
             )),
         );
 
         debug!("REMOVING -----\n\n");
 
         persisted_sender
-            .send((addr0, vec![(5, Persisted::Post, -1)]))
+            .send((addr0, vec![(5, Persisted::Post, -1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Err(broadcast::error::TryRecvError::Empty),
+            Ok((addr0, (3, vec![(QueryResult::PostTotalLikes(5, 3), -1)]))),
         );
     }
 }
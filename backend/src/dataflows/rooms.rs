@@ -0,0 +1,331 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, ROOM_BACKFILL};
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::operators::Threshold;
+use differential_dataflow::AsCollection;
+use log::debug;
+use timely::dataflow::operators::{Filter, Map};
+
+use crate::dataflows::SharedArrangements;
+use crate::operators::top_k::TopK;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "rooms",
+    consumes: &["Session", "JoinRoom", "RoomMessage", "Typing"],
+    produces: &["RoomPresence", "RoomTyping", "RoomMessage"],
+    dataflow: rooms_dataflow,
+};
+
+/// Sends the sessions in a chat room who is online and typing in it and its newest messages
+///
+/// A session is in a room while its `JoinRoom` and its `Session` record exist,
+/// the connection retracts the rooms of a session once it disconnects.
+/// Sessions that join a room receive the last `ROOM_BACKFILL` messages sent before they joined
+/// and every message sent since.
+pub fn rooms_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    // ((addr, (user id, room)), join time) of every room a session is in
+    let room_joins = collection
+        .flat_map(|(addr, (user_id, persisted))| {
+            if let Persisted::JoinRoom(room) = persisted {
+                vec![(addr, (user_id, room))]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .map(|((addr, user_room), time, diff)| (((addr, user_room), time), time, diff))
+        .as_collection()
+        .reduce(|_key, inputs, outputs| {
+            let joined: isize = inputs.iter().map(|(_time, diff)| *diff).sum();
+            let latest = inputs.iter().rev().find(|(_time, diff)| *diff > 0);
+
+            if let (true, Some((time, _diff))) = (joined > 0, latest) {
+                outputs.push((**time, 1));
+            }
+        });
+
+    // (room, (user id, addr, join time)) of every session in a room
+    let room_sessions = room_joins
+        .map(|((addr, (user_id, room)), time)| (addr, (user_id, room, time)))
+        .join_core(shared.sessions(), |addr, (user_id, room, time), session_user_id| {
+            (user_id == session_user_id).then(|| (room.clone(), (*user_id, *addr, *time)))
+        });
+
+    // (room, user id) of every user with a session in the room
+    let presence = room_sessions
+        .map(|(room, (user_id, _addr, _time))| (room, user_id))
+        .distinct()
+        .inspect(|v| debug!("room presence -- {:?}", v));
+
+    // `Typing` records are recent for `TYPING_WINDOW` or until the message is sent
+    let typing = shared
+        .recent
+        .flat_map(|(_addr, (user_id, persisted))| {
+            if let Persisted::Typing(room) = persisted {
+                vec![(room, user_id)]
+            } else {
+                vec![]
+            }
+        })
+        .distinct()
+        // users that left are no longer typing
+        .map(|room_user| (room_user, ()))
+        .semijoin(&presence)
+        .map(|(room_user, ())| room_user);
+
+    // (room, (time, message id, sender, body)) of every message, messages are never removed
+    let messages = collection
+        .flat_map(|(_addr, (message_id, persisted))| {
+            if let Persisted::RoomMessage(room, sender, body) = persisted {
+                vec![(room, (message_id, sender, body))]
+            } else {
+                vec![]
+            }
+        })
+        .inner
+        .filter(|(_, _time, diff)| *diff > 0)
+        .map(|((room, (message_id, sender, body)), time, diff)| {
+            ((room, (time, message_id, sender, body)), time, diff)
+        })
+        .as_collection();
+
+    let session_messages = messages
+        .join(&room_sessions)
+        .map(|(room, (message, (_user_id, addr, join_time)))| ((room, addr), (message, join_time)));
+
+    let live_messages = session_messages
+        .filter(|(_room_addr, ((time, _, _, _), join_time))| time >= join_time)
+        .map(|(room_addr, (message, _join_time))| (room_addr, message));
+
+    // no message is sent before the join time later on, the backfill of a session does not change
+    let backfill_messages = session_messages
+        .filter(|(_room_addr, ((time, _, _, _), join_time))| time < join_time)
+        .map(|(room_addr, (message, _join_time))| (room_addr, message))
+        .top_k(ROOM_BACKFILL)
+        .map(|(room_addr, (_rank, message))| (room_addr, message));
+
+    let presence_results = presence
+        .join(&room_sessions)
+        .map(|(room, (user_id, (_viewer, addr, _time)))| {
+            vec![(addr, QueryResult::RoomPresence(room, user_id))]
+        });
+
+    let typing_results = typing
+        .join(&room_sessions)
+        .map(|(room, (user_id, (_viewer, addr, _time)))| {
+            vec![(addr, QueryResult::RoomTyping(room, user_id))]
+        });
+
+    let message_results = live_messages.concat(&backfill_messages).map(
+        |((room, addr), (time, message_id, sender, body))| {
+            vec![(
+                addr,
+                QueryResult::RoomMessage(room, message_id, sender, time, body),
+            )]
+        },
+    );
+
+    presence_results
+        .concat(&typing_results)
+        .concat(&message_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::{ForumMinimal, TYPING_WINDOW};
+    use std::net::SocketAddr;
+    use std::time::Instant;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_rooms() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            rooms_dataflow,
+        );
+
+        let messages: Vec<_> = (0..=ROOM_BACKFILL as u64)
+            .map(|message_id| {
+                (
+                    message_id,
+                    Persisted::RoomMessage("general".into(), 55, format!("gg {}", message_id)),
+                    1,
+                )
+            })
+            .collect();
+
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr1, vec![(56, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr0,
+                [vec![(55, Persisted::JoinRoom("general".into()), 1)], messages]
+                    .concat()
+                    .into(),
+            ))
+            .unwrap();
+
+        for _ in 0..3 {
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        assert!(query_result_receiver.try_recv().is_ok());
+
+        // a session that joins is backfilled with the newest messages, the oldest is left out
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (56, Persisted::JoinRoom("general".into()), 1),
+                    (56, Persisted::Typing("general".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        let (addr, (time, results)) = query_result_receiver.try_recv().unwrap();
+        assert_eq!((addr, time), (addr0, 3));
+        assert_eq!(
+            results,
+            vec![
+                (QueryResult::RoomPresence("general".into(), 56), 1),
+                (QueryResult::RoomTyping("general".into(), 56), 1),
+            ]
+        );
+
+        let (addr, (time, results)) = query_result_receiver.try_recv().unwrap();
+        assert_eq!((addr, time), (addr1, 3));
+        let message_ids: Vec<u64> = results
+            .iter()
+            .filter_map(|(query_result, _diff)| match query_result {
+                QueryResult::RoomMessage(_room, message_id, 55, 2, _body) => Some(*message_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(message_ids, (1..=ROOM_BACKFILL as u64).collect::<Vec<_>>());
+        assert_eq!(
+            results[..3],
+            [
+                (QueryResult::RoomPresence("general".into(), 55), 1),
+                (QueryResult::RoomPresence("general".into(), 56), 1),
+                (QueryResult::RoomTyping("general".into(), 56), 1),
+            ]
+        );
+
+        // the backfill is kept, newer messages are added to it
+        persisted_sender
+            .send((
+                addr0,
+                vec![(
+                    11,
+                    Persisted::RoomMessage("general".into(), 55, "gg 11".into()),
+                    1,
+                )].into(),
+            ))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        for addr in [addr0, addr1] {
+            assert_eq!(
+                query_result_receiver.try_recv(),
+                Ok((
+                    addr,
+                    (
+                        4,
+                        vec![(
+                            QueryResult::RoomMessage("general".into(), 11, 55, 4, "gg 11".into()),
+                            1
+                        )]
+                    )
+                ))
+            );
+        }
+
+        // typing expires after `TYPING_WINDOW`, at a dataflow time of its own
+        forum_minimal.expire_recent_records(Instant::now() + TYPING_WINDOW);
+
+        for addr in [addr0, addr1] {
+            assert_eq!(
+                query_result_receiver.try_recv(),
+                Ok((addr, (5, vec![(QueryResult::RoomTyping("general".into(), 56), -1)])))
+            );
+        }
+
+        // leaving the room removes the user from the presence list
+        persisted_sender
+            .send((addr1, vec![(56, Persisted::JoinRoom("general".into()), -1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (6, vec![(QueryResult::RoomPresence("general".into(), 56), -1)])
+            ))
+        );
+        let (addr, (_time, results)) = query_result_receiver.try_recv().unwrap();
+        assert_eq!(addr, addr1);
+        assert!(results.iter().all(|(_query_result, diff)| *diff < 0));
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // sending a message stops typing before the window passes
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::Typing("general".into()), 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (12, Persisted::RoomMessage("general".into(), 55, "gg 12".into()), 1),
+                    (55, Persisted::Typing("general".into()), -1),
+                ].into(),
+            ))
+            .unwrap();
+        for _ in 0..2 {
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr0, (7, vec![(QueryResult::RoomTyping("general".into(), 55), 1)])))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    8,
+                    vec![
+                        (QueryResult::RoomTyping("general".into(), 55), -1),
+                        (QueryResult::RoomMessage("general".into(), 12, 55, 8, "gg 12".into()), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+    }
+}
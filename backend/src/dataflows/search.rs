@@ -0,0 +1,308 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
+use differential_dataflow::operators::Consolidate;
+use differential_dataflow::operators::Count;
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Threshold;
+use log::debug;
+
+use crate::dataflows::{session_viewers, visible_to_viewer, SharedArrangements};
+use crate::operators::only_latest::OnlyLatest;
+use crate::registry::DataflowModule;
+
+pub const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "if", "in", "into",
+    "is", "it", "no", "not", "of", "on", "or", "so", "that", "the", "their", "then", "there",
+    "these", "they", "this", "to", "was", "will", "with",
+];
+
+/// Splits text into unique lowercase words, leaving out stop words
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .filter(|token| !STOP_WORDS.contains(&token.as_str()))
+        .collect::<Vec<_>>();
+
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "search",
+    consumes: &[
+        "Search", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostTitle", "PostBody", "Session", "Block", "Mute",
+    ],
+    produces: &["SearchHit"],
+    dataflow: search_dataflow,
+};
+
+/// A post is a hit if its title or body contains every word of the session's `Search` query
+///
+/// Posts of users the user of the session blocked or muted are no hits.
+pub fn search_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    // (token, post id)
+    let index = collection
+        .flat_map(|(_addr, (post_id, persisted))| match persisted {
+            Persisted::PostTitle(text) | Persisted::PostBody(text) => tokenize(&text)
+                .into_iter()
+                .map(|token| (post_id, token))
+                .collect(),
+            _ => vec![],
+        })
+        .distinct()
+        // only posts that were not deleted
+        .join_core(shared.post_creation_times(), |post_id, token, _created| {
+            Some((token.clone(), *post_id))
+        });
+
+    // a session searches for the query it inserted last
+    let session_queries = collection
+        .flat_map(|(addr, (_id, persisted))| {
+            if let Persisted::Search(query) = persisted {
+                vec![(addr, query)]
+            } else {
+                vec![]
+            }
+        })
+        .only_latest()
+        .inspect(|v| debug!("search queries -- {:?}", v));
+
+    let query_tokens = session_queries
+        .flat_map(|(addr, query)| tokenize(&query).into_iter().map(move |token| (token, addr)));
+
+    let query_token_counts = query_tokens.map(|(_token, addr)| addr).count();
+
+    let matching_posts = index
+        .join(&query_tokens)
+        .map(|(_token, (post_id, addr))| (addr, post_id))
+        .count()
+        .map(|((addr, post_id), matched)| ((addr, matched), post_id))
+        .join(&query_token_counts.map(|(addr, count)| ((addr, count), ())))
+        .map(|((addr, _count), (post_id, ()))| (addr, post_id));
+
+    // (post id, session addr)
+    let session_hits = visible_to_viewer(
+        shared,
+        &session_viewers(shared, &matching_posts)
+            .map(|(addr, (post_id, viewer))| ((viewer, post_id), addr)),
+    )
+    .map(|((_viewer, post_id), addr)| (post_id, addr))
+    .consolidate()
+    .inspect(|v| debug!("search hits -- {:?}", v));
+
+    let post_titles = shared.fields().flat_map_ref(|post_id, persisted| {
+        if let Persisted::PostTitle(title) = persisted {
+            Some((*post_id, title.clone()))
+        } else {
+            None
+        }
+    });
+
+    // posts without a title are hits too, with an empty title
+    let untitled_hits = session_hits
+        .antijoin(&post_titles.map(|(post_id, _title)| post_id).distinct())
+        .map(|(post_id, addr)| vec![(addr, QueryResult::SearchHit(post_id, String::new()))]);
+
+    // a hit is retracted when the post stops matching or its title changes
+    session_hits
+        .join(&post_titles)
+        .map(|(post_id, (addr, title))| vec![(addr, QueryResult::SearchHit(post_id, title))])
+        .concat(&untitled_hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[test]
+    pub fn test_tokenize() {
+        assert_eq!(
+            tokenize("The Zerg, the Swarm and the ZERG-hive!"),
+            vec!["hive", "swarm", "zerg"]
+        );
+        assert_eq!(tokenize("to be or not to be"), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    pub async fn test_search() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            search_dataflow,
+        );
+
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (55, Persisted::Search("zerg rush".into()), 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostTitle("Zerg".into()), 1),
+                    (5, Persisted::PostBody("How to rush".into()), 1),
+                    (6, Persisted::Post, 1),
+                    (6, Persisted::PostTitle("Terran".into()), 1),
+                    (6, Persisted::PostBody("The Zerg are coming".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr0, (0, vec![(QueryResult::SearchHit(5, "Zerg".into()), 1)])))
+        );
+
+        // a post created by another session shows up as it starts matching
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (56, Persisted::Session, 1),
+                    (7, Persisted::Post, 1),
+                    (7, Persisted::PostTitle("Rush of the Zerg".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    1,
+                    vec![(QueryResult::SearchHit(7, "Rush of the Zerg".into()), 1)]
+                )
+            ))
+        );
+
+        // editing the title keeps the hit, but updates it
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (7, Persisted::PostTitle("Rush of the Zerg".into()), -1),
+                    (7, Persisted::PostTitle("Zerg Rush".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    2,
+                    vec![
+                        (QueryResult::SearchHit(7, "Rush of the Zerg".into()), -1),
+                        (QueryResult::SearchHit(7, "Zerg Rush".into()), 1),
+                    ]
+                )
+            ))
+        );
+
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (5, Persisted::PostBody("How to rush".into()), -1),
+                    (5, Persisted::PostBody("How to defend".into()), 1),
+                    (7, Persisted::Post, -1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    3,
+                    vec![
+                        (QueryResult::SearchHit(5, "Zerg".into()), -1),
+                        (QueryResult::SearchHit(7, "Zerg Rush".into()), -1),
+                    ]
+                )
+            ))
+        );
+
+        // posts without a title match by their body
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (8, Persisted::Post, 1),
+                    (8, Persisted::PostBody("Zerg rush at 12".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr0, (4, vec![(QueryResult::SearchHit(8, "".into()), 1)])))
+        );
+
+        // the newest query replaces the old one, even if it sorts before it
+        persisted_sender
+            .send((addr0, vec![(55, Persisted::Search("defend".into()), 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    5,
+                    vec![
+                        (QueryResult::SearchHit(8, "".into()), -1),
+                        (QueryResult::SearchHit(5, "Zerg".into()), 1),
+                    ]
+                )
+            ))
+        );
+
+        // posts of a blocked user are no hits, post 8 of user 56 matches but is left out
+        persisted_sender
+            .send((
+                addr0,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (55, Persisted::Block(56), 1),
+                    (55, Persisted::Search("zerg".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr0, (6, vec![(QueryResult::SearchHit(6, "Terran".into()), 1)])))
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use crate::forum_minimal::{
+    OutputScopeCollection, Persisted, QueryResult, SpamFlag, BACKEND_ADDR,
+    NEW_USER_FLOOD_THRESHOLD, POST_BURST_THRESHOLD, REPEATED_BODY_THRESHOLD,
+};
+use differential_dataflow::operators::Count;
+use differential_dataflow::operators::Join;
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Threshold;
+use log::debug;
+
+use crate::dataflows::{session_post_field_results, session_roles, SharedArrangements};
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "spam",
+    consumes: &[
+        "Session", "UserRole", "Post", "PostDeleted", "PostHidden", "PostTitle", "PostBody",
+        "PostLike", "Block", "Mute",
+    ],
+    produces: &["SpamFlag", "PostTitle", "PostBody", "PostTotalLikes", "PostMention"],
+    dataflow: spam_dataflow,
+};
+
+// bodies that only differ in case or whitespace are the same
+fn normalized_body(body: &str) -> String {
+    body.split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Flags posts whose body was posted `REPEATED_BODY_THRESHOLD` times,
+/// users that created `POST_BURST_THRESHOLD` posts within `SPAM_WINDOW`
+/// and ip addresses that `NEW_USER_FLOOD_THRESHOLD` new users came from within `SPAM_WINDOW`
+///
+/// The flags are sent to moderators and to `BACKEND_ADDR`, where `ForumMinimal` keeps them
+/// and rejects the posts and new users they are about (`ForumMinimal::check_spam_flags`).
+/// Flags are retracted once the posts are removed or the window has passed.
+pub fn spam_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let moderator_sessions = session_roles(shared)
+        .filter(|(_addr, role)| role.can_moderate())
+        .map(|(addr, _role)| ((), addr))
+        .distinct();
+
+    // (normalized body, post id) of posts that are not removed
+    let post_bodies = shared
+        .post_creation_times()
+        .join_core(shared.fields(), |post_id, _created, persisted| {
+            if let Persisted::PostBody(body) = persisted {
+                Some((normalized_body(body), *post_id))
+            } else {
+                None
+            }
+        });
+
+    let repeated_bodies = post_bodies
+        .join(
+            &post_bodies
+                .map(|(body, _post_id)| body)
+                .count()
+                .filter(|(_body, count)| *count as u64 >= REPEATED_BODY_THRESHOLD),
+        )
+        .map(|(_body, (post_id, count))| SpamFlag::RepeatedBody(post_id, count as u64));
+
+    // the posts created within the window, by their creator
+    let post_bursts = shared
+        .recent
+        .flat_map(|(_addr, (post_id, persisted))| {
+            if let Persisted::Post = persisted {
+                vec![(post_id, ())]
+            } else {
+                vec![]
+            }
+        })
+        .join_core(shared.post_creators(), |_post_id, (), user_id| Some(*user_id))
+        .count()
+        .filter(|(_user_id, count)| *count as u64 >= POST_BURST_THRESHOLD)
+        .map(|(user_id, count)| SpamFlag::PostBurst(user_id, count as u64));
+
+    // the ip address of the first session of every user within the window
+    let new_user_floods = shared
+        .recent
+        .flat_map(|(addr, (_user_id, persisted))| {
+            if let Persisted::Session = persisted {
+                vec![addr.ip().to_string()]
+            } else {
+                vec![]
+            }
+        })
+        .count()
+        .filter(|(_ip, count)| *count as u64 >= NEW_USER_FLOOD_THRESHOLD)
+        .map(|(ip, count)| SpamFlag::NewUserFlood(ip, count as u64));
+
+    let flags = repeated_bodies
+        .concat(&post_bursts)
+        .concat(&new_user_floods)
+        .inspect(|v| debug!("spam flags -- {:?}", v));
+
+    let backend_flag_results =
+        flags.map(|flag| vec![(BACKEND_ADDR, QueryResult::SpamFlag(flag))]);
+
+    let moderator_flags = flags
+        .map(|flag| ((), flag))
+        .join(&moderator_sessions)
+        .map(|((), (flag, addr))| (flag, addr));
+
+    let moderator_flag_results =
+        moderator_flags.map(|(flag, addr)| vec![(addr, QueryResult::SpamFlag(flag))]);
+
+    let moderator_post_ids = moderator_flags
+        .flat_map(|(flag, addr)| match flag {
+            SpamFlag::RepeatedBody(post_id, _count) => vec![(post_id, addr)],
+            _ => vec![],
+        })
+        .distinct();
+
+    backend_flag_results
+        .concat(&moderator_flag_results)
+        .concat(&session_post_field_results(shared, &moderator_post_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::{
+        ForumMinimal, Role, Transaction, TransactionError, TransactionOutcome, SPAM_WINDOW,
+    };
+    use std::net::SocketAddr;
+    use std::time::Instant;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_spam_flags() {
+        crate::init_logger();
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(64);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            spam_dataflow,
+        );
+        let mut outcome_receiver = forum_minimal.outcome_sender.subscribe();
+        forum_minimal.grant_role(1, Role::Moderator);
+
+        persisted_sender
+            .send((addr0, vec![(1, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostBody("Buy now".into()), 1),
+                    (6, Persisted::Post, 1),
+                    (6, Persisted::PostBody("buy   NOW".into()), 1),
+                    (7, Persisted::Post, 1),
+                    (7, Persisted::PostBody("Zerg rush".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(forum_minimal.spam_flags().count(), 0);
+
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (8, Persisted::Post, 1),
+                    (8, Persisted::PostBody("Buy now ".into()), 1),
+                    (9, Persisted::Post, 1),
+                    (9, Persisted::PostBody("Zerg".into()), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            forum_minimal.spam_flags().cloned().collect::<Vec<_>>(),
+            vec![
+                SpamFlag::RepeatedBody(5, 3),
+                SpamFlag::RepeatedBody(6, 3),
+                SpamFlag::RepeatedBody(8, 3),
+                SpamFlag::PostBurst(55, 5),
+            ]
+        );
+        // the backend keeps its flags, only the moderator is sent any
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr0,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostBody(5, "<p>Buy now</p>".into()), 1),
+                        (QueryResult::PostBody(6, "<p>buy   NOW</p>".into()), 1),
+                        (QueryResult::PostBody(8, "<p>Buy now </p>".into()), 1),
+                        (QueryResult::PostTotalLikes(5, 0), 1),
+                        (QueryResult::PostTotalLikes(6, 0), 1),
+                        (QueryResult::PostTotalLikes(8, 0), 1),
+                        (QueryResult::SpamFlag(SpamFlag::RepeatedBody(5, 3)), 1),
+                        (QueryResult::SpamFlag(SpamFlag::RepeatedBody(6, 3)), 1),
+                        (QueryResult::SpamFlag(SpamFlag::RepeatedBody(8, 3)), 1),
+                        (QueryResult::SpamFlag(SpamFlag::PostBurst(55, 5)), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+
+        // hiding a repeated post resolves its flags, a third user from the same address is a flood
+        persisted_sender
+            .send((addr0, vec![(8, Persisted::PostHidden("spam".into()), 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr2, vec![(56, Persisted::Session, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            forum_minimal.spam_flags().cloned().collect::<Vec<_>>(),
+            vec![
+                SpamFlag::PostBurst(55, 5),
+                SpamFlag::NewUserFlood("127.0.0.1".into(), 3),
+            ]
+        );
+
+        // flagged users can not post and no more new users come from the flooding address
+        let post = Transaction {
+            id: Some(70),
+            items: vec![(10, Persisted::Post, 1)],
+        };
+        let new_user = Transaction {
+            id: Some(71),
+            items: vec![(57, Persisted::Session, 1)],
+        };
+        persisted_sender.send((addr1, post.clone())).unwrap();
+        persisted_sender.send((addr0, new_user.clone())).unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((
+                addr1,
+                TransactionOutcome::Rejected(
+                    70,
+                    TransactionError::Unauthorized(
+                        "too many posts in a short time, try again later".into()
+                    )
+                )
+            ))
+        );
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((
+                addr0,
+                TransactionOutcome::Rejected(
+                    71,
+                    TransactionError::Unauthorized(
+                        "too many new users from your address, try again later".into()
+                    )
+                )
+            ))
+        );
+
+        // bursts and floods are forgotten once the window has passed
+        forum_minimal.expire_recent_records(Instant::now() + SPAM_WINDOW);
+
+        assert_eq!(forum_minimal.spam_flags().count(), 0);
+
+        persisted_sender.send((addr1, post)).unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((addr1, TransactionOutcome::Confirmed(70)))
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
+use differential_dataflow::operators::JoinCore;
+use log::debug;
+
+use crate::dataflows::{ranked_post_page_results, ranked_post_scores, SharedArrangements};
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "top_posts",
+    consumes: &[
+        "ViewTopPostsPage", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed", "PostTitle", "PostBody", "PostLike", "Session", "Block", "Mute",
+    ],
+    produces: &["TopPagePost", "PostTitle", "PostBody", "PostTotalLikes", "PostMention"],
+    dataflow: top_posts_dataflow,
+};
+
+pub fn top_posts_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    // most likes first, newer posts win ties
+    let post_scores = shared
+        .post_like_counts()
+        .join_core(shared.post_creation_times(), |post_id, likes, created| {
+            Some((*post_id, (*likes, *created)))
+        });
+    let ranked_posts =
+        ranked_post_scores(&post_scores).inspect(|v| debug!("top posts -- {:?}", v));
+
+    ranked_post_page_results(
+        shared,
+        &ranked_posts,
+        |persisted| {
+            if let Persisted::ViewTopPostsPage(page) = persisted {
+                Some(*page)
+            } else {
+                None
+            }
+        },
+        QueryResult::TopPagePost,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::ForumMinimal;
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    pub async fn test_top_posts() {
+        crate::init_logger();
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            top_posts_dataflow,
+        );
+
+        // every user likes as the user of its own session, post 6 is liked before it exists
+        persisted_sender
+            .send((
+                addr1,
+                vec![
+                    (56, Persisted::Session, 1),
+                    (56, Persisted::PostLike(5, true), 1),
+                    (56, Persisted::PostLike(6, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr2,
+                vec![(57, Persisted::Session, 1), (57, Persisted::PostLike(6, true), 1)].into(),
+            ))
+            .unwrap();
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (55, Persisted::ViewTopPostsPage(0), 1),
+                    (55, Persisted::Session, 1),
+                    (5, Persisted::Post, 1),
+                    (5, Persisted::PostTitle("Zerg".into()), 1),
+                    (7, Persisted::Post, 1),
+                    (7, Persisted::PostTitle("Protoss".into()), 1),
+                    (55, Persisted::PostLike(5, true), 1),
+                    (55, Persisted::PostLike(7, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    2,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), 1),
+                        (QueryResult::PostTitle(7, "Protoss".into()), 1),
+                        (QueryResult::PostTotalLikes(5, 2), 1),
+                        (QueryResult::PostTotalLikes(7, 1), 1),
+                        (QueryResult::TopPagePost(5, 0, 0), 1),
+                        (QueryResult::TopPagePost(7, 0, 1), 1),
+                    ]
+                )
+            ))
+        );
+
+        // post 6 overtakes post 7, which drops to the next page
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (6, Persisted::Post, 1),
+                    (6, Persisted::PostTitle("Terran".into()), 1),
+                    (55, Persisted::PostLike(6, true), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    3,
+                    vec![
+                        (QueryResult::PostTitle(7, "Protoss".into()), -1),
+                        (QueryResult::PostTotalLikes(7, 1), -1),
+                        (QueryResult::TopPagePost(5, 0, 0), -1),
+                        (QueryResult::TopPagePost(7, 0, 1), -1),
+                        (QueryResult::PostTitle(6, "Terran".into()), 1),
+                        (QueryResult::PostTotalLikes(6, 3), 1),
+                        (QueryResult::TopPagePost(5, 0, 1), 1),
+                        (QueryResult::TopPagePost(6, 0, 0), 1),
+                    ]
+                )
+            ))
+        );
+
+        // switching to the second page
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (55, Persisted::ViewTopPostsPage(0), -1),
+                    (55, Persisted::ViewTopPostsPage(1), 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    4,
+                    vec![
+                        (QueryResult::PostTitle(5, "Zerg".into()), -1),
+                        (QueryResult::PostTitle(6, "Terran".into()), -1),
+                        (QueryResult::PostTotalLikes(5, 2), -1),
+                        (QueryResult::PostTotalLikes(6, 3), -1),
+                        (QueryResult::TopPagePost(5, 0, 1), -1),
+                        (QueryResult::TopPagePost(6, 0, 0), -1),
+                        (QueryResult::PostTitle(7, "Protoss".into()), 1),
+                        (QueryResult::PostTotalLikes(7, 1), 1),
+                        (QueryResult::TopPagePost(7, 1, 2), 1),
+                    ]
+                )
+            ))
+        );
+    }
+}
@@ -1,62 +1,48 @@
-use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, ScopeCollection};
-use differential_dataflow::operators::Join;
-use differential_dataflow::operators::Reduce;
-use differential_dataflow::AsCollection;
+use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult};
+use differential_dataflow::operators::JoinCore;
+use differential_dataflow::operators::Threshold;
 use log::debug;
-use timely::dataflow::operators::Map;
-
-pub fn user_like_count_dataflow<'a>(collection: &ScopeCollection<'a>) -> OutputScopeCollection<'a> {
-    let likes_plus_one = collection.flat_map(|(_addr, (user_id, persisted))| {
+use crate::dataflows::SharedArrangements;
+use crate::operators::count_with_zeros::CountWithZeros;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "user_like_count",
+    consumes: &[
+        "Session", "PostLike", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed",
+    ],
+    produces: &["UserLikeCount"],
+    dataflow: user_like_count_dataflow,
+};
+
+pub fn user_like_count_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let collection = &shared.collection;
+
+    let likes = collection.flat_map(|(_addr, (user_id, persisted))| {
         if let Persisted::PostLike(post_id, true) = persisted {
             vec![(post_id, user_id)]
-        } else if Persisted::Session == persisted {
-            vec![(0, user_id)]
         } else {
             vec![]
         }
     });
 
-    let posts = collection.flat_map(|(_addr, (post_id, persisted))| {
-        if Persisted::Post == persisted {
-            vec![(post_id, ())]
-        } else if Persisted::Session == persisted {
-            vec![(0, ())]
-        } else {
-            vec![]
-        }
-    });    
+    let session_user_ids = shared
+        .user_sessions()
+        .as_collection(|user_id, _addr| *user_id);
 
-    let session_addr_to_user = collection.flat_map(|(addr, (user_id, persisted))| {
-        if Persisted::Session == persisted {
-            vec![(addr, user_id)]
-        } else {
-            vec![]
-        }
-    });
-
-    let session_user_to_addr = session_addr_to_user.map(|(addr, user_id)| (user_id, addr));
-
-    let results = likes_plus_one
-        .join(&posts)
-        .map(|(post_id, (user_id, ()))| (user_id, post_id))
-        .reduce(|user_id, inputs, outputs| {
-            debug!("user id: {}, inputs: {:?}", user_id, inputs);
-
-            outputs.push((inputs.len() - 1, 1));
+    // every post is counted once, only while it was not deleted
+    let results = likes
+        .distinct()
+        .join_core(shared.post_creation_times(), |_post_id, user_id, _created| {
+            Some(*user_id)
         })
-        .join(&session_user_to_addr)
-        .inspect(|v| debug!("v : {:?}", v))
-        .inner
-        .map(|((_user_id, (count, addr)), time, diff)| {
-            let result = if diff > 0 {
-                vec![(addr, QueryResult::UserLikeCount(count as u64))]
-            } else {
-                vec![]
-            };
-
-            (result, time, diff)
+        .count_with_zeros(&session_user_ids)
+        .join_core(shared.user_sessions(), |user_id, count, addr| {
+            Some((*user_id, (*count, *addr)))
         })
-        .as_collection();
+        .inspect(|v| debug!("v : {:?}", v))
+        .map(|(_user_id, (count, addr))| vec![(addr, QueryResult::UserLikeCount(count))]);
 
     results
 }
@@ -89,7 +75,7 @@ mod tests {
                     (55, Persisted::Session, 1),
                     (5, Persisted::Post, 1),
                     (55, Persisted::PostLike(5, true), 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -97,7 +83,7 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr0, vec![QueryResult::UserLikeCount(1)])),
+            Ok((addr0, (0, vec![(QueryResult::UserLikeCount(1), 1)]))),
         );
 
         persisted_sender
@@ -105,7 +91,7 @@ mod tests {
                 addr0,
                 vec![
                     (5, Persisted::Post, -1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -113,7 +99,16 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr0, vec![QueryResult::UserLikeCount(0)])),
+            Ok((
+                addr0,
+                (
+                    1,
+                    vec![
+                        (QueryResult::UserLikeCount(1), -1),
+                        (QueryResult::UserLikeCount(0), 1)
+                    ]
+                )
+            )),
         );
     }
 }
@@ -1,86 +1,37 @@
-use crate::forum_minimal::{OutputScopeCollection, Persisted, QueryResult, ScopeCollection};
-use differential_dataflow::operators::Join;
-use differential_dataflow::operators::Reduce;
-use differential_dataflow::AsCollection;
-use timely::dataflow::operators::Map;
+use crate::forum_minimal::{OutputScopeCollection, QueryResult};
+use differential_dataflow::operators::JoinCore;
 use log::debug;
-
-pub fn user_post_count_dataflow<'a>(collection: &ScopeCollection<'a>) -> OutputScopeCollection<'a> {
-    // get addrs with same user id as current session addr
-
-    let posts_plus_one = collection.flat_map(|(addr, (post_id, persisted))| {
-        if Persisted::Post == persisted {
-            vec![(addr, post_id)]
-        } else if Persisted::Session == persisted {
-            vec![(addr, 0)]
-        } else {
-            vec![]
-        }
-    });
-
-    let session_addr_to_user = collection.flat_map(|(addr, (user_id, persisted))| {
-        if Persisted::Session == persisted {
-            vec![(addr, user_id)]
-        } else {
-            vec![]
-        }
-    });
-
-    let session_user_to_addr = session_addr_to_user.map(|(addr, user_id)| (user_id, addr));
-
-    let posts_and_creator_user_ids = posts_plus_one
-        .join(&session_addr_to_user)
-        .map(|(addr, (post_id, user_id))| (post_id, (user_id, addr)))
-        .reduce(|post_id, inputs, output| {
-            debug!("post_id: {}, v : {:?}", post_id, inputs);
-
-            let mut found_removal = false;
-
-            'outer: for ((_rm_user_id, _rm_addr), rm_diff) in inputs {
-                if *rm_diff < 0 {
-                    found_removal = true;
-                    for ((add_user_id, _add_addr), add_diff) in inputs {
-                        if *add_diff > 0 {
-                            output.push(((*add_user_id), -1));
-                            continue 'outer;
-                        }
-                    }
-                }
-            }
-
-            if !found_removal {
-                for ((add_user_id, _add_addr), add_diff) in inputs {
-                    if *add_diff > 0 {
-                        output.push(((*add_user_id), 1));
-                    }
-                }
-            }
-        })
-        .map(|(post_id, user_id)| (user_id, post_id))
-        .inspect(|v| debug!("inspect : {:?}", v));
-
-    let user_post_counts = posts_and_creator_user_ids
-        .reduce(|user_id, inputs, outputs| {
-            debug!("user id: {}, inputs : {:?}", user_id, inputs);
-            let add_inputs = inputs
-                .into_iter()
-                .filter(|(_post_id, diff)| *diff > 0)
-                .collect::<Vec<_>>();
-
-            outputs.push((add_inputs.len() - 1, 1));
+use crate::dataflows::SharedArrangements;
+use crate::operators::count_with_zeros::CountWithZeros;
+use crate::registry::DataflowModule;
+
+pub const MODULE: DataflowModule = DataflowModule {
+    name: "user_post_count",
+    consumes: &[
+        "Session", "Post", "PostDeleted", "PostHidden", "Report", "ReportHideThreshold",
+        "PostReviewed",
+    ],
+    produces: &["UserPostCount"],
+    dataflow: user_post_count_dataflow,
+};
+
+pub fn user_post_count_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+    let session_user_ids = shared
+        .user_sessions()
+        .as_collection(|user_id, _addr| *user_id);
+
+    // posts that were not deleted, by their creator
+    let user_post_counts = shared
+        .post_creators()
+        .join_core(shared.post_creation_times(), |_post_id, user_id, _created| {
+            Some(*user_id)
         })
-        .join(&session_user_to_addr)
-        .inner
-        .map(|((_user_id, (count, addr)), time, diff)| {
-            let result = if diff > 0 {
-                vec![(addr, QueryResult::UserPostCount(count as u64))]
-            } else {
-                vec![]
-            };
-
-            (result, time, diff)
+        .inspect(|v| debug!("inspect : {:?}", v))
+        .count_with_zeros(&session_user_ids)
+        .join_core(shared.user_sessions(), |user_id, count, addr| {
+            Some((*user_id, (*count, *addr)))
         })
-        .as_collection();
+        .map(|(_user_id, (count, addr))| vec![(addr, QueryResult::UserPostCount(count))]);
 
     user_post_counts
 }
@@ -88,7 +39,7 @@ pub fn user_post_count_dataflow<'a>(collection: &ScopeCollection<'a>) -> OutputS
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::forum_minimal::ForumMinimal;
+    use crate::forum_minimal::{ForumMinimal, Persisted};
     use std::net::SocketAddr;
     use tokio::sync::broadcast;
 
@@ -114,7 +65,7 @@ mod tests {
                     (55, Persisted::Session, 1),
                     (5, Persisted::Post, 1),
                     (6, Persisted::Post, 1),
-                ],
+                ].into(),
             ))
             .unwrap();
 
@@ -122,24 +73,24 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr0, vec![QueryResult::UserPostCount(2)])),
+            Ok((addr0, (0, vec![(QueryResult::UserPostCount(2), 1)]))),
         );
 
         persisted_sender
-            .send((addr2, vec![(55, Persisted::Session, 1)]))
+            .send((addr2, vec![(55, Persisted::Session, 1)].into()))
             .unwrap();
 
         forum_minimal.advance_dataflow_computation_once().await;
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr2, vec![QueryResult::UserPostCount(2)])),
+            Ok((addr2, (1, vec![(QueryResult::UserPostCount(2), 1)]))),
         );
 
         persisted_sender
             .send((
                 addr1,
-                vec![(56, Persisted::Session, 1)],
+                vec![(56, Persisted::Session, 1)].into(),
             ))
             .unwrap();
 
@@ -147,13 +98,13 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr1, vec![QueryResult::UserPostCount(0)])),
+            Ok((addr1, (2, vec![(QueryResult::UserPostCount(0), 1)]))),
         );
 
         persisted_sender
             .send((
-                addr1,
-                vec![(5, Persisted::Post, -1)],
+                addr0,
+                vec![(5, Persisted::Post, -1)].into(),
             ))
             .unwrap();
 
@@ -161,7 +112,16 @@ mod tests {
 
         assert_eq!(
             query_result_receiver.try_recv(),
-            Ok((addr0, vec![QueryResult::UserPostCount(1)])),
+            Ok((
+                addr0,
+                (
+                    3,
+                    vec![
+                        (QueryResult::UserPostCount(2), -1),
+                        (QueryResult::UserPostCount(1), 1),
+                    ]
+                )
+            )),
         );
 
     }
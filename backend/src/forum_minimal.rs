@@ -1,27 +1,33 @@
 use df_forum_frontend::df_tuple_items::{Diff, Id, Time};
-pub use df_forum_frontend::persisted::{Persisted, PersistedItems, Post};
-pub use df_forum_frontend::query_result::QueryResult;
+pub use df_forum_frontend::persisted::{
+    Persisted, PersistedItems, Post, PostQuery, PostSort, Role, Transaction, TransactionId,
+};
+pub use df_forum_frontend::query_result::{
+    Notification, QueryResult, QueryResultFrame, ServerMessage, SpamFlag, TransactionError,
+    TransactionOutcome,
+};
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use timely::communication::allocator::thread::Thread;
+use timely::dataflow::ProbeHandle;
 use timely::worker::Worker;
 use timely::WorkerConfig;
 
-use std::net::SocketAddr;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::num::ParseIntError;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
-use tokio::sync::broadcast::Sender;
+use tokio::time::MissedTickBehavior;
 
 use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::arrange::{Arranged, TraceAgent};
 use differential_dataflow::operators::Consolidate;
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
 
-use crate::dataflows::page_post_ids::posts_post_ids_dataflow;
-use crate::dataflows::post_aggr::post_aggr_dataflow;
-use crate::dataflows::post_liked_by_user::post_liked_by_user_dataflow;
-use crate::dataflows::post_total_likes::post_total_likes_dataflow;
-use crate::dataflows::user_post_count::user_post_count_dataflow;
-use crate::dataflows::user_like_count::user_like_count_dataflow;
+use crate::dataflows::SharedArrangements;
+use crate::registry::DataflowRegistry;
 
 pub type InputFormat = (SocketAddr, (Id, Persisted));
 pub type OutputFormat = Vec<(SocketAddr, QueryResult)>;
@@ -29,12 +35,164 @@ pub type OutputFormat = Vec<(SocketAddr, QueryResult)>;
 pub type PersistedInputSession = InputSession<Time, InputFormat, Diff>;
 
 pub const POSTS_PER_PAGE: usize = 2;
+// how many posts the top and hot listings rank
+pub const RANKED_POSTS_LIMIT: usize = 100;
+// buckets (by post id) the listings rank posts in before ranking the best of every bucket
+pub const RANK_BUCKETS: u64 = 16;
+// every doubling of likes outweighs this much post age (in dataflow time)
+pub const HOT_HALF_LIFE: u64 = 8;
+// reports by different users that hide a post until a moderator reviews it,
+// admins can change it with `Persisted::ReportHideThreshold`
+pub const REPORT_HIDE_THRESHOLD: u64 = 3;
+// wall-clock time that post bursts and new users are counted in
+pub const SPAM_WINDOW: Duration = Duration::from_secs(60);
+// posts with the same body that are flagged as repeated
+pub const REPEATED_BODY_THRESHOLD: u64 = 3;
+// posts of one user within `SPAM_WINDOW` that are flagged as a burst
+pub const POST_BURST_THRESHOLD: u64 = 5;
+// new users from one ip address within `SPAM_WINDOW` that are flagged as a flood
+pub const NEW_USER_FLOOD_THRESHOLD: u64 = 3;
+// wall-clock time a `Typing` record is shown for
+pub const TYPING_WINDOW: Duration = Duration::from_secs(8);
+// how often the records whose window has passed are retracted from `SharedArrangements::recent`
+pub const RECENT_TICK: Duration = Duration::from_secs(1);
+// newest messages of a room that sessions receive, including the ones sent before they joined
+pub const ROOM_BACKFILL: usize = 10;
+// newest notifications of a user that are sent, read or not
+pub const NOTIFICATION_BACKLOG: usize = 20;
+// newest posts that mention a user that are sent to the user
+pub const MENTION_FEED_LENGTH: usize = 20;
+// bytes of markdown a post body can have
+pub const POST_BODY_MAX_LEN: usize = 20_000;
+
+// ids of applied transactions that are remembered to recognize retries,
+// clients retry right after reconnecting, long before this many other transactions are applied
+pub const APPLIED_TRANSACTIONS_LIMIT: usize = 100_000;
+
+/// Comma separated user ids that are admins from the start
+pub const ADMINS_ENV_VAR: &str = "DF_FORUM_ADMINS";
+// the address records that do not come from a session are inserted with
+pub const BACKEND_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
 
 pub struct ForumMinimal {
     pub input: Rc<RefCell<PersistedInputSession>>,
+    // records within their `recent_window`, see `SharedArrangements::recent`
+    pub recent_input: Rc<RefCell<PersistedInputSession>>,
     pub worker: Rc<RefCell<Worker<timely::communication::allocator::Thread>>>,
-    pub persisted_receiver: broadcast::Receiver<(SocketAddr, PersistedItems)>,
+    pub persisted_receiver: broadcast::Receiver<(SocketAddr, Transaction)>,
     pub dataflow_time: u64,
+    pub probe: ProbeHandle<Time>,
+    // output of times the probe has not passed yet
+    pub pending_results: PendingResults,
+    pub query_result_sender: QueryResultSender,
+    // outcome of every transaction with an id, sent after the frame of its dataflow time
+    pub outcome_sender: OutcomeSender,
+    // ids of the latest applied transactions, a transaction that is sent again is not applied twice
+    pub applied_transactions: AppliedTransactions,
+    // current multiplicity of every record that exists at most once
+    pub set_records: HashMap<SetRecord, Diff>,
+    // user id of every session, used to authorize its transactions
+    pub session_users: HashMap<SocketAddr, Id>,
+    // (post id, user id) of the posts created by a session
+    pub post_creators: HashMap<Id, Id>,
+    // (conversation id, participants) of every conversation
+    pub conversations: HashMap<Id, Vec<Id>>,
+    // current flags of the spam dataflow, sent to `BACKEND_ADDR`
+    pub spam_flags: BTreeMap<SpamFlag, Diff>,
+    // the records of `recent_input` by the instant their window passes
+    pub recent_records: BTreeMap<Instant, Vec<InputFormat>>,
+    // users that had a session, only the first session of a user is a new user
+    pub known_users: HashSet<Id>,
+}
+
+/// How long an accepted record is part of `SharedArrangements::recent`
+///
+/// Posts and the first session of a user are counted by the spam dataflow,
+/// users are shown as typing in a chat room.
+pub fn recent_window(persisted: &Persisted) -> Option<Duration> {
+    match persisted {
+        Persisted::Post | Persisted::Session => Some(SPAM_WINDOW),
+        Persisted::Typing(_) => Some(TYPING_WINDOW),
+        _ => None,
+    }
+}
+
+/// The ids of the latest applied transactions, the oldest id is forgotten first
+pub struct AppliedTransactions {
+    ids: HashSet<TransactionId>,
+    order: VecDeque<TransactionId>,
+    limit: usize,
+}
+
+impl AppliedTransactions {
+    pub fn new(limit: usize) -> Self {
+        AppliedTransactions {
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn contains(&self, id: TransactionId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub fn insert(&mut self, id: TransactionId) {
+        if !self.ids.insert(id) {
+            return;
+        }
+
+        self.order.push_back(id);
+        if self.order.len() > self.limit {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+/// A record whose multiplicity is clamped to 0 or 1
+///
+/// A post exists once no matter which session created or deleted it,
+/// a session once per connection.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SetRecord {
+    Post(Id),
+    PostDeleted(Id),
+    PostHidden(Id, String),
+    PostReviewed(Id),
+    Session(SocketAddr, Id),
+    Role(Id, Role),
+    Block(Id, Id),
+    Mute(Id, Id),
+    Conversation(Id),
+    RoomMember(SocketAddr, Id, String),
+}
+
+impl SetRecord {
+    pub fn of(addr: SocketAddr, id: Id, persisted: &Persisted) -> Option<Self> {
+        match persisted {
+            Persisted::Post => Some(SetRecord::Post(id)),
+            Persisted::PostDeleted => Some(SetRecord::PostDeleted(id)),
+            Persisted::PostHidden(reason) => Some(SetRecord::PostHidden(id, reason.clone())),
+            Persisted::PostReviewed => Some(SetRecord::PostReviewed(id)),
+            Persisted::Session => Some(SetRecord::Session(addr, id)),
+            Persisted::UserRole(role) => Some(SetRecord::Role(id, *role)),
+            Persisted::Block(user_id) => Some(SetRecord::Block(id, *user_id)),
+            Persisted::Mute(user_id) => Some(SetRecord::Mute(id, *user_id)),
+            Persisted::Conversation(_) => Some(SetRecord::Conversation(id)),
+            Persisted::JoinRoom(room) => Some(SetRecord::RoomMember(addr, id, room.clone())),
+            _ => None,
+        }
+    }
 }
 
 type ScopeThread = timely::communication::allocator::Thread;
@@ -46,42 +204,70 @@ pub type OutputScopeCollection<'a> =
     differential_dataflow::Collection<ScopeChild<'a>, OutputFormat>;
 
 pub type Collection<'a, D> = differential_dataflow::Collection<ScopeChild<'a>, D>;
+pub type Arrangement<'a, K, V> =
+    Arranged<ScopeChild<'a>, TraceAgent<OrdValSpine<K, V, Time, Diff>>>;
 
-pub type QueryResultSender = broadcast::Sender<(SocketAddr, Vec<QueryResult>)>;
-
-pub fn default_dataflows<'a>(collection: &ScopeCollection<'a>) -> OutputScopeCollection<'a> {
-    posts_post_ids_dataflow(collection)
-        .concat(&post_aggr_dataflow(collection))
-        .concat(&post_liked_by_user_dataflow(collection))
-        .concat(&post_total_likes_dataflow(collection))
-        .concat(&user_post_count_dataflow(collection))
-        .concat(&user_like_count_dataflow(collection))
-}
+pub type QueryResultSender = broadcast::Sender<(SocketAddr, QueryResultFrame)>;
+pub type OutcomeSender = broadcast::Sender<(SocketAddr, TransactionOutcome)>;
+pub type PendingResults = Rc<RefCell<BTreeMap<Time, Vec<(OutputFormat, Diff)>>>>;
 
 impl ForumMinimal {
     pub fn new(
-        persisted_sender: broadcast::Sender<(SocketAddr, PersistedItems)>,
-        query_result_sender: broadcast::Sender<(SocketAddr, Vec<QueryResult>)>,
+        persisted_sender: broadcast::Sender<(SocketAddr, Transaction)>,
+        query_result_sender: QueryResultSender,
+    ) -> Self {
+        Self::new_with_registry(
+            persisted_sender,
+            query_result_sender,
+            DataflowRegistry::default(),
+        )
+    }
+
+    pub fn new_with_registry(
+        persisted_sender: broadcast::Sender<(SocketAddr, Transaction)>,
+        query_result_sender: QueryResultSender,
+        registry: DataflowRegistry,
     ) -> Self {
-        Self::new_with_dataflows(persisted_sender, query_result_sender, default_dataflows)
+        Self::new_with_dataflows(persisted_sender, query_result_sender, move |shared| {
+            registry.build(shared)
+        })
     }
 
-    pub fn new_with_dataflows<F: for<'a> Fn(&ScopeCollection<'a>) -> OutputScopeCollection<'a>>(
-        persisted_sender: broadcast::Sender<(SocketAddr, PersistedItems)>,
-        query_result_sender: broadcast::Sender<(SocketAddr, Vec<QueryResult>)>,
+    pub fn new_with_dataflows<
+        F: for<'a> Fn(&SharedArrangements<'a>) -> OutputScopeCollection<'a>,
+    >(
+        persisted_sender: broadcast::Sender<(SocketAddr, Transaction)>,
+        query_result_sender: QueryResultSender,
         init_dataflows: F,
     ) -> Self {
-        let worker_fn = move |worker: &mut Worker<Thread>| {
+        let mut probe = ProbeHandle::new();
+        let pending_results: PendingResults = Rc::new(RefCell::new(BTreeMap::new()));
+        let pending_results0 = pending_results.clone();
+
+        let worker_fn = |worker: &mut Worker<Thread>| {
             worker.dataflow(|scope| {
                 let mut input: PersistedInputSession = InputSession::new();
+                let mut recent_input: PersistedInputSession = InputSession::new();
                 let collection = input.to_collection(scope);
+                let recent = recent_input.to_collection(scope);
+                let shared = SharedArrangements::new(&collection, &recent);
 
-                // Send everything at once to prevent flickering (but still split by session)
-                init_dataflows(&collection)
+                // a time can come in several batches, they are sent once the probe passes it
+                init_dataflows(&shared)
                     .consolidate()
-                    .inspect_batch(move |_time, aug| batch_send(aug, &query_result_sender));
+                    .inspect_batch(move |_time, aug| {
+                        let mut pending_results = pending_results0.borrow_mut();
 
-                input
+                        for (query_results, time, diff) in aug {
+                            pending_results
+                                .entry(*time)
+                                .or_default()
+                                .push((query_results.clone(), *diff));
+                        }
+                    })
+                    .probe_with(&mut probe);
+
+                (input, recent_input)
             })
         };
 
@@ -89,79 +275,1123 @@ impl ForumMinimal {
         let worker = Worker::new(WorkerConfig::default(), alloc);
         let worker0 = Rc::new(RefCell::new(worker.clone()));
         let worker1 = worker0.clone();
-        let input = worker_fn(&mut worker1.borrow_mut());
-
-        let input0: Rc<RefCell<InputSession<u64, (SocketAddr, (u64, Persisted)), isize>>> =
-            Rc::new(RefCell::new(input));
-        let input1 = input0.clone();
-
-        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
-        input1
-            .borrow_mut()
-            .insert((addr, (0, Persisted::PlusOneDummy)));
+        let (input, recent_input) = worker_fn(&mut worker1.borrow_mut());
 
         ForumMinimal {
-            input: input1,
+            input: Rc::new(RefCell::new(input)),
+            recent_input: Rc::new(RefCell::new(recent_input)),
             worker: worker0,
             persisted_receiver: persisted_sender.subscribe(),
-            dataflow_time: 1,
+            dataflow_time: 0,
+            probe,
+            pending_results,
+            query_result_sender,
+            outcome_sender: broadcast::channel(64).0,
+            applied_transactions: AppliedTransactions::new(APPLIED_TRANSACTIONS_LIMIT),
+            set_records: HashMap::new(),
+            session_users: HashMap::new(),
+            post_creators: HashMap::new(),
+            conversations: HashMap::new(),
+            spam_flags: BTreeMap::new(),
+            recent_records: BTreeMap::new(),
+            known_users: HashSet::new(),
         }
     }
 
     pub async fn advance_dataflow_computation_once(&mut self) {
-        let (addr, persisted_items) = self.persisted_receiver.recv().await.unwrap();
+        let (addr, transaction) = self.persisted_receiver.recv().await.unwrap();
+
+        self.apply_transaction(addr, transaction, Instant::now());
+    }
 
+    /// Applies a transaction at the next dataflow time and sends its results and outcome
+    pub fn apply_transaction(&mut self, addr: SocketAddr, transaction: Transaction, now: Instant) {
         self.dataflow_time += 1;
 
-        for (id, item, diff) in persisted_items {
-            if diff > 0 {
-                self.input.borrow_mut().insert((addr, (id, item)));
-            } else {
-                self.input.borrow_mut().remove((addr, (id, item)));
+        let validation = validate_transaction(&transaction.items)
+            .and_then(|()| self.authorize_transaction(addr, &transaction.items))
+            .and_then(|()| self.check_spam_flags(addr, &transaction.items));
+        // a retried transaction is confirmed again, but applied only once
+        let is_duplicate = transaction
+            .id
+            .is_some_and(|id| self.applied_transactions.contains(id));
+
+        if validation.is_ok() && !is_duplicate {
+            if let Some(id) = transaction.id {
+                self.applied_transactions.insert(id);
+            }
+
+            for (id, item, diff) in transaction.items {
+                let diff = match SetRecord::of(addr, id, &item) {
+                    Some(record) => self.clamp_set_record(record, diff),
+                    None => diff,
+                };
+
+                let is_recent = match item {
+                    Persisted::Session => diff > 0 && self.known_users.insert(id),
+                    _ => diff > 0,
+                };
+                if let Some(window) = recent_window(&item).filter(|_window| is_recent) {
+                    let record = (addr, (id, item.clone()));
+
+                    self.recent_input.borrow_mut().insert(record.clone());
+                    self.recent_records.entry(now + window).or_default().push(record);
+                }
+                // the user stops typing before the window passes, with sending a message
+                if let (Persisted::Typing(_), true) = (&item, diff < 0) {
+                    self.remove_recent_records(&(addr, (id, item.clone())));
+                }
+                // typing is only recent, the main input would keep a record of every keystroke
+                if let Persisted::Typing(_) = item {
+                    continue;
+                }
+
+                match item {
+                    Persisted::Session if diff > 0 => {
+                        self.session_users.insert(addr, id);
+                    }
+                    Persisted::Session if diff < 0 => {
+                        self.session_users.remove(&addr);
+                    }
+                    Persisted::Post if diff > 0 => {
+                        if let Some(user_id) = self.session_users.get(&addr) {
+                            self.post_creators.insert(id, *user_id);
+                        }
+                    }
+                    Persisted::Post if diff < 0 => {
+                        self.post_creators.remove(&id);
+                    }
+                    Persisted::Conversation(ref participants) if diff > 0 => {
+                        self.conversations.insert(id, participants.clone());
+                    }
+                    _ => {}
+                }
+
+                if diff > 0 {
+                    self.input.borrow_mut().insert((addr, (id, item)));
+                } else if diff < 0 {
+                    self.input.borrow_mut().remove((addr, (id, item)));
+                }
             }
         }
-        self.input.borrow_mut().advance_to(self.dataflow_time);
 
-        for _ in 0..1000 {
-            self.input.borrow_mut().flush();
-            self.worker.borrow_mut().step();
+        self.step();
+
+        // the results of the transaction were sent above, the outcome follows them
+        if let Some(transaction_id) = transaction.id {
+            let outcome = match validation {
+                Ok(()) => TransactionOutcome::Confirmed(transaction_id),
+                Err(error) => TransactionOutcome::Rejected(transaction_id, error),
+            };
+
+            // there is no receiver while no session is connected
+            let _ = self.outcome_sender.send((addr, outcome));
         }
     }
+
+    /// Retracts the records whose window has passed from `SharedArrangements::recent`,
+    /// at a dataflow time of their own
+    pub fn expire_recent_records(&mut self, now: Instant) {
+        let still_recent = self.recent_records.split_off(&now);
+        let expired = std::mem::replace(&mut self.recent_records, still_recent);
+
+        if expired.is_empty() {
+            return;
+        }
+
+        self.dataflow_time += 1;
+
+        for record in expired.into_values().flatten() {
+            self.recent_input.borrow_mut().remove(record);
+        }
+
+        self.step();
+    }
+
+    // retracts a record from `SharedArrangements::recent` before its window passes
+    fn remove_recent_records(&mut self, record: &InputFormat) {
+        for records in self.recent_records.values_mut() {
+            records.retain(|recent| {
+                let is_removed = recent == record;
+                if is_removed {
+                    self.recent_input.borrow_mut().remove(recent.clone());
+                }
+                !is_removed
+            });
+        }
+        self.recent_records.retain(|_expiry, records| !records.is_empty());
+    }
+
+    // runs the dataflows up to the current dataflow time and sends the completed results
+    fn step(&mut self) {
+        for input in [&self.input, &self.recent_input] {
+            input.borrow_mut().advance_to(self.dataflow_time);
+            input.borrow_mut().flush();
+        }
+
+        let probe = &self.probe;
+        let input_time = self.dataflow_time;
+        self.worker
+            .borrow_mut()
+            .step_while(|| probe.less_than(&input_time));
+
+        self.send_completed_results();
+    }
+
+    /// Grants a role without a transaction, ie. to the admins configured with `DF_FORUM_ADMINS`
+    pub fn grant_role(&mut self, user_id: Id, role: Role) {
+        if self.clamp_set_record(SetRecord::Role(user_id, role), 1) > 0 {
+            self.input
+                .borrow_mut()
+                .insert((BACKEND_ADDR, (user_id, Persisted::UserRole(role))));
+        }
+    }
+
+    pub fn user_role(&self, user_id: Id) -> Option<Role> {
+        [Role::Admin, Role::Moderator]
+            .into_iter()
+            .find(|role| self.set_records.contains_key(&SetRecord::Role(user_id, *role)))
+    }
+
+    /// Whether the creator of a post blocked the user
+    pub fn is_blocked_by_creator(&self, user_id: Id, post_id: Id) -> bool {
+        self.post_creators.get(&post_id).is_some_and(|creator| {
+            self.set_records
+                .contains_key(&SetRecord::Block(*creator, user_id))
+        })
+    }
+
+    /// Whether the user takes part in a conversation that exists or is started by the transaction
+    pub fn is_participant(&self, user_id: Id, conversation_id: Id, items: &PersistedItems) -> bool {
+        let started = items.iter().find_map(|(id, persisted, diff)| match persisted {
+            Persisted::Conversation(participants) if *id == conversation_id && *diff > 0 => {
+                Some(participants)
+            }
+            _ => None,
+        });
+
+        started
+            .or_else(|| self.conversations.get(&conversation_id))
+            .is_some_and(|participants| participants.contains(&user_id))
+    }
+
+    /// Whether the session is in a room or joins it with the transaction
+    pub fn is_room_member(
+        &self,
+        addr: SocketAddr,
+        user_id: Id,
+        room: &str,
+        items: &PersistedItems,
+    ) -> bool {
+        let joins = items.iter().any(|(id, persisted, diff)| match persisted {
+            Persisted::JoinRoom(other) => *id == user_id && other == room && *diff > 0,
+            _ => false,
+        });
+
+        joins
+            || self
+                .set_records
+                .contains_key(&SetRecord::RoomMember(addr, user_id, room.to_string()))
+    }
+
+    /// The user of the session, including a session the transaction starts
+    pub fn session_user<'a>(
+        &'a self,
+        addr: SocketAddr,
+        items: &'a PersistedItems,
+    ) -> Option<&'a Id> {
+        items
+            .iter()
+            .find_map(|(id, persisted, diff)| {
+                (matches!(persisted, Persisted::Session) && *diff > 0).then_some(id)
+            })
+            .or_else(|| self.session_users.get(&addr))
+    }
+
+    /// Checks that the user of the session may change the records of a transaction
+    ///
+    /// Roles and the report threshold are changed by admins, posts are hidden and reviewed
+    /// by moderators and deleted by their creator or a moderator.
+    /// Posts created without a session have no known creator, only moderators can delete them.
+    /// Users like posts as the user of their session,
+    /// they can not like the posts of users that blocked them.
+    /// Only participants write to a conversation, conversations and messages are never removed.
+    /// Sessions write to the rooms they are in.
+    pub fn authorize_transaction(
+        &self,
+        addr: SocketAddr,
+        items: &PersistedItems,
+    ) -> Result<(), TransactionError> {
+        let user_id = self.session_user(addr, items);
+        let role = user_id.and_then(|user_id| self.user_role(*user_id));
+        let can_moderate = role.is_some_and(|role| role.can_moderate());
+
+        for (id, persisted, diff) in items {
+            let is_creator = self
+                .post_creators
+                .get(id)
+                .is_some_and(|creator| user_id == Some(creator));
+
+            match persisted {
+                Persisted::UserRole(_) if role != Some(Role::Admin) => {
+                    return Err(TransactionError::Unauthorized(
+                        "only admins can change roles".to_string(),
+                    ));
+                }
+                Persisted::ReportHideThreshold(_) if role != Some(Role::Admin) => {
+                    return Err(TransactionError::Unauthorized(
+                        "only admins can change the report threshold".to_string(),
+                    ));
+                }
+                Persisted::PostHidden(_) if !can_moderate => {
+                    return Err(TransactionError::Unauthorized(
+                        "only moderators can hide posts".to_string(),
+                    ));
+                }
+                Persisted::PostReviewed if !can_moderate => {
+                    return Err(TransactionError::Unauthorized(
+                        "only moderators can review posts".to_string(),
+                    ));
+                }
+                // every user reports a post at most once
+                Persisted::Report(_, _) if user_id != Some(id) => {
+                    return Err(TransactionError::Unauthorized(
+                        "reports are made by the user of the session".to_string(),
+                    ));
+                }
+                Persisted::Block(_) | Persisted::Mute(_) if user_id != Some(id) => {
+                    return Err(TransactionError::Unauthorized(
+                        "blocks are made by the user of the session".to_string(),
+                    ));
+                }
+                // one like counts per user and post
+                Persisted::PostLike(_, _) if user_id != Some(id) => {
+                    return Err(TransactionError::Unauthorized(
+                        "likes are made by the user of the session".to_string(),
+                    ));
+                }
+                Persisted::PostLike(post_id, _like)
+                    if *diff > 0 && self.is_blocked_by_creator(*id, *post_id) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "the creator of the post blocked you".to_string(),
+                    ));
+                }
+                Persisted::Conversation(_)
+                | Persisted::DirectMessage(_, _, _)
+                | Persisted::RoomMessage(_, _, _)
+                    if *diff < 0 =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "conversations and messages can not be removed".to_string(),
+                    ));
+                }
+                Persisted::DirectMessage(_, sender, _) | Persisted::RoomMessage(_, sender, _)
+                    if user_id != Some(sender) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "messages are sent by the user of the session".to_string(),
+                    ));
+                }
+                Persisted::Conversation(participants)
+                    if !user_id.is_some_and(|user_id| participants.contains(user_id)) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "conversations are started by one of their participants".to_string(),
+                    ));
+                }
+                Persisted::Conversation(participants)
+                    if self
+                        .conversations
+                        .get(id)
+                        .is_some_and(|existing| existing != participants) =>
+                {
+                    return Err(TransactionError::Invalid(
+                        "the conversation already exists".to_string(),
+                    ));
+                }
+                Persisted::ConversationRead(_, _) if user_id != Some(id) => {
+                    return Err(TransactionError::Unauthorized(
+                        "read receipts are made by the user of the session".to_string(),
+                    ));
+                }
+                Persisted::DirectMessage(conversation_id, _, _)
+                | Persisted::ConversationRead(conversation_id, _)
+                    if !user_id.is_some_and(|user_id| {
+                        self.is_participant(*user_id, *conversation_id, items)
+                    }) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "only participants can use a conversation".to_string(),
+                    ));
+                }
+                Persisted::NotificationsRead(_) if user_id != Some(id) => {
+                    return Err(TransactionError::Unauthorized(
+                        "notifications are read by the user of the session".to_string(),
+                    ));
+                }
+                Persisted::JoinRoom(_) | Persisted::Typing(_) if user_id != Some(id) => {
+                    return Err(TransactionError::Unauthorized(
+                        "rooms are joined by the user of the session".to_string(),
+                    ));
+                }
+                Persisted::RoomMessage(room, _, _) | Persisted::Typing(room)
+                    if !user_id.is_some_and(|user_id| {
+                        self.is_room_member(addr, *user_id, room, items)
+                    }) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "only sessions in the room can write to it".to_string(),
+                    ));
+                }
+                Persisted::PostDeleted if !can_moderate && !is_creator => {
+                    return Err(TransactionError::Unauthorized(
+                        "only the creator or a moderator can delete a post".to_string(),
+                    ));
+                }
+                Persisted::Post if *diff < 0 && !can_moderate && !is_creator => {
+                    return Err(TransactionError::Unauthorized(
+                        "only the creator or a moderator can delete a post".to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // returns the part of `diff` that keeps the multiplicity of the record at 0 or 1
+    fn clamp_set_record(&mut self, record: SetRecord, diff: Diff) -> Diff {
+        let count = self.set_records.get(&record).copied().unwrap_or(0);
+        let clamped = (count + diff).clamp(0, 1);
+
+        if clamped == 0 {
+            self.set_records.remove(&record);
+        } else {
+            self.set_records.insert(record, clamped);
+        }
+
+        clamped - count
+    }
+
+    /// Rejects the posts of users with a burst of posts
+    /// and the sessions of new users from an address new users flood in from
+    pub fn check_spam_flags(
+        &self,
+        addr: SocketAddr,
+        items: &PersistedItems,
+    ) -> Result<(), TransactionError> {
+        let user_id = self.session_user(addr, items);
+        let ip = addr.ip().to_string();
+
+        for (id, persisted, diff) in items {
+            if *diff <= 0 {
+                continue;
+            }
+
+            match persisted {
+                Persisted::Post
+                    if self.spam_flags().any(|flag| {
+                        matches!(flag, SpamFlag::PostBurst(burst_user_id, _count)
+                            if Some(burst_user_id) == user_id)
+                    }) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "too many posts in a short time, try again later".to_string(),
+                    ));
+                }
+                Persisted::Session
+                    if !self.known_users.contains(id)
+                        && self.spam_flags().any(|flag| {
+                            matches!(flag, SpamFlag::NewUserFlood(flood_ip, _count)
+                                if *flood_ip == ip)
+                        }) =>
+                {
+                    return Err(TransactionError::Unauthorized(
+                        "too many new users from your address, try again later".to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spam flags that are currently raised, see `dataflows::spam`
+    pub fn spam_flags(&self) -> impl Iterator<Item = &SpamFlag> {
+        self.spam_flags
+            .iter()
+            .filter(|(_flag, diff)| **diff > 0)
+            .map(|(flag, _diff)| flag)
+    }
+
+    /// Sends the output of every time the probe has passed, one frame per session and time
+    ///
+    /// Results for `BACKEND_ADDR` are kept instead of sent.
+    pub fn send_completed_results(&mut self) {
+        let mut pending_results = self.pending_results.borrow_mut();
+
+        while let Some(entry) = pending_results.first_entry() {
+            if self.probe.less_equal(entry.key()) {
+                break;
+            }
+
+            let (time, mut query_results) = entry.remove_entry();
+
+            for (results, diff) in &mut query_results {
+                results.retain(|(addr, query_result)| match (addr, query_result) {
+                    (&BACKEND_ADDR, QueryResult::SpamFlag(flag)) => {
+                        *self.spam_flags.entry(flag.clone()).or_default() += *diff;
+                        false
+                    }
+                    (addr, _query_result) => *addr != BACKEND_ADDR,
+                });
+            }
+            self.spam_flags.retain(|_flag, diff| *diff != 0);
+
+            batch_send(time, &query_results, &self.query_result_sender);
+        }
+    }
+    /// Applies transactions as they arrive and retracts the records that are no longer recent
     pub async fn loop_advance_dataflow_computation(&mut self) {
+        let mut ticks = tokio::time::interval(RECENT_TICK);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
-            self.advance_dataflow_computation_once().await;
+            tokio::select! {
+                received = self.persisted_receiver.recv() => {
+                    let (addr, transaction) = received.unwrap();
+                    self.apply_transaction(addr, transaction, Instant::now());
+                }
+                _ = ticks.tick() => self.expire_recent_records(Instant::now()),
+            }
         }
     }
 }
+/// Parses the comma separated user ids of `DF_FORUM_ADMINS`
+pub fn parse_user_ids(user_ids: &str) -> Result<Vec<Id>, ParseIntError> {
+    user_ids
+        .split(',')
+        .map(|user_id| user_id.trim())
+        .filter(|user_id| !user_id.is_empty())
+        .map(|user_id| user_id.parse())
+        .collect()
+}
+
+/// Checks a transaction before it is applied, a rejected transaction is not applied at all
+pub fn validate_transaction(items: &PersistedItems) -> Result<(), TransactionError> {
+    for (id, persisted, diff) in items {
+        if *diff <= 0 {
+            continue;
+        }
+
+        match persisted {
+            Persisted::PostTitle(title) if title.trim().is_empty() => {
+                return Err(TransactionError::Invalid("post title is empty".to_string()));
+            }
+            Persisted::PostBody(body) if body.trim().is_empty() => {
+                return Err(TransactionError::Invalid("post body is empty".to_string()));
+            }
+            Persisted::PostBody(body) if body.len() > POST_BODY_MAX_LEN => {
+                return Err(TransactionError::Invalid(format!(
+                    "post body is too long, at most {} bytes",
+                    POST_BODY_MAX_LEN
+                )));
+            }
+            Persisted::Report(_post_id, reason) if reason.trim().is_empty() => {
+                return Err(TransactionError::Invalid("report reason is empty".to_string()));
+            }
+            Persisted::Block(user_id) | Persisted::Mute(user_id) if user_id == id => {
+                return Err(TransactionError::Invalid("users can not block themselves".to_string()));
+            }
+            Persisted::Conversation(participants)
+                if participants.iter().collect::<HashSet<_>>().len() < 2 =>
+            {
+                return Err(TransactionError::Invalid(
+                    "a conversation needs two or more users".to_string(),
+                ));
+            }
+            Persisted::DirectMessage(_conversation_id, _sender, body) if body.trim().is_empty() => {
+                return Err(TransactionError::Invalid("message is empty".to_string()));
+            }
+            Persisted::RoomMessage(_room, _sender, body) if body.trim().is_empty() => {
+                return Err(TransactionError::Invalid("message is empty".to_string()));
+            }
+            Persisted::JoinRoom(room) if room.trim().is_empty() => {
+                return Err(TransactionError::Invalid("room name is empty".to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the changes of one dataflow time, one frame per session
+///
+/// Sessions are sent to in ascending order of their address.
+/// Diffs of the same result are added up and results that cancel out are left out.
+/// Retractions come before additions, so a result that is replaced (ie. a like count)
+/// is first retracted and then added with its new value, both sorted by `QueryResult`.
 pub fn batch_send(
-    query_results_aug: &[(Vec<(SocketAddr, QueryResult)>, u64, isize)],
-    query_result_sender: &Sender<(SocketAddr, Vec<QueryResult>)>,
+    time: Time,
+    query_results: &[(OutputFormat, Diff)],
+    query_result_sender: &QueryResultSender,
 ) {
-    let mut sessions: HashMap<SocketAddr, Vec<QueryResult>> = HashMap::new();
-
-    let query_results = query_results_aug
-        .to_vec()
-        .into_iter()
-        .map(|(qr, _time, _diff)| qr)
-        .flatten()
-        .collect::<Vec<_>>();
+    let mut sessions: BTreeMap<SocketAddr, BTreeMap<QueryResult, Diff>> = BTreeMap::new();
 
     // Break apart query_results by session
 
-    for (session_addr, query_result) in query_results {
-        if None == sessions.get(&session_addr) {
-            sessions.insert(session_addr, Vec::new());
+    for (results, diff) in query_results {
+        for (session_addr, query_result) in results {
+            *sessions
+                .entry(*session_addr)
+                .or_default()
+                .entry(query_result.clone())
+                .or_default() += diff;
         }
-        sessions
-            .get_mut(&session_addr)
-            .expect("session not found")
-            .push(query_result);
     }
 
-    for (session_addr, query_results) in sessions.iter() {
+    for (session_addr, query_results) in sessions {
+        let mut query_results = query_results
+            .into_iter()
+            .filter(|(_query_result, diff)| *diff != 0)
+            .collect::<Vec<_>>();
+
+        if query_results.is_empty() {
+            continue;
+        }
+
+        query_results.sort_by_key(|(_query_result, diff)| *diff > 0);
+
         query_result_sender
             .clone()
-            .send((*session_addr, query_results.clone()))
+            .send((session_addr, (time, query_results)))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use differential_dataflow::operators::Count;
+
+    fn post_ids_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+        shared
+            .collection
+            .filter(|(_addr, (_id, persisted))| *persisted == Persisted::Post)
+            .map(|(addr, (id, _persisted))| vec![(addr, QueryResult::PagePost(id, 0, 0))])
+    }
+
+    // the ids and the number of the posts, built by two operators
+    fn post_ids_and_count_dataflow<'a>(
+        shared: &SharedArrangements<'a>,
+    ) -> OutputScopeCollection<'a> {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let post_count = shared
+            .collection
+            .map(|_| ())
+            .count()
+            .map(move |((), count)| vec![(addr, QueryResult::PostCount(count as u64))]);
+
+        post_ids_dataflow(shared).concat(&post_count)
+    }
+
+    // the number of records in the main collection
+    fn record_count_dataflow<'a>(shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+
+        shared
+            .collection
+            .map(|_| ())
+            .count()
+            .map(move |((), count)| vec![(addr, QueryResult::PostCount(count as u64))])
+    }
+
+    #[tokio::test]
+    pub async fn test_typing_is_only_recent() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            record_count_dataflow,
+        );
+
+        persisted_sender
+            .send((addr, vec![(55, Persisted::Session, 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr, (0, vec![(QueryResult::PostCount(1), 1)])))
+        );
+
+        // every keystroke sends a `Typing`, none of them is kept
+        for diff in [1, 1, 1, -1, 1] {
+            persisted_sender
+                .send((addr, vec![(55, Persisted::Typing("general".into()), diff)].into()))
+                .unwrap();
+            forum_minimal.advance_dataflow_computation_once().await;
+        }
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+        assert_eq!(forum_minimal.recent_records.values().flatten().count(), 1);
+    }
+
+    #[tokio::test]
+    pub async fn test_one_frame_per_time() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            post_ids_and_count_dataflow,
+        );
+
+        persisted_sender
+            .send((addr, vec![(5, Persisted::Post, 1), (6, Persisted::Post, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr, vec![(7, Persisted::Post, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // the results of both operators are sent together, one frame per transaction
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    0,
+                    vec![
+                        (QueryResult::PostCount(2), 1),
+                        (QueryResult::PagePost(5, 0, 0), 1),
+                        (QueryResult::PagePost(6, 0, 0), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PostCount(2), -1),
+                        (QueryResult::PostCount(3), 1),
+                        (QueryResult::PagePost(7, 0, 0), 1),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+        assert!(forum_minimal.pending_results.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_transaction_outcome() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            post_ids_dataflow,
+        );
+        let mut outcome_receiver = forum_minimal.outcome_sender.subscribe();
+
+        persisted_sender
+            .send((
+                addr,
+                Transaction {
+                    id: Some(70),
+                    items: vec![(5, Persisted::Post, 1)],
+                },
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr, (0, vec![(QueryResult::PagePost(5, 0, 0), 1)])))
+        );
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((addr, TransactionOutcome::Confirmed(70)))
+        );
+
+        persisted_sender
+            .send((
+                addr,
+                Transaction {
+                    id: Some(71),
+                    items: vec![
+                        (6, Persisted::Post, 1),
+                        (6, Persisted::PostTitle(" ".into()), 1),
+                    ],
+                },
+            ))
             .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // nothing of the rejected transaction is applied
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((
+                addr,
+                TransactionOutcome::Rejected(
+                    71,
+                    TransactionError::Invalid("post title is empty".into())
+                )
+            ))
+        );
+    }
+
+    #[test]
+    pub fn test_validate_post_body_length() {
+        let body = |len| vec![(5, Persisted::PostBody("a".repeat(len)), 1)];
+
+        assert_eq!(validate_transaction(&body(POST_BODY_MAX_LEN)), Ok(()));
+        assert_eq!(
+            validate_transaction(&body(POST_BODY_MAX_LEN + 1)),
+            Err(TransactionError::Invalid("post body is too long, at most 20000 bytes".into()))
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_duplicate_transaction() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            post_ids_dataflow,
+        );
+        let mut outcome_receiver = forum_minimal.outcome_sender.subscribe();
+
+        let transaction = Transaction {
+            id: Some(70),
+            items: vec![(55, Persisted::Session, 1), (5, Persisted::Post, 1)],
+        };
+        persisted_sender.send((addr, transaction.clone())).unwrap();
+        persisted_sender.send((addr, transaction)).unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr, (0, vec![(QueryResult::PagePost(5, 0, 0), 1)])))
+        );
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((addr, TransactionOutcome::Confirmed(70)))
+        );
+        // the retry is confirmed again without being applied
+        assert_eq!(
+            outcome_receiver.try_recv(),
+            Ok((addr, TransactionOutcome::Confirmed(70)))
+        );
+
+        // a post exists at most once, even when it is inserted by another transaction
+        persisted_sender
+            .send((addr, vec![(5, Persisted::Post, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr, vec![(5, Persisted::Post, -1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr, vec![(5, Persisted::Post, -1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr, (3, vec![(QueryResult::PagePost(5, 0, 0), -1)])))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
+        assert_eq!(
+            forum_minimal.set_records.keys().collect::<Vec<_>>(),
+            vec![&SetRecord::Session(addr, 55)]
+        );
+    }
+
+    #[test]
+    pub fn test_applied_transactions_limit() {
+        let mut applied_transactions = AppliedTransactions::new(2);
+
+        applied_transactions.insert(70);
+        applied_transactions.insert(71);
+        applied_transactions.insert(71);
+        assert!(applied_transactions.contains(70));
+        assert_eq!(applied_transactions.len(), 2);
+
+        // the oldest id makes room for the new one
+        applied_transactions.insert(72);
+        assert!(!applied_transactions.contains(70));
+        assert!(applied_transactions.contains(71));
+        assert!(applied_transactions.contains(72));
+        assert_eq!(applied_transactions.len(), 2);
+    }
+
+    #[tokio::test]
+    pub async fn test_authorize_transaction() {
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, _query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut forum_minimal = ForumMinimal::new_with_dataflows(
+            persisted_sender.clone(),
+            query_result_sender,
+            post_ids_dataflow,
+        );
+        forum_minimal.grant_role(1, Role::Moderator);
+
+        persisted_sender
+            .send((addr0, vec![(1, Persisted::Session, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr1, vec![(55, Persisted::Session, 1), (5, Persisted::Post, 1)].into()))
+            .unwrap();
+        persisted_sender
+            .send((addr2, vec![(56, Persisted::Session, 1)].into()))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        let delete = vec![(5, Persisted::PostDeleted, 1)];
+        let hide = vec![(5, Persisted::PostHidden("spam".into()), 1)];
+        let grant = vec![(56, Persisted::UserRole(Role::Moderator), 1)];
+
+        assert_eq!(forum_minimal.authorize_transaction(addr1, &delete), Ok(()));
+        assert_eq!(forum_minimal.authorize_transaction(addr0, &delete), Ok(()));
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &delete),
+            Err(TransactionError::Unauthorized(
+                "only the creator or a moderator can delete a post".into()
+            ))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(5, Persisted::Post, -1)]),
+            Err(TransactionError::Unauthorized(
+                "only the creator or a moderator can delete a post".into()
+            ))
+        );
+        // posts without a known creator belong to nobody
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(6, Persisted::PostDeleted, 1)]),
+            Err(TransactionError::Unauthorized(
+                "only the creator or a moderator can delete a post".into()
+            ))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr0, &vec![(6, Persisted::PostDeleted, 1)]),
+            Ok(())
+        );
+
+        assert_eq!(forum_minimal.authorize_transaction(addr0, &hide), Ok(()));
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr1, &hide),
+            Err(TransactionError::Unauthorized("only moderators can hide posts".into()))
+        );
+
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr0, &grant),
+            Err(TransactionError::Unauthorized("only admins can change roles".into()))
+        );
+        forum_minimal.grant_role(1, Role::Admin);
+        assert_eq!(forum_minimal.authorize_transaction(addr0, &grant), Ok(()));
+
+        let like = vec![(56, Persisted::PostLike(5, true), 1)];
+
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr0, &like),
+            Err(TransactionError::Unauthorized(
+                "likes are made by the user of the session".into()
+            ))
+        );
+
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(55, Persisted::Block(56), 1)]),
+            Err(TransactionError::Unauthorized(
+                "blocks are made by the user of the session".into()
+            ))
+        );
+        persisted_sender
+            .send((addr1, vec![(55, Persisted::Block(56), 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        // the blocked user can no longer like the creator's posts
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &like),
+            Err(TransactionError::Unauthorized("the creator of the post blocked you".into()))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr0, &vec![(1, Persisted::PostLike(5, true), 1)]),
+            Ok(())
+        );
+
+        // a conversation can be started and written to in one transaction by its participants
+        let conversation = vec![
+            (7, Persisted::Conversation(vec![55, 56]), 1),
+            (8, Persisted::DirectMessage(7, 55, "gg".into()), 1),
+        ];
+
+        assert_eq!(forum_minimal.authorize_transaction(addr1, &conversation), Ok(()));
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr0, &conversation),
+            Err(TransactionError::Unauthorized(
+                "conversations are started by one of their participants".into()
+            ))
+        );
+        persisted_sender
+            .send((addr1, conversation.into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(56, Persisted::ConversationRead(7, 9), 1)]),
+            Ok(())
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr0, &vec![(9, Persisted::DirectMessage(7, 1, "hi".into()), 1)]),
+            Err(TransactionError::Unauthorized(
+                "only participants can use a conversation".into()
+            ))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(9, Persisted::DirectMessage(7, 55, "hi".into()), 1)]),
+            Err(TransactionError::Unauthorized(
+                "messages are sent by the user of the session".into()
+            ))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(7, Persisted::Conversation(vec![1, 56]), 1)]),
+            Err(TransactionError::Invalid("the conversation already exists".into()))
+        );
+
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &vec![(55, Persisted::NotificationsRead(3), 1)]),
+            Err(TransactionError::Unauthorized(
+                "notifications are read by the user of the session".into()
+            ))
+        );
+
+        // sessions write to a room once they joined it
+        let room_message = vec![(10, Persisted::RoomMessage("general".into(), 55, "gg".into()), 1)];
+
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr1, &room_message),
+            Err(TransactionError::Unauthorized(
+                "only sessions in the room can write to it".into()
+            ))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr1, &vec![(56, Persisted::JoinRoom("general".into()), 1)]),
+            Err(TransactionError::Unauthorized(
+                "rooms are joined by the user of the session".into()
+            ))
+        );
+        persisted_sender
+            .send((addr1, vec![(55, Persisted::JoinRoom("general".into()), 1)].into()))
+            .unwrap();
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(forum_minimal.authorize_transaction(addr1, &room_message), Ok(()));
+        assert_eq!(
+            forum_minimal.authorize_transaction(
+                addr2,
+                &vec![(10, Persisted::RoomMessage("general".into(), 56, "gg".into()), 1)]
+            ),
+            Err(TransactionError::Unauthorized(
+                "only sessions in the room can write to it".into()
+            ))
+        );
+        assert_eq!(
+            forum_minimal.authorize_transaction(addr2, &room_message),
+            Err(TransactionError::Unauthorized(
+                "messages are sent by the user of the session".into()
+            ))
+        );
+    }
+
+    #[test]
+    pub fn test_batch_send_order() {
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+
+        batch_send(
+            1,
+            &[
+                (vec![(addr1, QueryResult::PostTotalLikes(5, 1))], 1),
+                (vec![(addr0, QueryResult::PagePost(5, 0, 0))], 1),
+                (vec![(addr1, QueryResult::PostTotalLikes(5, 0))], -1),
+                (vec![(addr2, QueryResult::PagePost(6, 0, 0))], 1),
+                (vec![(addr2, QueryResult::PagePost(6, 0, 0))], -1),
+            ],
+            &query_result_sender,
+        );
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr0, (1, vec![(QueryResult::PagePost(5, 0, 0), 1)])))
+        );
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((
+                addr1,
+                (
+                    1,
+                    vec![
+                        (QueryResult::PostTotalLikes(5, 0), -1),
+                        (QueryResult::PostTotalLikes(5, 1), 1)
+                    ]
+                )
+            ))
+        );
+        // the changes of addr2 cancel out
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        );
     }
 }
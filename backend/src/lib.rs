@@ -7,9 +7,13 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 
+pub mod auth;
 pub mod dataflows;
 pub mod forum_minimal;
 pub mod operators;
+pub mod rate_limit;
+pub mod registry;
+pub mod session_state;
 
 use std::io::Write;
 use std::sync::Once;
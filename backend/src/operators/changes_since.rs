@@ -0,0 +1,42 @@
+use differential_dataflow::difference::Abelian;
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::AsCollection;
+use differential_dataflow::{Collection, ExchangeData};
+use std::ops::Add;
+use timely::dataflow::operators::Map;
+use timely::dataflow::*;
+
+pub trait ChangesSince<G, K, V, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord + Add<Output = G::Timestamp>,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    fn changes_since(&self, window: G::Timestamp) -> Collection<G, (K, V), R>;
+}
+
+impl<G, K, V, R> ChangesSince<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord + Add<Output = G::Timestamp>,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    /// only keeps the changes made in the last `window` ticks of time
+    /// every change is undone again once `window` has passed
+    fn changes_since(&self, window: G::Timestamp) -> Collection<G, (K, V), R> {
+        self.inner
+            .flat_map(move |(data, time, diff)| {
+                let expiry = time.clone() + window.clone();
+                vec![
+                    (data.clone(), time, diff.clone()),
+                    (data, expiry, diff.neg()),
+                ]
+            })
+            .as_collection()
+    }
+}
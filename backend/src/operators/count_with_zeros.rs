@@ -0,0 +1,45 @@
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::*;
+
+pub trait CountWithZeros<G, K>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+{
+    fn count_with_zeros(&self, keys: &Collection<G, K, isize>) -> Collection<G, (K, u64), isize>;
+}
+
+impl<G, K> CountWithZeros<G, K> for Collection<G, K, isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+{
+    /// counts the records of every key in `keys`, a key without (or with no more) records counts 0
+    ///
+    /// keys that are not in `keys` are not counted at all
+    fn count_with_zeros(&self, keys: &Collection<G, K, isize>) -> Collection<G, (K, u64), isize> {
+        self.map(|key| (key, true))
+            .concat(&keys.map(|key| (key, false)))
+            .reduce(|_key, inputs, outputs| {
+                let mut counted = false;
+                let mut count = 0;
+
+                for (is_record, diff) in inputs {
+                    if **is_record {
+                        count += diff;
+                    } else {
+                        counted = *diff > 0;
+                    }
+                }
+
+                if counted {
+                    outputs.push((count.max(0) as u64, 1));
+                }
+            })
+    }
+}
@@ -0,0 +1,38 @@
+use differential_dataflow::difference::Abelian;
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::AsCollection;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::operators::Filter;
+use timely::dataflow::*;
+use timely::order::PartialOrder;
+
+use crate::operators::only_latest::OnlyLatest;
+
+pub trait LatestBefore<G, K, V, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    fn latest_before(&self, before: G::Timestamp) -> Collection<G, (K, V), R>;
+}
+
+impl<G, K, V, R> LatestBefore<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    /// the value every key had just before `before`, changes from then on are ignored
+    fn latest_before(&self, before: G::Timestamp) -> Collection<G, (K, V), R> {
+        self.inner
+            .filter(move |(_, time, _diff)| time.less_than(&before))
+            .as_collection()
+            .only_latest()
+    }
+}
@@ -0,0 +1,32 @@
+use differential_dataflow::difference::Abelian;
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::*;
+
+use crate::operators::select_by_time;
+
+pub trait LatestN<G, K, V, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    fn latest_n(&self, n: usize) -> Collection<G, (K, V), R>;
+}
+
+impl<G, K, V, R> LatestN<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    /// keeps the `n` newest values of every key, older values drop out as new ones arrive
+    fn latest_n(&self, n: usize) -> Collection<G, (K, V), R> {
+        select_by_time(self, move |index, len| index + n >= len)
+    }
+}
@@ -1,2 +1,70 @@
-
+pub mod changes_since;
+pub mod count_with_zeros;
+pub mod latest_before;
+pub mod latest_n;
+pub mod only_earliest;
 pub mod only_latest;
+pub mod top_k;
+
+use differential_dataflow::difference::Abelian;
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::AsCollection;
+use differential_dataflow::{Collection, ExchangeData};
+use std::collections::BTreeMap;
+use timely::dataflow::operators::Map;
+use timely::dataflow::*;
+
+/// Shared core of the temporal operators.
+///
+/// Values are grouped by key and ordered by the time they were last inserted at.
+/// Values whose diffs add up to zero (or less) are dropped before `select` is called
+/// with `(index, len)` of every remaining value, oldest first.
+pub fn select_by_time<G, K, V, R, S>(
+    collection: &Collection<G, (K, V), R>,
+    select: S,
+) -> Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+    S: Fn(usize, usize) -> bool + 'static,
+{
+    collection
+        .inner
+        .map(|((key, value), time, diff)| {
+            // let reduce sort by time
+            ((key, (time.clone(), value)), time, diff)
+        })
+        .as_collection()
+        .reduce(move |_key, inputs, outputs| {
+            let mut totals: BTreeMap<&V, (R, &G::Timestamp)> = BTreeMap::new();
+
+            // inputs are sorted by time, so the last positive diff wins
+            for ((time, value), diff) in inputs {
+                let total = totals.entry(value).or_insert((R::zero(), time));
+                total.0 += diff;
+                if *diff > R::zero() {
+                    total.1 = time;
+                }
+            }
+
+            let mut alive = totals
+                .into_iter()
+                .filter(|(_value, (total, _time))| *total > R::zero())
+                .map(|(value, (total, time))| (time, value, total))
+                .collect::<Vec<_>>();
+            alive.sort();
+
+            let len = alive.len();
+            for (index, (time, value, total)) in alive.into_iter().enumerate() {
+                if select(index, len) {
+                    outputs.push(((time.clone(), value.clone()), total));
+                }
+            }
+        })
+        .map(|(key, (_time, value))| (key, value))
+}
@@ -0,0 +1,32 @@
+use differential_dataflow::difference::Abelian;
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::*;
+
+use crate::operators::select_by_time;
+
+pub trait OnlyEarliest<G, K, V, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    fn only_earliest(&self) -> Collection<G, (K, V), R>;
+}
+
+impl<G, K, V, R> OnlyEarliest<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    /// keeps the first value inserted for every key, later values are ignored while it is alive
+    fn only_earliest(&self) -> Collection<G, (K, V), R> {
+        select_by_time(self, |index, _len| index == 0)
+    }
+}
@@ -0,0 +1,43 @@
+use differential_dataflow::difference::Abelian;
+use differential_dataflow::hashable::Hashable;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::*;
+
+pub trait TopK<G, K, V, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    fn top_k(&self, k: usize) -> Collection<G, (K, (u64, V)), R>;
+}
+
+impl<G, K, V, R> TopK<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: Abelian + ExchangeData,
+{
+    /// keeps the `k` largest values of every key together with their rank (0 is the largest)
+    fn top_k(&self, k: usize) -> Collection<G, (K, (u64, V)), R> {
+        self.reduce(move |_key, inputs, outputs| {
+            // inputs are sorted by value, smallest first
+            let ranked = inputs
+                .iter()
+                .filter(|(_value, diff)| *diff > R::zero())
+                .rev()
+                .take(k)
+                .enumerate();
+
+            for (rank, (value, diff)) in ranked {
+                outputs.push(((rank as u64, (*value).clone()), diff.clone()));
+            }
+        })
+    }
+}
@@ -0,0 +1,378 @@
+use crate::forum_minimal::{Persisted, PersistedItems, TransactionError};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// Limits as `kind=capacity/per_second` pairs, ie. `posts=5/0.2,likes=20/2`,
+/// kinds that are left out keep their default limit
+pub const RATE_LIMITS_ENV_VAR: &str = "DF_FORUM_RATE_LIMITS";
+
+/// Writes that are limited separately, every transaction is also a `Transaction` write
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WriteKind {
+    Transaction,
+    Post,
+    Like,
+    PageChange,
+}
+
+impl WriteKind {
+    pub const ALL: [WriteKind; 4] = [
+        WriteKind::Transaction,
+        WriteKind::Post,
+        WriteKind::Like,
+        WriteKind::PageChange,
+    ];
+
+    pub fn of(persisted: &Persisted, diff: isize) -> Option<Self> {
+        match persisted {
+            Persisted::Post if diff > 0 => Some(WriteKind::Post),
+            Persisted::PostLike(_, _) => Some(WriteKind::Like),
+            Persisted::ViewPostsPage(_)
+            | Persisted::ViewTopPostsPage(_)
+            | Persisted::ViewHotPostsPage(_)
+            | Persisted::Search(_)
+            | Persisted::Query(_)
+                if diff > 0 =>
+            {
+                Some(WriteKind::PageChange)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WriteKind::Transaction => "transactions",
+            WriteKind::Post => "posts",
+            WriteKind::Like => "likes",
+            WriteKind::PageChange => "page_changes",
+        }
+    }
+}
+
+/// A token bucket that holds up to `capacity` writes and refills `per_second` writes a second
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limit {
+    pub capacity: f64,
+    pub per_second: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimits {
+    pub transactions: Limit,
+    pub posts: Limit,
+    pub likes: Limit,
+    pub page_changes: Limit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            // every transaction advances the dataflow time
+            transactions: Limit {
+                capacity: 20.0,
+                per_second: 5.0,
+            },
+            posts: Limit {
+                capacity: 5.0,
+                per_second: 0.2,
+            },
+            likes: Limit {
+                capacity: 20.0,
+                per_second: 2.0,
+            },
+            page_changes: Limit {
+                capacity: 20.0,
+                per_second: 5.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RateLimitsError {
+    UnknownKind(String),
+    InvalidLimit(String),
+}
+
+impl RateLimits {
+    pub fn limit(&self, kind: WriteKind) -> Limit {
+        match kind {
+            WriteKind::Transaction => self.transactions,
+            WriteKind::Post => self.posts,
+            WriteKind::Like => self.likes,
+            WriteKind::PageChange => self.page_changes,
+        }
+    }
+
+    fn limit_mut(&mut self, kind: WriteKind) -> &mut Limit {
+        match kind {
+            WriteKind::Transaction => &mut self.transactions,
+            WriteKind::Post => &mut self.posts,
+            WriteKind::Like => &mut self.likes,
+            WriteKind::PageChange => &mut self.page_changes,
+        }
+    }
+
+    /// Parses `DF_FORUM_RATE_LIMITS`, see `RATE_LIMITS_ENV_VAR`
+    pub fn parse(limits: &str) -> Result<Self, RateLimitsError> {
+        let mut rate_limits = RateLimits::default();
+
+        for pair in limits
+            .split(',')
+            .map(|pair| pair.trim())
+            .filter(|pair| !pair.is_empty())
+        {
+            let invalid = || RateLimitsError::InvalidLimit(pair.to_string());
+
+            let (name, limit) = pair.split_once('=').ok_or_else(invalid)?;
+            let (capacity, per_second) = limit.split_once('/').ok_or_else(invalid)?;
+
+            let kind = WriteKind::ALL
+                .into_iter()
+                .find(|kind| kind.name() == name.trim())
+                .ok_or_else(|| RateLimitsError::UnknownKind(name.trim().to_string()))?;
+
+            let limit = Limit {
+                capacity: capacity.trim().parse().map_err(|_| invalid())?,
+                per_second: per_second.trim().parse().map_err(|_| invalid())?,
+            };
+
+            if limit.capacity < 1.0 || limit.per_second <= 0.0 {
+                return Err(invalid());
+            }
+
+            *rate_limits.limit_mut(kind) = limit;
+        }
+
+        Ok(rate_limits)
+    }
+
+    /// Applies `DF_FORUM_RATE_LIMITS` if it is set
+    pub fn from_env() -> Result<Self, RateLimitsError> {
+        match std::env::var(RATE_LIMITS_ENV_VAR) {
+            Ok(limits) => Self::parse(&limits),
+            Err(_) => Ok(RateLimits::default()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bucket {
+    User(u64),
+    Connection(SocketAddr),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn refilled(&self, limit: Limit, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * limit.per_second).min(limit.capacity)
+    }
+}
+
+/// Token buckets of every connection and of every user, shared by all connections
+///
+/// A transaction takes tokens from the buckets of its connection and of the user of the connection,
+/// a user can not get around the limits by opening more connections.
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: HashMap<(Bucket, WriteKind), TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        RateLimiter {
+            limits,
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn tokens(&self, bucket: Bucket, kind: WriteKind, now: Instant) -> f64 {
+        let limit = self.limits.limit(kind);
+
+        self.buckets
+            .get(&(bucket, kind))
+            .map_or(limit.capacity, |token_bucket| {
+                token_bucket.refilled(limit, now)
+            })
+    }
+
+    /// Takes the tokens for the writes of a transaction, nothing is taken if it is rate limited
+    ///
+    /// A transaction with more writes of a kind than its bucket holds could never be applied,
+    /// it is invalid instead of rate limited.
+    pub fn check(
+        &mut self,
+        addr: SocketAddr,
+        user_id: u64,
+        items: &PersistedItems,
+        now: Instant,
+    ) -> Result<(), TransactionError> {
+        let mut costs: HashMap<WriteKind, f64> = HashMap::from([(WriteKind::Transaction, 1.0)]);
+        for (_id, persisted, diff) in items {
+            if let Some(kind) = WriteKind::of(persisted, *diff) {
+                *costs.entry(kind).or_default() += 1.0;
+            }
+        }
+
+        for (kind, cost) in &costs {
+            let limit = self.limits.limit(*kind);
+
+            if *cost > limit.capacity {
+                return Err(TransactionError::Invalid(format!(
+                    "too many {} in one transaction, at most {}",
+                    kind.name(),
+                    limit.capacity
+                )));
+            }
+        }
+
+        let buckets = [Bucket::Connection(addr), Bucket::User(user_id)];
+        let mut retry_after_secs: f64 = 0.0;
+
+        for bucket in &buckets {
+            for (kind, cost) in &costs {
+                let limit = self.limits.limit(*kind);
+                let tokens = self.tokens(*bucket, *kind, now);
+
+                if tokens < *cost {
+                    retry_after_secs = retry_after_secs.max((cost - tokens) / limit.per_second);
+                }
+            }
+        }
+
+        if retry_after_secs > 0.0 {
+            return Err(TransactionError::RateLimited(
+                (retry_after_secs * 1000.0).ceil() as u64,
+            ));
+        }
+
+        for bucket in &buckets {
+            for (kind, cost) in &costs {
+                let tokens = self.tokens(*bucket, *kind, now);
+
+                self.buckets.insert(
+                    (*bucket, *kind),
+                    TokenBucket {
+                        tokens: tokens - cost,
+                        updated: now,
+                    },
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the buckets of a closed connection and every bucket that is full again
+    pub fn remove_connection(&mut self, addr: SocketAddr, now: Instant) {
+        let limits = self.limits;
+
+        self.buckets.retain(|(bucket, kind), token_bucket| {
+            *bucket != Bucket::Connection(addr)
+                && token_bucket.refilled(limits.limit(*kind), now) < limits.limit(*kind).capacity
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_parse_rate_limits() {
+        let limits = RateLimits::parse("posts=2/0.5, likes = 10/1").unwrap();
+
+        assert_eq!(
+            limits.posts,
+            Limit {
+                capacity: 2.0,
+                per_second: 0.5
+            }
+        );
+        assert_eq!(limits.likes.capacity, 10.0);
+        assert_eq!(limits.transactions, RateLimits::default().transactions);
+
+        assert_eq!(
+            RateLimits::parse("comments=2/1"),
+            Err(RateLimitsError::UnknownKind("comments".into()))
+        );
+        assert_eq!(
+            RateLimits::parse("posts=2"),
+            Err(RateLimitsError::InvalidLimit("posts=2".into()))
+        );
+        assert_eq!(
+            RateLimits::parse("posts=2/0"),
+            Err(RateLimitsError::InvalidLimit("posts=2/0".into()))
+        );
+    }
+
+    #[test]
+    pub fn test_rate_limiter() {
+        let addr0: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let addr1: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let mut limiter = RateLimiter::new(RateLimits {
+            posts: Limit {
+                capacity: 2.0,
+                per_second: 0.5,
+            },
+            ..RateLimits::default()
+        });
+        let now = Instant::now();
+        let post = |id| {
+            vec![
+                (id, Persisted::Post, 1),
+                (id, Persisted::PostTitle("Zerg".into()), 1),
+            ]
+        };
+
+        assert_eq!(limiter.check(addr0, 55, &post(5), now), Ok(()));
+        assert_eq!(limiter.check(addr0, 55, &post(6), now), Ok(()));
+        assert_eq!(
+            limiter.check(addr0, 55, &post(7), now),
+            Err(TransactionError::RateLimited(2000))
+        );
+
+        // the user's bucket is shared by all of its connections
+        assert_eq!(
+            limiter.check(addr1, 55, &post(7), now),
+            Err(TransactionError::RateLimited(2000))
+        );
+        assert_eq!(limiter.check(addr1, 56, &post(7), now), Ok(()));
+
+        // other writes are limited separately
+        assert_eq!(
+            limiter.check(
+                addr0,
+                55,
+                &vec![(55, Persisted::PostLike(5, true), 1)],
+                now
+            ),
+            Ok(())
+        );
+
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.check(addr0, 55, &post(7), later), Ok(()));
+
+        // more posts than the bucket holds are never applied, retrying does not help
+        let posts = [post(8), post(9), post(10)].concat();
+        assert_eq!(
+            limiter.check(addr1, 57, &posts, later),
+            Err(TransactionError::Invalid("too many posts in one transaction, at most 2".into()))
+        );
+
+        limiter.remove_connection(addr0, later);
+        assert!(limiter
+            .buckets
+            .keys()
+            .all(|(bucket, _kind)| *bucket != Bucket::Connection(addr0)));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::dataflows::SharedArrangements;
+use crate::forum_minimal::{OutputFormat, OutputScopeCollection};
+use log::info;
+
+use crate::dataflows;
+
+pub type DataflowFn = for<'a> fn(&SharedArrangements<'a>) -> OutputScopeCollection<'a>;
+
+/// Comma separated names of the dataflows to run, all registered dataflows run if it is not set
+pub const DATAFLOWS_ENV_VAR: &str = "DF_FORUM_DATAFLOWS";
+
+/// A named forum feature, implemented as a single dataflow
+#[derive(Clone, Copy)]
+pub struct DataflowModule {
+    pub name: &'static str,
+    // names of the `Persisted` variants the dataflow reads,
+    // including the ones behind the shared arrangements it uses
+    pub consumes: &'static [&'static str],
+    // names of the `QueryResult` variants the dataflow sends, checked by `test_module_variant_names`
+    pub produces: &'static [&'static str],
+    pub dataflow: DataflowFn,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    UnknownDataflow(String),
+    DuplicateDataflow(String),
+}
+
+/// The dataflows `ForumMinimal` is built from
+///
+/// New features register a `DataflowModule` here (or on a registry passed to
+/// `ForumMinimal::new_with_registry`) instead of being wired up in `forum_minimal.rs`.
+pub struct DataflowRegistry {
+    modules: Vec<(DataflowModule, bool)>,
+}
+
+impl Default for DataflowRegistry {
+    /// Every built-in dataflow, all enabled
+    fn default() -> Self {
+        let mut registry = Self::new();
+
+        for module in [
+            dataflows::page_post_ids::MODULE,
+            dataflows::post_aggr::MODULE,
+            dataflows::post_liked_by_user::MODULE,
+            dataflows::post_total_likes::MODULE,
+            dataflows::user_post_count::MODULE,
+            dataflows::user_like_count::MODULE,
+            dataflows::top_posts::MODULE,
+            dataflows::hot_posts::MODULE,
+            dataflows::search::MODULE,
+            dataflows::post_query::MODULE,
+            dataflows::moderation::MODULE,
+            dataflows::moderation_queue::MODULE,
+            dataflows::spam::MODULE,
+            dataflows::blocks::MODULE,
+            dataflows::direct_messages::MODULE,
+            dataflows::rooms::MODULE,
+            dataflows::notifications::MODULE,
+            dataflows::mentions::MODULE,
+        ] {
+            registry
+                .register(module)
+                .expect("built-in dataflow names are unique");
+        }
+
+        registry
+    }
+}
+
+impl DataflowRegistry {
+    pub fn new() -> Self {
+        DataflowRegistry {
+            modules: Vec::new(),
+        }
+    }
+
+    /// Adds an enabled module
+    pub fn register(&mut self, module: DataflowModule) -> Result<(), RegistryError> {
+        if self
+            .modules
+            .iter()
+            .any(|(other, _)| other.name == module.name)
+        {
+            return Err(RegistryError::DuplicateDataflow(module.name.to_string()));
+        }
+
+        self.modules.push((module, true));
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), RegistryError> {
+        let (_module, module_enabled) = self
+            .modules
+            .iter_mut()
+            .find(|(module, _)| module.name == name)
+            .ok_or_else(|| RegistryError::UnknownDataflow(name.to_string()))?;
+
+        *module_enabled = enabled;
+        Ok(())
+    }
+
+    /// Disables every module not named in the comma separated `names`
+    pub fn enable_only(&mut self, names: &str) -> Result<(), RegistryError> {
+        let names = names
+            .split(',')
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+
+        for name in &names {
+            if !self.modules.iter().any(|(module, _)| module.name == *name) {
+                return Err(RegistryError::UnknownDataflow(name.to_string()));
+            }
+        }
+
+        for (module, enabled) in self.modules.iter_mut() {
+            *enabled = names.contains(&module.name);
+        }
+
+        Ok(())
+    }
+
+    /// Applies `DF_FORUM_DATAFLOWS` if it is set
+    pub fn configure_from_env(&mut self) -> Result<(), RegistryError> {
+        if let Ok(names) = std::env::var(DATAFLOWS_ENV_VAR) {
+            self.enable_only(&names)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn enabled_modules(&self) -> impl Iterator<Item = &DataflowModule> {
+        self.modules
+            .iter()
+            .filter(|(_module, enabled)| *enabled)
+            .map(|(module, _)| module)
+    }
+
+    /// Builds every enabled dataflow and concatenates their results,
+    /// only the shared arrangements they use are built
+    pub fn build<'a>(&self, shared: &SharedArrangements<'a>) -> OutputScopeCollection<'a> {
+        let mut results = shared
+            .collection
+            .flat_map(|_| Vec::<OutputFormat>::new());
+
+        for module in self.enabled_modules() {
+            info!(
+                "dataflow {} (consumes {:?}, produces {:?})",
+                module.name, module.consumes, module.produces
+            );
+
+            results = results.concat(&(module.dataflow)(shared));
+        }
+        info!("shared arrangements {:?}", shared.built());
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forum_minimal::{ForumMinimal, Persisted, QueryResult};
+    use std::net::SocketAddr;
+    use tokio::sync::broadcast;
+
+    #[test]
+    pub fn test_enable_only() {
+        let mut registry = DataflowRegistry::default();
+
+        assert_eq!(registry.enable_only("post_aggr, search"), Ok(()));
+        assert_eq!(
+            registry
+                .enabled_modules()
+                .map(|module| module.name)
+                .collect::<Vec<_>>(),
+            vec!["post_aggr", "search"]
+        );
+
+        assert_eq!(
+            registry.enable_only("post_aggr,no_such_dataflow"),
+            Err(RegistryError::UnknownDataflow("no_such_dataflow".into()))
+        );
+        assert_eq!(
+            registry.register(dataflows::search::MODULE),
+            Err(RegistryError::DuplicateDataflow("search".into()))
+        );
+    }
+
+    /// Whether `name` is a variant of `T`, read from the error of deserializing it
+    fn is_variant<T: serde::de::DeserializeOwned>(name: &str) -> bool {
+        match serde_json::from_value::<T>(serde_json::Value::String(name.to_string())) {
+            Ok(_) => true,
+            Err(err) => !err.to_string().starts_with("unknown variant"),
+        }
+    }
+
+    #[test]
+    pub fn test_module_variant_names() {
+        assert!(is_variant::<Persisted>("Post"));
+        assert!(is_variant::<Persisted>("PostTitle"));
+        assert!(!is_variant::<Persisted>("DeletePost"));
+
+        for module in DataflowRegistry::default().enabled_modules() {
+            for name in module.consumes {
+                assert!(is_variant::<Persisted>(name), "{} consumes {}", module.name, name);
+            }
+            for name in module.produces {
+                assert!(is_variant::<QueryResult>(name), "{} produces {}", module.name, name);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_build_uses_shared_arrangements() {
+        use differential_dataflow::AsCollection;
+        use timely::dataflow::operators::ToStream;
+
+        timely::example(|scope| {
+            let collection = Vec::new().to_stream(scope).as_collection();
+            let recent = Vec::new().to_stream(scope).as_collection();
+            let shared = SharedArrangements::new(&collection, &recent);
+
+            let mut registry = DataflowRegistry::default();
+            registry.enable_only("user_post_count").unwrap();
+            registry.build(&shared);
+
+            // only the arrangements `user_post_count` uses and the ones they are built from
+            assert_eq!(
+                shared.built(),
+                vec!["sessions", "user_sessions", "post_creation_times", "post_creators"]
+            );
+        });
+    }
+
+    #[tokio::test]
+    pub async fn test_registry_dataflows() {
+        crate::init_logger();
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (query_result_sender, mut query_result_receiver) = broadcast::channel(16);
+        let (persisted_sender, _persisted_receiver) = broadcast::channel(16);
+
+        let mut registry = DataflowRegistry::default();
+        registry.enable_only("post_aggr,user_post_count").unwrap();
+        registry.set_enabled("user_post_count", false).unwrap();
+
+        let mut forum_minimal = ForumMinimal::new_with_registry(
+            persisted_sender.clone(),
+            query_result_sender,
+            registry,
+        );
+
+        persisted_sender
+            .send((
+                addr,
+                vec![
+                    (55, Persisted::Session, 1),
+                    (55, Persisted::ViewPostsPage(0), 1),
+                    (5, Persisted::Post, 1),
+                ].into(),
+            ))
+            .unwrap();
+
+        forum_minimal.advance_dataflow_computation_once().await;
+
+        assert_eq!(
+            query_result_receiver.try_recv(),
+            Ok((addr, (0, vec![(QueryResult::PostAggregates(1, 1), 1)])))
+        );
+    }
+}
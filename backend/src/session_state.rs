@@ -0,0 +1,76 @@
+use crate::forum_minimal::{Persisted, PersistedItems};
+use df_forum_frontend::df_tuple_items::{Diff, Id};
+use std::collections::HashMap;
+
+/// The session state records (`Persisted::is_session_state`) a connection inserted
+/// and has not retracted yet
+///
+/// They only mean something while the connection is open, once it is closed
+/// `retractions` takes them out of the dataflows again.
+#[derive(Debug, Default)]
+pub struct SessionRecords {
+    records: HashMap<(Id, Persisted), Diff>,
+}
+
+impl SessionRecords {
+    pub fn track(&mut self, items: &PersistedItems) {
+        for (id, persisted, diff) in items {
+            if !persisted.is_session_state() {
+                continue;
+            }
+
+            let key = (*id, persisted.clone());
+            let count = self.records.entry(key.clone()).or_default();
+            *count += diff;
+            if *count == 0 {
+                self.records.remove(&key);
+            }
+        }
+    }
+
+    /// Retracts every tracked record, sorted to be deterministic
+    pub fn retractions(&mut self) -> PersistedItems {
+        let mut retractions: PersistedItems = self
+            .records
+            .drain()
+            .map(|((id, persisted), count)| (id, persisted, -count))
+            .collect();
+
+        retractions.sort();
+        retractions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_session_records() {
+        let mut session_records = SessionRecords::default();
+
+        session_records.track(&vec![
+            (55, Persisted::Session, 1),
+            (55, Persisted::ViewPostsPage(0), 1),
+            (5, Persisted::Post, 1),
+        ]);
+        session_records.track(&vec![
+            (55, Persisted::ViewPostsPage(0), -1),
+            (55, Persisted::ViewPostsPage(1), 1),
+            (55, Persisted::Search("zerg".into()), 1),
+            (55, Persisted::JoinRoom("lobby".into()), 1),
+            (55, Persisted::JoinRoom("lobby".into()), -1),
+        ]);
+
+        // records that are no session state stay
+        assert_eq!(
+            session_records.retractions(),
+            vec![
+                (55, Persisted::ViewPostsPage(1), -1),
+                (55, Persisted::Search("zerg".into()), -1),
+                (55, Persisted::Session, -1),
+            ]
+        );
+        assert_eq!(session_records.retractions(), vec![]);
+    }
+}
@@ -1,3 +1,5 @@
+// abomonation_derive expands to impls nested inside a const block
+#![allow(non_local_definitions)]
 extern crate differential_dataflow;
 extern crate serde;
 extern crate timely;
@@ -8,7 +10,13 @@ extern crate abomonation;
 extern crate abomonation_derive;
 extern crate df_forum_backend;
 
+use df_forum_backend::operators::changes_since::ChangesSince;
+use df_forum_backend::operators::count_with_zeros::CountWithZeros;
+use df_forum_backend::operators::latest_before::LatestBefore;
+use df_forum_backend::operators::latest_n::LatestN;
+use df_forum_backend::operators::only_earliest::OnlyEarliest;
 use df_forum_backend::operators::only_latest::OnlyLatest;
+use df_forum_backend::operators::top_k::TopK;
 
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -17,6 +25,7 @@ use timely::worker::Worker;
 use timely::WorkerConfig;
 
 use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::Consolidate;
 use differential_dataflow::operators::Count;
 use differential_dataflow::operators::Join;
 use differential_dataflow::operators::Reduce;
@@ -454,7 +463,7 @@ fn aggregation_replacement() {
         vec![
             ((10, post10), 0, 1),
             ((11, post11), 0, 1),
-            ((10, post10), 1, -2),
+            ((10, post10), 1, -1),
             ((10, post10_updated), 1, 1),
         ]
     );
@@ -464,8 +473,427 @@ fn aggregation_replacement() {
         vec![
             ((20, 8), 0, 1),
             ((21, 17), 0, 1),
+            ((20, 3), 1, 1),
             ((20, 8), 1, -1),
-            ((20, 11), 1, 1),
+        ]
+    );
+}
+
+#[test]
+fn only_latest_removal() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut input = InputSession::new();
+            let manages = input.to_collection(scope);
+
+            manages
+                .only_latest()
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            input
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let input = worker_fn(&mut worker);
+
+    let input0 = Rc::new(RefCell::new(input));
+    let input1 = input0.clone();
+    input0.borrow_mut().insert((10, 1));
+    input0.borrow_mut().advance_to(1u64);
+    input0.borrow_mut().insert((10, 2));
+    input0.borrow_mut().advance_to(2u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            input0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(
+        *output1.borrow(),
+        vec![((10, 1), 0, 1), ((10, 1), 1, -1), ((10, 2), 1, 1)]
+    );
+
+    // removing the latest value makes the previous one visible again
+    input1.borrow_mut().remove((10, 2));
+    input1.borrow_mut().advance_to(3u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![
+            ((10, 1), 0, 1),
+            ((10, 1), 1, -1),
+            ((10, 2), 1, 1),
+            ((10, 1), 2, 1),
+            ((10, 2), 2, -1),
+        ]
+    );
+}
+
+#[test]
+fn only_earliest() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut input = InputSession::new();
+            let manages = input.to_collection(scope);
+
+            manages
+                .only_earliest()
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            input
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let input = worker_fn(&mut worker);
+
+    let input0 = Rc::new(RefCell::new(input));
+    let input1 = input0.clone();
+    input0.borrow_mut().insert((10, 5));
+    input0.borrow_mut().insert((11, 7));
+    input0.borrow_mut().advance_to(1u64);
+    input0.borrow_mut().insert((10, 3));
+    input0.borrow_mut().advance_to(2u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            input0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(*output1.borrow(), vec![((10, 5), 0, 1), ((11, 7), 0, 1)]);
+
+    input1.borrow_mut().remove((10, 5));
+    input1.borrow_mut().advance_to(3u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![
+            ((10, 5), 0, 1),
+            ((11, 7), 0, 1),
+            ((10, 5), 2, -1),
+            ((10, 3), 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn latest_n() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut input = InputSession::new();
+            let manages = input.to_collection(scope);
+
+            manages
+                .latest_n(2)
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            input
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let input = worker_fn(&mut worker);
+
+    let input0 = Rc::new(RefCell::new(input));
+    let input1 = input0.clone();
+    input0.borrow_mut().insert((10, 1));
+    input0.borrow_mut().advance_to(1u64);
+    input0.borrow_mut().insert((10, 2));
+    input0.borrow_mut().advance_to(2u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            input0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(*output1.borrow(), vec![((10, 1), 0, 1), ((10, 2), 1, 1)]);
+
+    input1.borrow_mut().insert((10, 3));
+    input1.borrow_mut().advance_to(3u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![
+            ((10, 1), 0, 1),
+            ((10, 2), 1, 1),
+            ((10, 1), 2, -1),
+            ((10, 3), 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn latest_before() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut input = InputSession::new();
+            let manages = input.to_collection(scope);
+
+            manages
+                .latest_before(2)
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            input
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let input = worker_fn(&mut worker);
+
+    let input0 = Rc::new(RefCell::new(input));
+    let input1 = input0.clone();
+    input0.borrow_mut().insert((10, 1));
+    input0.borrow_mut().advance_to(1u64);
+    input0.borrow_mut().insert((10, 2));
+    input0.borrow_mut().advance_to(2u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            input0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(
+        *output1.borrow(),
+        vec![((10, 1), 0, 1), ((10, 1), 1, -1), ((10, 2), 1, 1)]
+    );
+
+    // changes from time 2 on are ignored
+    input1.borrow_mut().insert((10, 3));
+    input1.borrow_mut().remove((10, 2));
+    input1.borrow_mut().advance_to(3u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![((10, 1), 0, 1), ((10, 1), 1, -1), ((10, 2), 1, 1)]
+    );
+}
+
+#[test]
+fn changes_since() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut input = InputSession::new();
+            let manages = input.to_collection(scope);
+
+            manages
+                .changes_since(2)
+                .consolidate()
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            input
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let input = worker_fn(&mut worker);
+
+    let input0 = Rc::new(RefCell::new(input));
+    let input1 = input0.clone();
+    input0.borrow_mut().insert((10, 1));
+    input0.borrow_mut().advance_to(1u64);
+    input0.borrow_mut().insert((11, 1));
+    input0.borrow_mut().advance_to(2u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            input0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(*output1.borrow(), vec![((10, 1), 0, 1), ((11, 1), 1, 1)]);
+
+    // the change made at time 0 leaves the window at time 2
+    input1.borrow_mut().advance_to(3u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![((10, 1), 0, 1), ((11, 1), 1, 1), ((10, 1), 2, -1)]
+    );
+
+    input1.borrow_mut().advance_to(4u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![
+            ((10, 1), 0, 1),
+            ((11, 1), 1, 1),
+            ((10, 1), 2, -1),
+            ((11, 1), 3, -1),
+        ]
+    );
+}
+
+#[test]
+fn top_k() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut input = InputSession::new();
+            let manages = input.to_collection(scope);
+
+            manages
+                .top_k(2)
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            input
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let input = worker_fn(&mut worker);
+
+    let input0 = Rc::new(RefCell::new(input));
+    let input1 = input0.clone();
+    input0.borrow_mut().insert((10, 5));
+    input0.borrow_mut().insert((10, 3));
+    input0.borrow_mut().insert((10, 1));
+    input0.borrow_mut().advance_to(1u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            input0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(
+        *output1.borrow(),
+        vec![((10, (0, 5)), 0, 1), ((10, (1, 3)), 0, 1)]
+    );
+
+    input1.borrow_mut().insert((10, 4));
+    input1.borrow_mut().advance_to(2u64);
+
+    go();
+    assert_eq!(
+        *output1.borrow(),
+        vec![
+            ((10, (0, 5)), 0, 1),
+            ((10, (1, 3)), 0, 1),
+            ((10, (1, 3)), 1, -1),
+            ((10, (1, 4)), 1, 1),
+        ]
+    );
+}
+
+#[test]
+fn count_with_zeros() {
+    let output0 = Rc::new(RefCell::new(Vec::new()));
+    let output1 = output0.clone();
+
+    let worker_fn = move |worker: &mut Worker<Thread>| {
+        worker.dataflow(|scope| {
+            let mut records_input = InputSession::new();
+            let mut keys_input = InputSession::new();
+            let records = records_input.to_collection(scope);
+            let keys = keys_input.to_collection(scope);
+
+            records
+                .count_with_zeros(&keys)
+                .inspect(move |v| output0.borrow_mut().push(*v));
+
+            (records_input, keys_input)
+        })
+    };
+
+    let alloc = Thread::new();
+    let mut worker = Worker::new(WorkerConfig::default(), alloc);
+    let (records_input, keys_input) = worker_fn(&mut worker);
+
+    let records0 = Rc::new(RefCell::new(records_input));
+    let records1 = records0.clone();
+    let keys0 = Rc::new(RefCell::new(keys_input));
+    let keys1 = keys0.clone();
+
+    keys0.borrow_mut().insert(10);
+    keys0.borrow_mut().insert(20);
+    records0.borrow_mut().insert(10);
+    records0.borrow_mut().insert(10);
+    // not counted, 30 is not a key
+    records0.borrow_mut().insert(30);
+    keys0.borrow_mut().advance_to(1u64);
+    records0.borrow_mut().advance_to(1u64);
+
+    let mut go = move || {
+        for _ in 0..10 {
+            records0.borrow_mut().flush();
+            keys0.borrow_mut().flush();
+            worker.step();
+        }
+    };
+
+    go();
+
+    assert_eq!(
+        *output1.borrow(),
+        vec![((10, 2), 0, 1), ((20, 0), 0, 1)]
+    );
+
+    records1.borrow_mut().remove(10);
+    records1.borrow_mut().remove(10);
+    records1.borrow_mut().advance_to(2u64);
+    keys1.borrow_mut().advance_to(2u64);
+
+    go();
+
+    assert_eq!(
+        *output1.borrow(),
+        vec![
+            ((10, 2), 0, 1),
+            ((20, 0), 0, 1),
+            ((10, 0), 1, 1),
+            ((10, 2), 1, -1),
         ]
     );
 }
@@ -2,70 +2,386 @@ use web_sys::Event;
 use web_sys::MessageEvent as WebSocketMessageEvent;
 use web_sys::WebSocket;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use crate::log;
-use crate::persisted::PersistedItems;
-use crate::query_result::QueryResult;
+use crate::outbox::{Outbox, OUTBOX_LOCAL_STORAGE_KEY};
+use crate::persisted::{PersistedItems, Transaction, TransactionId};
+use crate::{get_local_storage, get_random_u64, log};
+use crate::query_result::{ServerMessage, TransactionError, TransactionOutcome};
+
+// the first reconnection attempt waits 500ms, every further one twice as long, at most 30s
+pub const RECONNECT_BASE_DELAY_MS: i32 = 500;
+pub const RECONNECT_MAX_DELAY_MS: i32 = 30_000;
+// the token the backend issued for the user, sent when connecting to be the same user again
+pub const TOKEN_LOCAL_STORAGE_KEY: &str = "df_forum_token";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connected,
+    // the connection was lost, attempt number `attempt` starts in `delay_ms`
+    Reconnecting { attempt: u32, delay_ms: i32 },
+}
+
+// what happens to a queued transaction once its outcome arrived
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutboxAction {
+    Remove(TransactionId),
+    // the backend did not apply it because of its rate limit, it is sent again after `delay_ms`
+    ResendAfter { id: TransactionId, delay_ms: i32 },
+}
+
+/// Confirmed transactions and invalid or unauthorized ones leave the outbox,
+/// rate limited ones stay until the backend accepts them
+pub fn outbox_action(outcome: &TransactionOutcome) -> OutboxAction {
+    match outcome {
+        TransactionOutcome::Rejected(id, TransactionError::RateLimited(retry_after_ms)) => {
+            OutboxAction::ResendAfter {
+                id: *id,
+                delay_ms: i32::try_from(*retry_after_ms).unwrap_or(i32::MAX),
+            }
+        }
+        TransactionOutcome::Confirmed(id) | TransactionOutcome::Rejected(id, _) => {
+            OutboxAction::Remove(*id)
+        }
+    }
+}
+
+pub fn reconnect_delay_ms(attempt: u32) -> i32 {
+    RECONNECT_BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(RECONNECT_MAX_DELAY_MS)
+}
 
 pub struct FrontendConnection {
     pub websocket: Rc<RefCell<WebSocket>>,
-    pub onmessage: Option<fn(Vec<QueryResult>) -> ()>,
+    state: Rc<ConnectionState>,
 }
 
-impl FrontendConnection {
-    pub fn new(url: &str) -> Self {
-        let websocket = Rc::new(RefCell::new(WebSocket::new(url).unwrap()));
-        let websocket0 = websocket.clone();
+type EventHandler = RefCell<Option<Closure<dyn FnMut(Event)>>>;
+type MessageHandler = RefCell<Option<Closure<dyn FnMut(WebSocketMessageEvent)>>>;
+type StatusHandler = RefCell<Option<Box<dyn Fn(ConnectionStatus)>>>;
+type ServerMessageHandler = RefCell<Option<Box<dyn Fn(ServerMessage)>>>;
+type OpenHandler = RefCell<Option<Box<dyn FnMut(u64)>>>;
 
-        let onclose = Closure::<dyn FnMut(Event)>::new(move |_event: Event| {
-            log(&format!("websocket closed"));
+// shared with the websocket callbacks, the callbacks are attached again to every new websocket
+#[derive(Default)]
+struct ConnectionState {
+    url: String,
+    outbox: RefCell<Outbox>,
+    reconnect_attempts: Cell<u32>,
+    // the user the backend authenticated the first connection as
+    user_id: Cell<Option<u64>>,
+    onopen: OpenHandler,
+    onreconnect: RefCell<Option<Box<dyn Fn()>>>,
+    onstatus: StatusHandler,
+    on_server_message: ServerMessageHandler,
+    websocket_onopen: EventHandler,
+    websocket_onclose: EventHandler,
+    websocket_onmessage: MessageHandler,
+}
+
+impl ConnectionState {
+    fn websocket_url(&self) -> String {
+        match get_local_storage().get_item(TOKEN_LOCAL_STORAGE_KEY).ok().flatten() {
+            Some(token) => format!("{}/?token={}", self.url, token),
+            None => self.url.clone(),
+        }
+    }
+
+    fn attach(&self, websocket: &WebSocket) {
+        if let Some(onopen) = self.websocket_onopen.borrow().as_ref() {
+            websocket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        }
+        if let Some(onclose) = self.websocket_onclose.borrow().as_ref() {
+            websocket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        }
+        if let Some(onmessage) = self.websocket_onmessage.borrow().as_ref() {
+            websocket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        }
+    }
+
+    fn store_outbox(&self) {
+        let json = self.outbox.borrow().to_json();
+
+        if get_local_storage()
+            .set_item(OUTBOX_LOCAL_STORAGE_KEY, &json)
+            .is_err()
+        {
+            log("could not store outbox");
+        }
+    }
+
+    // queues the transaction until its outcome arrives and sends it if the websocket is open
+    fn send(&self, websocket: &WebSocket, transaction: Transaction) {
+        if self.outbox.borrow_mut().push(transaction.clone()) {
+            self.store_outbox();
+        }
+
+        self.send_now(websocket, &transaction);
+    }
+
+    fn send_now(&self, websocket: &WebSocket, transaction: &Transaction) {
+        let msg = serde_json::to_string(transaction).unwrap();
+
+        if websocket.ready_state() == WebSocket::OPEN && websocket.send_with_str(&msg).is_ok() {
+            log(&("sending: ".to_string() + &msg));
+        } else {
+            log(&("offline, queued: ".to_string() + &msg));
+        }
+    }
+
+    // sends every transaction without an outcome again, in the order they were made,
+    // including the ones that were sent on a connection that was lost before the outcome arrived
+    fn resend_outbox(&self, websocket: &WebSocket) {
+        let transactions = self.outbox.borrow().transactions().to_vec();
+
+        for transaction in &transactions {
+            self.send_now(websocket, transaction);
+        }
+    }
+
+    // the transaction was confirmed or rejected, it is not sent again
+    fn acknowledge(&self, id: TransactionId) {
+        if self.outbox.borrow_mut().remove(id) {
+            self.store_outbox();
+        }
+    }
+
+    // sends a rate limited transaction again on the websocket of that time,
+    // unless a reconnection got it confirmed or rejected for good in between
+    fn resend_later(
+        state: &Rc<Self>,
+        websocket: &Rc<RefCell<WebSocket>>,
+        id: TransactionId,
+        delay_ms: i32,
+    ) {
+        let state = state.clone();
+        let websocket = websocket.clone();
+        let resend = Closure::once_into_js(move || {
+            let transaction = state.outbox.borrow().get(id).cloned();
+
+            if let Some(transaction) = transaction {
+                state.send_now(&websocket.borrow(), &transaction);
+            }
         });
 
-        websocket0
-            .borrow()
-            .set_onclose(Some(onclose.as_ref().unchecked_ref()));
-        onclose.forget();
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(resend.unchecked_ref(), delay_ms)
+            .unwrap();
+    }
+
+    // the backend bound the connection to the user, only now the session can be started
+    fn authenticated(&self, websocket: &WebSocket, user_id: u64, token: &str) {
+        if get_local_storage()
+            .set_item(TOKEN_LOCAL_STORAGE_KEY, token)
+            .is_err()
+        {
+            log("could not store token");
+        }
 
-        FrontendConnection {
-            websocket: websocket0.clone(),
-            onmessage: None,
+        match self.user_id.replace(Some(user_id)) {
+            None => {
+                if let Some(onopen) = self.onopen.borrow_mut().as_mut() {
+                    onopen(user_id);
+                }
+            }
+            Some(previous_user_id) if previous_user_id == user_id => {
+                if let Some(onreconnect) = self.onreconnect.borrow().as_ref() {
+                    onreconnect();
+                }
+            }
+            // the backend no longer knows the token, the page belongs to a different user
+            Some(_) => {
+                web_sys::window().unwrap().location().reload().unwrap();
+                return;
+            }
         }
+
+        self.resend_outbox(websocket);
     }
 
-    pub fn set_onopen(&self, onopen: Closure<dyn FnMut(Event)>) {
-        self.websocket
-            .borrow()
-            .set_onopen(Some(onopen.as_ref().unchecked_ref()));
-        onopen.forget();       
+    fn set_status(&self, status: ConnectionStatus) {
+        if let Some(onstatus) = self.onstatus.borrow().as_ref() {
+            onstatus(status);
+        }
     }
+}
+
+impl FrontendConnection {
+    pub fn new(url: &str) -> Self {
+        let stored_outbox = get_local_storage()
+            .get_item(OUTBOX_LOCAL_STORAGE_KEY)
+            .ok()
+            .flatten();
+        let state = Rc::new(ConnectionState {
+            url: url.to_string(),
+            outbox: RefCell::new(Outbox::from_json(stored_outbox.as_deref())),
+            ..ConnectionState::default()
+        });
+        let websocket = Rc::new(RefCell::new(WebSocket::new(&state.websocket_url()).unwrap()));
+
+        let state0 = state.clone();
+        let onopen = Closure::<dyn FnMut(Event)>::new(move |_event: Event| {
+            log("websocket opened");
+
+            state0.reconnect_attempts.set(0);
+            state0.set_status(ConnectionStatus::Connected);
+        });
+
+        let state1 = state.clone();
+        let websocket1 = websocket.clone();
+        let onclose = Closure::<dyn FnMut(Event)>::new(move |_event: Event| {
+            let attempt = state1.reconnect_attempts.get() + 1;
+            let delay_ms = reconnect_delay_ms(attempt - 1);
+            state1.reconnect_attempts.set(attempt);
 
-    pub fn init_on_parsed_message(&self, on_parsed_message: Box<dyn Fn(Vec<QueryResult>)>) {
+            log(&format!("websocket closed, reconnecting in {}ms", delay_ms));
+            state1.set_status(ConnectionStatus::Reconnecting { attempt, delay_ms });
+
+            let state2 = state1.clone();
+            let websocket2 = websocket1.clone();
+            let reconnect = Closure::once_into_js(move || {
+                match WebSocket::new(&state2.websocket_url()) {
+                    Ok(new_websocket) => {
+                        state2.attach(&new_websocket);
+                        *websocket2.borrow_mut() = new_websocket;
+                    }
+                    Err(_) => log("could not create websocket"),
+                }
+            });
+
+            web_sys::window()
+                .unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    reconnect.unchecked_ref(),
+                    delay_ms,
+                )
+                .unwrap();
+        });
+
+        let state3 = state.clone();
+        let websocket3 = websocket.clone();
         let onmessage = Closure::<dyn FnMut(WebSocketMessageEvent)>::new(
             move |message: WebSocketMessageEvent| {
                 let data = message.data().as_string().unwrap();
                 log(&format!("got websocket message: {:?}", data));
 
-                let parsed_data: Vec<QueryResult> =
-                    serde_json::from_str(&data).expect("could not parse QueryResults");
+                let server_message: ServerMessage =
+                    serde_json::from_str(&data).expect("could not parse ServerMessage");
+
+                match &server_message {
+                    ServerMessage::Authenticated(user_id, token) => {
+                        state3.authenticated(&websocket3.borrow(), *user_id, token);
+                        return;
+                    }
+                    ServerMessage::Frame((time, _query_results)) => {
+                        log(&format!("query results of dataflow time {}", time));
+                    }
+                    ServerMessage::Outcome(outcome) => match outbox_action(outcome) {
+                        OutboxAction::Remove(id) => state3.acknowledge(id),
+                        OutboxAction::ResendAfter { id, delay_ms } => {
+                            ConnectionState::resend_later(&state3, &websocket3, id, delay_ms)
+                        }
+                    },
+                }
 
-                on_parsed_message(parsed_data);
+                if let Some(on_server_message) = state3.on_server_message.borrow().as_ref() {
+                    on_server_message(server_message);
+                }
             },
         );
-        self.websocket
-            .borrow()
-            .set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
-        onmessage.forget();
+
+        *state.websocket_onopen.borrow_mut() = Some(onopen);
+        *state.websocket_onclose.borrow_mut() = Some(onclose);
+        *state.websocket_onmessage.borrow_mut() = Some(onmessage);
+        state.attach(&websocket.borrow());
+
+        FrontendConnection { websocket, state }
+    }
+
+    // called with the user of the first connection once the backend authenticated it
+    pub fn set_onopen(&self, onopen: Box<dyn FnMut(u64)>) {
+        *self.state.onopen.borrow_mut() = Some(onopen);
     }
 
-    pub fn send_transaction(&self, persisted_items: PersistedItems) {
-        let msg = serde_json::to_string(&persisted_items).unwrap();
-        log(&("sending: ".to_string() + &msg));
+    // called instead of onopen once a lost connection is back, the new connection is a new session
+    pub fn set_onreconnect(&self, onreconnect: Box<dyn Fn()>) {
+        *self.state.onreconnect.borrow_mut() = Some(onreconnect);
+    }
 
-        self.websocket.clone().borrow().send_with_str(&msg).unwrap();
+    pub fn set_onstatus(&self, onstatus: Box<dyn Fn(ConnectionStatus)>) {
+        *self.state.onstatus.borrow_mut() = Some(onstatus);
+    }
+
+    // called with every frame and outcome, the authentication is handled by the connection
+    pub fn init_on_parsed_message(&self, on_parsed_message: Box<dyn Fn(ServerMessage)>) {
+        *self.state.on_server_message.borrow_mut() = Some(on_parsed_message);
+    }
+
+    /// Sends the items as one transaction, it stays in the outbox and is sent again
+    /// on every new connection until the backend confirms or rejects it,
+    /// a transaction rejected by the rate limit is sent again once the limit allows it
+    pub fn send_transaction(&self, persisted_items: PersistedItems) -> TransactionId {
+        let id = get_random_u64();
+        let transaction = Transaction {
+            id: Some(id),
+            items: persisted_items,
+        };
+
+        self.state.send(&self.websocket.borrow(), transaction);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_reconnect_delay() {
+        assert_eq!(reconnect_delay_ms(0), 500);
+        assert_eq!(reconnect_delay_ms(1), 1000);
+        assert_eq!(reconnect_delay_ms(3), 4000);
+        assert_eq!(reconnect_delay_ms(6), RECONNECT_MAX_DELAY_MS);
+        assert_eq!(reconnect_delay_ms(u32::MAX), RECONNECT_MAX_DELAY_MS);
+    }
+
+    #[test]
+    pub fn test_outbox_action() {
+        assert_eq!(
+            outbox_action(&TransactionOutcome::Confirmed(70)),
+            OutboxAction::Remove(70)
+        );
+        assert_eq!(
+            outbox_action(&TransactionOutcome::Rejected(
+                71,
+                TransactionError::Invalid("post title is empty".into())
+            )),
+            OutboxAction::Remove(71)
+        );
+        assert_eq!(
+            outbox_action(&TransactionOutcome::Rejected(
+                72,
+                TransactionError::Unauthorized("only moderators can hide posts".into())
+            )),
+            OutboxAction::Remove(72)
+        );
+        // rate limited transactions stay queued
+        assert_eq!(
+            outbox_action(&TransactionOutcome::Rejected(73, TransactionError::RateLimited(2000))),
+            OutboxAction::ResendAfter { id: 73, delay_ms: 2000 }
+        );
+        assert_eq!(
+            outbox_action(&TransactionOutcome::Rejected(
+                74,
+                TransactionError::RateLimited(u64::MAX)
+            )),
+            OutboxAction::ResendAfter { id: 74, delay_ms: i32::MAX }
+        );
     }
 }
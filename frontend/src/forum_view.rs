@@ -0,0 +1,963 @@
+use std::collections::BTreeMap;
+
+use crate::df_tuple_items::Diff;
+use crate::persisted::{Role, TransactionId};
+use crate::query_result::{
+    Notification, QueryResult, QueryResultChanges, SpamFlag, TransactionError, TransactionOutcome,
+};
+
+/// A post as it is rendered, fields that have not arrived yet are `None`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PostView {
+    pub id: u64,
+    pub page: u64,
+    pub time: u64,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub creator: Option<String>,
+    pub total_likes: Option<u64>,
+    pub liked_by_user: bool,
+    // ids of the users the body mentions, smallest first
+    pub mentions: Vec<u64>,
+}
+
+/// A soft deleted or hidden post, only moderators see them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HiddenPostView {
+    pub id: u64,
+    pub title: Option<String>,
+    // reason of the moderator that hid the post, `None` if it was deleted
+    pub reason: Option<String>,
+}
+
+/// A reported post in the moderation queue, only moderators see them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueuedPostView {
+    pub id: u64,
+    pub title: Option<String>,
+    pub report_count: u64,
+    pub rank: u64,
+    pub reasons: Vec<String>,
+}
+
+/// A user the session's user blocked or muted
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HiddenUserView {
+    pub user_id: u64,
+    // blocked users can also not like the user's posts, muted users only are not seen
+    pub blocked: bool,
+}
+
+/// A flag of the spam dataflow, only moderators see them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpamFlagView {
+    pub flag: SpamFlag,
+    // title of the flagged post, `None` for flags of users and addresses
+    pub title: Option<String>,
+}
+
+/// A conversation the session's user takes part in
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConversationView {
+    pub id: u64,
+    pub participants: Vec<u64>,
+    // messages of the other participants the user has not read, `None` until it arrives
+    pub unread: Option<u64>,
+}
+
+/// A direct message of a conversation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageView {
+    pub id: u64,
+    pub sender: u64,
+    pub time: u64,
+    pub body: String,
+    // participants other than the sender that read the message
+    pub read_by: Vec<u64>,
+}
+
+/// A chat room the session is in
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoomView {
+    pub name: String,
+    // users with a session in the room
+    pub online: Vec<u64>,
+    pub typing: Vec<u64>,
+    // the newest messages, oldest first, rooms have no read receipts
+    pub messages: Vec<MessageView>,
+}
+
+/// A notification of the session's user
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NotificationView {
+    pub notification: Notification,
+    pub time: u64,
+    pub unread: bool,
+    // title of the post the notification is about, `None` until it arrives
+    pub title: Option<String>,
+}
+
+/// Everything the posts page shows, built from the query results of one session
+///
+/// Results are kept together with their summed up diffs, so batches can be ingested in any order:
+/// a retraction that arrives before its result cancels it once the result arrives,
+/// and fields that arrive before their post show up as soon as the post does.
+///
+/// The expected changes of a transaction can be applied right away with `apply_optimistic`,
+/// they are dropped once the transaction is confirmed (its real results arrived in the frame before)
+/// or rejected, which rolls them back.
+/// Rate limited transactions are sent again by the connection, their changes are kept.
+#[derive(Clone, Debug, Default)]
+pub struct ForumView {
+    results: BTreeMap<QueryResult, Diff>,
+    optimistic: BTreeMap<TransactionId, QueryResultChanges>,
+    rejections: Vec<(TransactionId, TransactionError)>,
+}
+
+impl ForumView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ingest(&mut self, changes: QueryResultChanges) {
+        for (query_result, diff) in changes {
+            add_diff(&mut self.results, query_result, diff);
+        }
+    }
+
+    pub fn ingest_outcome(&mut self, outcome: TransactionOutcome) {
+        match outcome {
+            TransactionOutcome::Confirmed(id) => {
+                self.optimistic.remove(&id);
+            }
+            TransactionOutcome::Rejected(id, error) => {
+                if !matches!(error, TransactionError::RateLimited(_)) {
+                    self.optimistic.remove(&id);
+                }
+                self.rejections.push((id, error));
+            }
+        }
+    }
+
+    pub fn apply_optimistic(&mut self, id: TransactionId, changes: QueryResultChanges) {
+        self.optimistic.insert(id, changes);
+    }
+
+    /// Transactions the server rejected since the last call
+    pub fn take_rejections(&mut self) -> Vec<(TransactionId, TransactionError)> {
+        std::mem::take(&mut self.rejections)
+    }
+
+    /// Forgets every result but keeps the unconfirmed optimistic changes,
+    /// used when the results of a new session replace the ones of a lost connection
+    pub fn clear_results(&mut self) {
+        self.results.clear();
+    }
+
+    fn current(&self) -> impl Iterator<Item = QueryResult> {
+        let mut results = self.results.clone();
+
+        for changes in self.optimistic.values() {
+            for (query_result, diff) in changes {
+                add_diff(&mut results, query_result.clone(), *diff);
+            }
+        }
+
+        results
+            .into_iter()
+            .filter(|(_query_result, count)| *count > 0)
+            .map(|(query_result, _count)| query_result)
+    }
+
+    pub fn post(&self, id: u64) -> Option<PostView> {
+        self.posts().into_iter().find(|post| post.id == id)
+    }
+
+    /// Posts on the current page, newest first
+    pub fn posts(&self) -> Vec<PostView> {
+        let mut posts: BTreeMap<u64, PostView> = BTreeMap::new();
+
+        let current: Vec<QueryResult> = self.current().collect();
+
+        for query_result in &current {
+            if let QueryResult::PagePost(id, page, time) = query_result {
+                posts.insert(
+                    *id,
+                    PostView {
+                        id: *id,
+                        page: *page,
+                        time: *time,
+                        ..PostView::default()
+                    },
+                );
+            }
+        }
+
+        fill_post_fields(&current, &mut posts);
+
+        let mut posts: Vec<PostView> = posts.into_values().collect();
+        posts.sort_by(|a, b| b.time.cmp(&a.time).then(a.id.cmp(&b.id)));
+        posts
+    }
+
+    /// Posts that mention the session's user, newest first, their page is always 0
+    pub fn mention_feed(&self) -> Vec<PostView> {
+        let current: Vec<QueryResult> = self.current().collect();
+
+        let mut posts: BTreeMap<u64, PostView> = current
+            .iter()
+            .filter_map(|query_result| match query_result {
+                QueryResult::MentionFeedPost(id, time) => Some((
+                    *id,
+                    PostView {
+                        id: *id,
+                        time: *time,
+                        ..PostView::default()
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+
+        fill_post_fields(&current, &mut posts);
+
+        let mut posts: Vec<PostView> = posts.into_values().collect();
+        posts.sort_by(|a, b| b.time.cmp(&a.time).then(a.id.cmp(&b.id)));
+        posts
+    }
+
+    /// Total post count and page count
+    pub fn post_aggregates(&self) -> Option<(u64, u64)> {
+        self.current().fold(None, |aggregates, query_result| match query_result {
+            QueryResult::PostAggregates(post_count, page_count) => Some((post_count, page_count)),
+            _ => aggregates,
+        })
+    }
+
+    pub fn user_post_count(&self) -> Option<u64> {
+        self.current().fold(None, |count, query_result| match query_result {
+            QueryResult::UserPostCount(user_post_count) => Some(user_post_count),
+            _ => count,
+        })
+    }
+
+    pub fn role(&self) -> Option<Role> {
+        self.current()
+            .filter_map(|query_result| match query_result {
+                QueryResult::UserRole(role) => Some(role),
+                _ => None,
+            })
+            .max()
+    }
+
+    pub fn hidden_posts(&self) -> Vec<HiddenPostView> {
+        let current: Vec<QueryResult> = self.current().collect();
+
+        current
+            .iter()
+            .filter_map(|query_result| match query_result {
+                QueryResult::HiddenPost(id, reason) => Some(HiddenPostView {
+                    id: *id,
+                    title: current.iter().find_map(|other| match other {
+                        QueryResult::PostTitle(other_id, title) if other_id == id => {
+                            Some(title.clone())
+                        }
+                        _ => None,
+                    }),
+                    reason: reason.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Reported posts, most reports first
+    pub fn moderation_queue(&self) -> Vec<QueuedPostView> {
+        let current: Vec<QueryResult> = self.current().collect();
+
+        let mut queue: Vec<QueuedPostView> = current
+            .iter()
+            .filter_map(|query_result| match query_result {
+                QueryResult::ModerationQueuePost(id, report_count, rank) => Some(QueuedPostView {
+                    id: *id,
+                    report_count: *report_count,
+                    rank: *rank,
+                    ..QueuedPostView::default()
+                }),
+                _ => None,
+            })
+            .collect();
+
+        for queued_post in queue.iter_mut() {
+            for query_result in &current {
+                match query_result {
+                    QueryResult::PostTitle(id, title) if *id == queued_post.id => {
+                        queued_post.title = Some(title.clone());
+                    }
+                    QueryResult::PostReport(id, reason) if *id == queued_post.id => {
+                        queued_post.reasons.push(reason.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        queue.sort_by_key(|queued_post| queued_post.rank);
+        queue
+    }
+
+    pub fn hidden_users(&self) -> Vec<HiddenUserView> {
+        self.current()
+            .filter_map(|query_result| match query_result {
+                QueryResult::BlockedUser(user_id) => Some(HiddenUserView {
+                    user_id,
+                    blocked: true,
+                }),
+                QueryResult::MutedUser(user_id) => Some(HiddenUserView {
+                    user_id,
+                    blocked: false,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn spam_flags(&self) -> Vec<SpamFlagView> {
+        let current: Vec<QueryResult> = self.current().collect();
+
+        current
+            .iter()
+            .filter_map(|query_result| match query_result {
+                QueryResult::SpamFlag(flag) => Some(SpamFlagView {
+                    flag: flag.clone(),
+                    title: current.iter().find_map(|other| match (flag, other) {
+                        (
+                            SpamFlag::RepeatedBody(id, _count),
+                            QueryResult::PostTitle(other_id, title),
+                        ) if other_id == id => Some(title.clone()),
+                        _ => None,
+                    }),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn conversations(&self) -> Vec<ConversationView> {
+        let mut conversations: BTreeMap<u64, ConversationView> = BTreeMap::new();
+
+        for query_result in self.current() {
+            match query_result {
+                QueryResult::Conversation(id, participants) => {
+                    let conversation = conversations.entry(id).or_default();
+                    conversation.id = id;
+                    conversation.participants = participants;
+                }
+                QueryResult::ConversationUnread(id, count) => {
+                    conversations.entry(id).or_default().unread = Some(count);
+                }
+                _ => {}
+            }
+        }
+
+        // unread counts can arrive before their conversation
+        conversations
+            .into_values()
+            .filter(|conversation| !conversation.participants.is_empty())
+            .collect()
+    }
+
+    /// Messages of a conversation, oldest first
+    pub fn messages(&self, conversation_id: u64) -> Vec<MessageView> {
+        let current: Vec<QueryResult> = self.current().collect();
+
+        let mut messages: Vec<MessageView> = current
+            .iter()
+            .filter_map(|query_result| match query_result {
+                QueryResult::DirectMessage(id, message_id, sender, time, body)
+                    if *id == conversation_id =>
+                {
+                    Some(MessageView {
+                        id: *message_id,
+                        sender: *sender,
+                        time: *time,
+                        body: body.clone(),
+                        read_by: current
+                            .iter()
+                            .filter_map(|other| match other {
+                                QueryResult::ConversationReadBy(other_id, user_id, read_time)
+                                    if *other_id == conversation_id
+                                        && user_id != sender
+                                        && read_time >= time =>
+                                {
+                                    Some(*user_id)
+                                }
+                                _ => None,
+                            })
+                            .collect(),
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+
+        messages.sort_by_key(|message| (message.time, message.id));
+        messages
+    }
+
+    /// The time up to which a participant read a conversation
+    pub fn read_time(&self, conversation_id: u64, user_id: u64) -> Option<u64> {
+        self.current().find_map(|query_result| match query_result {
+            QueryResult::ConversationReadBy(id, reader, time)
+                if id == conversation_id && reader == user_id =>
+            {
+                Some(time)
+            }
+            _ => None,
+        })
+    }
+
+    pub fn room(&self, name: &str) -> RoomView {
+        let mut room = RoomView {
+            name: name.to_string(),
+            ..RoomView::default()
+        };
+
+        for query_result in self.current() {
+            match query_result {
+                QueryResult::RoomPresence(other, user_id) if other == name => {
+                    room.online.push(user_id);
+                }
+                QueryResult::RoomTyping(other, user_id) if other == name => {
+                    room.typing.push(user_id);
+                }
+                QueryResult::RoomMessage(other, id, sender, time, body) if other == name => {
+                    room.messages.push(MessageView {
+                        id,
+                        sender,
+                        time,
+                        body,
+                        read_by: vec![],
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        room.messages.sort_by_key(|message| (message.time, message.id));
+        room
+    }
+
+    /// Notifications of the session's user, newest first
+    pub fn notifications(&self) -> Vec<NotificationView> {
+        let current: Vec<QueryResult> = self.current().collect();
+
+        let mut notifications: Vec<NotificationView> = current
+            .iter()
+            .filter_map(|query_result| match query_result {
+                QueryResult::Notification(notification, time, unread) => Some(NotificationView {
+                    notification: notification.clone(),
+                    time: *time,
+                    unread: *unread,
+                    title: current.iter().find_map(|other| match (notification, other) {
+                        (
+                            Notification::PostLiked(id, _) | Notification::Mentioned(id, _),
+                            QueryResult::PostTitle(other_id, title),
+                        ) if other_id == id => Some(title.clone()),
+                        _ => None,
+                    }),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        notifications.sort_by_key(|notification| std::cmp::Reverse(notification.time));
+        notifications
+    }
+
+    /// The time up to which the session's user read their notifications
+    pub fn notifications_read_until(&self) -> Option<u64> {
+        self.current().find_map(|query_result| match query_result {
+            QueryResult::NotificationsReadUntil(time) => Some(time),
+            _ => None,
+        })
+    }
+
+    pub fn user_like_count(&self) -> Option<u64> {
+        self.current().fold(None, |count, query_result| match query_result {
+            QueryResult::UserLikeCount(user_like_count) => Some(user_like_count),
+            _ => count,
+        })
+    }
+}
+
+fn add_diff(results: &mut BTreeMap<QueryResult, Diff>, query_result: QueryResult, diff: Diff) {
+    let count = results.entry(query_result.clone()).or_default();
+    *count += diff;
+
+    if *count == 0 {
+        results.remove(&query_result);
+    }
+}
+
+/// Fills in the fields of the posts from the results, results of other posts are skipped
+fn fill_post_fields(current: &[QueryResult], posts: &mut BTreeMap<u64, PostView>) {
+    for query_result in current {
+        match query_result {
+            QueryResult::PostTitle(id, title) => {
+                if let Some(post) = posts.get_mut(id) {
+                    post.title = Some(title.clone());
+                }
+            }
+            QueryResult::PostBody(id, body) => {
+                if let Some(post) = posts.get_mut(id) {
+                    post.body = Some(body.clone());
+                }
+            }
+            QueryResult::PostCreator(id, creator) => {
+                if let Some(post) = posts.get_mut(id) {
+                    post.creator = Some(creator.clone());
+                }
+            }
+            QueryResult::PostTotalLikes(id, likes) => {
+                if let Some(post) = posts.get_mut(id) {
+                    post.total_likes = Some(*likes);
+                }
+            }
+            QueryResult::PostLikedByUser(id, is_liked) => {
+                if let Some(post) = posts.get_mut(id) {
+                    post.liked_by_user = *is_liked;
+                }
+            }
+            QueryResult::PostMention(id, user_id) => {
+                if let Some(post) = posts.get_mut(id) {
+                    post.mentions.push(*user_id);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_fields_before_post() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::PostTitle(5, "Zerg".into()), 1),
+            (QueryResult::PostTotalLikes(5, 2), 1),
+        ]);
+
+        assert_eq!(view.posts(), vec![]);
+
+        view.ingest(vec![(QueryResult::PagePost(5, 0, 0), 1)]);
+
+        assert_eq!(
+            view.posts(),
+            vec![PostView {
+                id: 5,
+                title: Some("Zerg".into()),
+                total_likes: Some(2),
+                ..PostView::default()
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_retraction_before_result() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::PagePost(5, 0, 0), 1),
+            (QueryResult::PostTotalLikes(5, 1), 1),
+        ]);
+        // the next like count arrives before the retraction of the previous one
+        view.ingest(vec![(QueryResult::PostTotalLikes(5, 0), -1)]);
+        view.ingest(vec![
+            (QueryResult::PostTotalLikes(5, 1), -1),
+            (QueryResult::PostTotalLikes(5, 0), 1),
+        ]);
+
+        assert_eq!(view.posts()[0].total_likes, None);
+
+        view.ingest(vec![(QueryResult::PostTotalLikes(5, 0), 1)]);
+
+        assert_eq!(view.posts()[0].total_likes, Some(0));
+    }
+
+    #[test]
+    pub fn test_posts_newest_first() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::PagePost(5, 0, 10), 1),
+            (QueryResult::PagePost(6, 0, 30), 1),
+            (QueryResult::PagePost(7, 0, 20), 1),
+            (QueryResult::PostLikedByUser(7, true), 1),
+        ]);
+
+        let posts = view.posts();
+        assert_eq!(
+            posts.iter().map(|post| post.id).collect::<Vec<_>>(),
+            vec![6, 7, 5]
+        );
+        assert!(posts[1].liked_by_user);
+
+        view.ingest(vec![(QueryResult::PagePost(6, 0, 30), -1)]);
+
+        assert_eq!(
+            view.posts().iter().map(|post| post.id).collect::<Vec<_>>(),
+            vec![7, 5]
+        );
+    }
+
+    #[test]
+    pub fn test_aggregates() {
+        let mut view = ForumView::new();
+
+        assert_eq!(view.post_aggregates(), None);
+
+        view.ingest(vec![
+            (QueryResult::PostAggregates(3, 1), 1),
+            (QueryResult::UserPostCount(2), 1),
+            (QueryResult::UserLikeCount(1), 1),
+        ]);
+        view.ingest(vec![
+            (QueryResult::PostAggregates(3, 1), -1),
+            (QueryResult::PostAggregates(4, 2), 1),
+        ]);
+
+        assert_eq!(view.post_aggregates(), Some((4, 2)));
+        assert_eq!(view.user_post_count(), Some(2));
+        assert_eq!(view.user_like_count(), Some(1));
+    }
+
+    #[test]
+    pub fn test_optimistic_rollback() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::PagePost(5, 0, 0), 1),
+            (QueryResult::PostTotalLikes(5, 0), 1),
+        ]);
+
+        let like = vec![
+            (QueryResult::PostLikedByUser(5, true), 1),
+            (QueryResult::PostTotalLikes(5, 0), -1),
+            (QueryResult::PostTotalLikes(5, 1), 1),
+        ];
+        view.apply_optimistic(70, like.clone());
+        view.apply_optimistic(71, like);
+
+        assert!(view.post(5).unwrap().liked_by_user);
+        assert_eq!(view.post(5).unwrap().total_likes, Some(1));
+
+        // confirmed after its real results
+        view.ingest(vec![
+            (QueryResult::PostLikedByUser(5, true), 1),
+            (QueryResult::PostTotalLikes(5, 0), -1),
+            (QueryResult::PostTotalLikes(5, 1), 1),
+        ]);
+        view.ingest_outcome(TransactionOutcome::Confirmed(70));
+        let rejection = TransactionError::Invalid("post body is empty".into());
+        view.ingest_outcome(TransactionOutcome::Rejected(71, rejection.clone()));
+
+        assert_eq!(view.post(5).unwrap().total_likes, Some(1));
+        assert_eq!(view.take_rejections(), vec![(71, rejection)]);
+        assert_eq!(view.take_rejections(), vec![]);
+
+        // a rate limited like is kept, it is sent again
+        view.apply_optimistic(73, vec![(QueryResult::PostLikedByUser(5, true), -1)]);
+        view.ingest_outcome(TransactionOutcome::Rejected(73, TransactionError::RateLimited(2000)));
+        assert!(!view.post(5).unwrap().liked_by_user);
+        view.ingest_outcome(TransactionOutcome::Confirmed(73));
+        assert!(view.post(5).unwrap().liked_by_user);
+        view.take_rejections();
+
+        view.apply_optimistic(
+            72,
+            vec![
+                (QueryResult::PagePost(6, 0, u64::MAX), 1),
+                (QueryResult::PostTitle(6, "Zerg".into()), 1),
+            ],
+        );
+        view.clear_results();
+
+        assert_eq!(view.posts().len(), 1);
+        assert_eq!(view.post(6).unwrap().title, Some("Zerg".into()));
+
+        view.ingest_outcome(TransactionOutcome::Rejected(
+            72,
+            TransactionError::Invalid("".into()),
+        ));
+
+        assert_eq!(view.posts(), vec![]);
+    }
+
+    #[test]
+    pub fn test_moderator_view() {
+        let mut view = ForumView::new();
+
+        assert_eq!(view.role(), None);
+
+        view.ingest(vec![
+            (QueryResult::UserRole(Role::Moderator), 1),
+            (QueryResult::UserRole(Role::Admin), 1),
+            (QueryResult::HiddenPost(5, Some("spam".into())), 1),
+            (QueryResult::PostTitle(5, "Zerg".into()), 1),
+            (QueryResult::HiddenPost(6, None), 1),
+        ]);
+
+        assert_eq!(view.role(), Some(Role::Admin));
+        assert_eq!(
+            view.hidden_posts(),
+            vec![
+                HiddenPostView {
+                    id: 5,
+                    title: Some("Zerg".into()),
+                    reason: Some("spam".into()),
+                },
+                HiddenPostView {
+                    id: 6,
+                    title: None,
+                    reason: None,
+                },
+            ]
+        );
+        // hidden posts are not on the page
+        assert_eq!(view.posts(), vec![]);
+    }
+
+    #[test]
+    pub fn test_moderation_queue() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::ModerationQueuePost(5, 1, 1), 1),
+            (QueryResult::ModerationQueuePost(6, 2, 0), 1),
+            (QueryResult::PostTitle(6, "Terran".into()), 1),
+            (QueryResult::PostReport(6, "spam".into()), 1),
+            (QueryResult::PostReport(6, "rude".into()), 1),
+        ]);
+
+        assert_eq!(
+            view.moderation_queue(),
+            vec![
+                QueuedPostView {
+                    id: 6,
+                    title: Some("Terran".into()),
+                    report_count: 2,
+                    rank: 0,
+                    reasons: vec!["rude".into(), "spam".into()],
+                },
+                QueuedPostView {
+                    id: 5,
+                    title: None,
+                    report_count: 1,
+                    rank: 1,
+                    reasons: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_hidden_users() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::BlockedUser(56), 1),
+            (QueryResult::MutedUser(57), 1),
+            (QueryResult::MutedUser(58), 1),
+            (QueryResult::MutedUser(58), -1),
+        ]);
+
+        assert_eq!(
+            view.hidden_users(),
+            vec![
+                HiddenUserView {
+                    user_id: 56,
+                    blocked: true,
+                },
+                HiddenUserView {
+                    user_id: 57,
+                    blocked: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_spam_flags() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::SpamFlag(SpamFlag::RepeatedBody(5, 3)), 1),
+            (QueryResult::SpamFlag(SpamFlag::PostBurst(55, 5)), 1),
+            (QueryResult::PostTitle(5, "Zerg".into()), 1),
+        ]);
+
+        assert_eq!(
+            view.spam_flags(),
+            vec![
+                SpamFlagView {
+                    flag: SpamFlag::RepeatedBody(5, 3),
+                    title: Some("Zerg".into()),
+                },
+                SpamFlagView {
+                    flag: SpamFlag::PostBurst(55, 5),
+                    title: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_conversations() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::ConversationUnread(7, 1), 1),
+            (QueryResult::Conversation(7, vec![55, 56]), 1),
+            (QueryResult::ConversationUnread(8, 0), 1),
+            (QueryResult::DirectMessage(7, 10, 56, 4, "wp".into()), 1),
+            (QueryResult::DirectMessage(7, 9, 55, 3, "gg".into()), 1),
+            (QueryResult::ConversationReadBy(7, 56, 3), 1),
+        ]);
+
+        assert_eq!(
+            view.conversations(),
+            vec![ConversationView {
+                id: 7,
+                participants: vec![55, 56],
+                unread: Some(1),
+            }]
+        );
+        assert_eq!(
+            view.messages(7),
+            vec![
+                MessageView {
+                    id: 9,
+                    sender: 55,
+                    time: 3,
+                    body: "gg".into(),
+                    read_by: vec![56],
+                },
+                MessageView {
+                    id: 10,
+                    sender: 56,
+                    time: 4,
+                    body: "wp".into(),
+                    read_by: vec![],
+                },
+            ]
+        );
+        assert_eq!(view.read_time(7, 56), Some(3));
+        assert_eq!(view.read_time(7, 55), None);
+    }
+
+    #[test]
+    pub fn test_room() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::RoomPresence("general".into(), 55), 1),
+            (QueryResult::RoomPresence("general".into(), 56), 1),
+            (QueryResult::RoomPresence("random".into(), 57), 1),
+            (QueryResult::RoomTyping("general".into(), 56), 1),
+            (QueryResult::RoomMessage("general".into(), 9, 56, 4, "wp".into()), 1),
+            (QueryResult::RoomMessage("general".into(), 8, 55, 3, "gg".into()), 1),
+            (QueryResult::RoomPresence("general".into(), 56), -1),
+        ]);
+
+        assert_eq!(
+            view.room("general"),
+            RoomView {
+                name: "general".into(),
+                online: vec![55],
+                typing: vec![56],
+                messages: vec![
+                    MessageView {
+                        id: 8,
+                        sender: 55,
+                        time: 3,
+                        body: "gg".into(),
+                        read_by: vec![],
+                    },
+                    MessageView {
+                        id: 9,
+                        sender: 56,
+                        time: 4,
+                        body: "wp".into(),
+                        read_by: vec![],
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_notifications() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::Notification(Notification::PostLiked(5, 56), 3, false), 1),
+            (QueryResult::Notification(Notification::PostLiked(6, 57), 4, true), 1),
+            (QueryResult::PostTitle(5, "Zerg".into()), 1),
+            (QueryResult::NotificationsReadUntil(3), 1),
+        ]);
+
+        assert_eq!(
+            view.notifications(),
+            vec![
+                NotificationView {
+                    notification: Notification::PostLiked(6, 57),
+                    time: 4,
+                    unread: true,
+                    title: None,
+                },
+                NotificationView {
+                    notification: Notification::PostLiked(5, 56),
+                    time: 3,
+                    unread: false,
+                    title: Some("Zerg".into()),
+                },
+            ]
+        );
+        assert_eq!(view.notifications_read_until(), Some(3));
+    }
+
+    #[test]
+    pub fn test_mentions() {
+        let mut view = ForumView::new();
+
+        view.ingest(vec![
+            (QueryResult::PagePost(5, 0, 1), 1),
+            (QueryResult::PostBody(5, "@57 and @56".into()), 1),
+            (QueryResult::PostMention(5, 57), 1),
+            (QueryResult::PostMention(5, 56), 1),
+            (QueryResult::MentionFeedPost(6, 2), 1),
+            (QueryResult::PostTitle(6, "Zerg".into()), 1),
+        ]);
+
+        assert_eq!(
+            view.post(5).unwrap().mentions,
+            vec![56, 57]
+        );
+        // feed posts are not on the page
+        assert_eq!(view.post(6), None);
+        assert_eq!(
+            view.mention_feed(),
+            vec![PostView {
+                id: 6,
+                time: 2,
+                title: Some("Zerg".into()),
+                ..PostView::default()
+            }]
+        );
+    }
+}
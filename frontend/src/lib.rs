@@ -1,4 +1,6 @@
 // #SPC-forum_minimal
+// abomonation_derive expands to impls nested inside a const block
+#![allow(non_local_definitions)]
 extern crate abomonation;
 #[macro_use]
 extern crate abomonation_derive;
@@ -14,22 +16,32 @@ wasm_bindgen_test_configure!(run_in_browser);
 
 pub mod connection;
 pub mod df_tuple_items;
+pub mod forum_view;
+pub mod markdown;
+pub mod outbox;
 pub mod persisted;
 pub mod query_result;
 
+use connection::ConnectionStatus;
+use forum_view::{
+    ConversationView, ForumView, HiddenPostView, HiddenUserView, MessageView,
+    NotificationView, PostView, QueuedPostView, RoomView, SpamFlagView,
+};
 use persisted::Persisted;
-use query_result::QueryResult;
-use std::cell::RefCell;
+use query_result::{Notification, QueryResult, ServerMessage, SpamFlag, TransactionError};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use wasm_bindgen::prelude::*;
 
 use wasm_bindgen::JsCast;
 use web_sys::{
-    Document, Element, Event, HtmlElement, HtmlInputElement, HtmlTextAreaElement, Storage,
+    Document, Element, HtmlElement, HtmlInputElement, HtmlTextAreaElement, Storage,
 };
 
-pub const USER_ID_LOCAL_STORAGE_KEY: &'static str = "df_forum_username";
+type RenderHandle = Rc<RefCell<Option<Box<dyn Fn()>>>>;
+
+pub const USER_ID_LOCAL_STORAGE_KEY: &str = "df_forum_username";
 pub const WEBSOCKET_PORT: usize = 5050;
 
 #[wasm_bindgen]
@@ -52,7 +64,22 @@ pub fn get_random_u64() -> u64 {
 pub fn bootstrap() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    let hostname = web_sys::window().unwrap().location().hostname().unwrap();
+    let location = web_sys::window().unwrap().location();
+    let hostname = location.hostname().unwrap();
+
+    // the token printed by the backend for an admin is adopted from the page url, `/?token=...`
+    if let Some(token) = location
+        .search()
+        .unwrap()
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("token="))
+    {
+        get_local_storage()
+            .set_item(connection::TOKEN_LOCAL_STORAGE_KEY, token)
+            .unwrap();
+    }
+
     let websocket_url = format!("ws://{}:{}", hostname, WEBSOCKET_PORT);
 
     let connection = Rc::new(RefCell::new(connection::FrontendConnection::new(
@@ -61,36 +88,38 @@ pub fn bootstrap() {
 
     let connection0 = connection.clone();
 
-    let onopen = Closure::<dyn FnMut(Event)>::new(move |_event: Event| {
-        log(&format!("websocket opened"));
-
-        let local_storage = get_local_storage();
-        let user_id = if let Ok(Some(user_id_str)) = local_storage.get_item(USER_ID_LOCAL_STORAGE_KEY) {
-            if let Ok(user_id) = user_id_str.parse() {
-                user_id
-            } else {
-                log("invalid user id, defaulting to 1");
-                1
-            }
-        } else {
-            let user_id = get_random_u64();
-
-            get_local_storage()
-                .set_item(USER_ID_LOCAL_STORAGE_KEY, &user_id.to_string())
-                .unwrap();
-
-            user_id
-        };
-            
+    let onopen = move |user_id| {
         connection0.clone().borrow().send_transaction(vec![
             (user_id, Persisted::Session, 1),
             (user_id, Persisted::ViewPostsPage(0), 1),
         ]);
 
         render_page_posts(user_id, connection0.clone());
-    });
+    };
+
+    connection.borrow().set_onopen(Box::new(onopen));
+    connection
+        .borrow()
+        .set_onstatus(Box::new(render_connection_status));
+}
 
-    connection.borrow().set_onopen(onopen);
+pub fn render_connection_status(status: ConnectionStatus) {
+    let (document, _root) = document_and_root();
+    let status_el = document.get_element_by_id("connection-status").unwrap();
+
+    match status {
+        ConnectionStatus::Connected => {
+            status_el.set_attribute("style", "display: none").unwrap();
+        }
+        ConnectionStatus::Reconnecting { attempt, delay_ms } => {
+            status_el.set_text_content(Some(&format!(
+                "Offline - reconnecting in {}s (attempt {})",
+                (delay_ms + 999) / 1000,
+                attempt
+            )));
+            status_el.set_attribute("style", "display: inline").unwrap();
+        }
+    }
 }
 
 pub fn document_and_root() -> (Document, Element) {
@@ -143,6 +172,15 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
     let connection2 = connection.clone();
     let connection3 = connection.clone();
     let connection4 = connection.clone();
+    let connection7 = connection.clone();
+
+    let forum_view = Rc::new(RefCell::new(ForumView::new()));
+    let forum_view0 = forum_view.clone();
+    let forum_view1 = forum_view.clone();
+    // set once the render closure below exists, the click handlers use it to show optimistic changes
+    let render: RenderHandle = Rc::default();
+    let render0 = render.clone();
+    let render1 = render.clone();
 
     let view_posts_page_id = get_random_u64();
 
@@ -153,11 +191,9 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
 
     let use_different_name = document.get_element_by_id("switch-user-id").unwrap();
     let use_different_name_click = Closure::<dyn FnMut()>::new(move || {
-
-        let user_id = get_random_u64();
-
+        // without a token the backend makes the next connection a new user
         get_local_storage()
-            .set_item(USER_ID_LOCAL_STORAGE_KEY, &user_id.to_string())
+            .remove_item(connection::TOKEN_LOCAL_STORAGE_KEY)
             .unwrap();
 
         web_sys::window().unwrap().location().reload().unwrap();
@@ -201,14 +237,26 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
 
         let body_el = post_body.dyn_ref::<HtmlTextAreaElement>().unwrap();
         let body = body_el.value();
-        if !title.is_empty() && !body.is_empty() {
+        if !title.trim().is_empty() && !body.trim().is_empty() {
             let id = get_random_u64();
-            connection0.borrow().send_transaction(vec![
+            let transaction_id = connection0.borrow().send_transaction(vec![
                 (id, Persisted::Post, 1),
-                (id, Persisted::PostTitle(title), 1),
-                (id, Persisted::PostBody(body), 1),
+                (id, Persisted::PostTitle(title.clone()), 1),
+                (id, Persisted::PostBody(body.clone()), 1),
             ]);
 
+            // new posts are shown first on the first page
+            forum_view0.borrow_mut().apply_optimistic(
+                transaction_id,
+                vec![
+                    (QueryResult::PagePost(id, 0, u64::MAX), 1),
+                    (QueryResult::PostTitle(id, title), 1),
+                    (QueryResult::PostBody(id, markdown::render(&body)), 1),
+                    (QueryResult::PostCreator(id, user_id.to_string()), 1),
+                    (QueryResult::PostTotalLikes(id, 0), 1),
+                ],
+            );
+
             let (_, root) = document_and_root();
             let old_page: u64 = root.get_attribute("page").unwrap().parse().unwrap();
 
@@ -224,7 +272,13 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
 
             title_el.set_value("");
             body_el.set_value("");
+
+            if let Some(render) = render0.borrow().as_ref() {
+                render();
+            }
         } else {
+            create_post_error.set_text_content(Some("Please fill out all fields"));
+
             let create_post_error_el = create_post_error.dyn_ref::<HtmlElement>().unwrap();
             // trigger reflow to restart the animation
             create_post_error_el.offset_height();
@@ -290,8 +344,696 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
 
     next_page_click.forget();
 
-    let on_parsed_message = move |items: Vec<QueryResult>| {
+    let create_post_element = move |post_id: u64| {
+        let (document, _root) = document_and_root();
+        let posts_container = document.query_selector("#post-container").unwrap().unwrap();
+        let posts = posts_container.children();
+
+        let post_template = document.query_selector("#post-template").unwrap().unwrap();
+        let new_post = document.create_element("div").unwrap();
+        new_post.set_inner_html(&post_template.inner_html());
+        new_post.set_id(&post_id.to_string());
+
+        let connection5 = connection4.clone();
+        let forum_view2 = forum_view1.clone();
+        let render2 = render1.clone();
+        let post = new_post.clone();
+
+        let delete_button = new_post.query_selector(".post-delete").unwrap().unwrap();
+        let delete_button_click = Closure::<dyn FnMut()>::new(move || {
+            let page: u64 = post.get_attribute("page").unwrap().parse().unwrap();
+            // soft delete, moderators can still see and restore the post
+            let mut persisted = vec![(post_id, Persisted::PostDeleted, 1)];
+
+            // the current post, the post template
+            if posts.length() == 2 && page > 0 {
+                let (_, root) = document_and_root();
+                root.set_attribute("page", &((page - 1).to_string()))
+                    .unwrap();
+                update_page_label();
+
+                persisted.push((
+                    view_posts_page_id,
+                    Persisted::ViewPostsPage(page),
+                    -1,
+                ));
+                persisted.push((
+                    view_posts_page_id,
+                    Persisted::ViewPostsPage(page - 1),
+                    1,
+                ));
+            }
+
+            let transaction_id = connection5.clone().borrow().send_transaction(persisted);
+
+            let mut forum_view = forum_view2.borrow_mut();
+            if let Some(post) = forum_view.post(post_id) {
+                forum_view.apply_optimistic(
+                    transaction_id,
+                    vec![(QueryResult::PagePost(post.id, post.page, post.time), -1)],
+                );
+            }
+            drop(forum_view);
+
+            if let Some(render) = render2.borrow().as_ref() {
+                render();
+            }
+        });
+
+        let delete_button_el = delete_button.dyn_ref::<HtmlElement>().unwrap();
+        delete_button_el.set_onclick(Some(delete_button_click.as_ref().unchecked_ref()));
+
+        delete_button_click.forget();
+        let connection8 = connection4.clone();
+        let forum_view5 = forum_view1.clone();
+        let render4 = render1.clone();
+
+        let hide_button = new_post.query_selector(".post-hide").unwrap().unwrap();
+        let hide_button_click = Closure::<dyn FnMut()>::new(move || {
+            let reason = web_sys::window()
+                .unwrap()
+                .prompt_with_message("Why is the post hidden?")
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+
+            if reason.trim().is_empty() {
+                return;
+            }
+
+            let transaction_id = connection8
+                .borrow()
+                .send_transaction(vec![(post_id, Persisted::PostHidden(reason), 1)]);
+
+            let mut forum_view = forum_view5.borrow_mut();
+            if let Some(post) = forum_view.post(post_id) {
+                forum_view.apply_optimistic(
+                    transaction_id,
+                    vec![(QueryResult::PagePost(post.id, post.page, post.time), -1)],
+                );
+            }
+            drop(forum_view);
+
+            if let Some(render) = render4.borrow().as_ref() {
+                render();
+            }
+        });
+
+        let hide_button_el = hide_button.dyn_ref::<HtmlElement>().unwrap();
+        hide_button_el.set_onclick(Some(hide_button_click.as_ref().unchecked_ref()));
+
+        hide_button_click.forget();
+        let connection10 = connection4.clone();
+
+        let report_button = new_post.query_selector(".post-report").unwrap().unwrap();
+        let report_button_click = Closure::<dyn FnMut()>::new(move || {
+            let reason = web_sys::window()
+                .unwrap()
+                .prompt_with_message("Why are you reporting the post?")
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+
+            if !reason.trim().is_empty() {
+                connection10
+                    .borrow()
+                    .send_transaction(vec![(user_id, Persisted::Report(post_id, reason), 1)]);
+            }
+        });
+
+        let report_button_el = report_button.dyn_ref::<HtmlElement>().unwrap();
+        report_button_el.set_onclick(Some(report_button_click.as_ref().unchecked_ref()));
+
+        report_button_click.forget();
+
+        // #SPC-forum_minimal.user_blocks
+        for (selector, blocks) in [(".post-mute", false), (".post-block", true)] {
+            let connection11 = connection4.clone();
+            let forum_view6 = forum_view1.clone();
+            let render5 = render1.clone();
+
+            let button = new_post.query_selector(selector).unwrap().unwrap();
+            let button_click = Closure::<dyn FnMut()>::new(move || {
+                let mut forum_view = forum_view6.borrow_mut();
+                let creator = forum_view
+                    .post(post_id)
+                    .and_then(|post| post.creator)
+                    .and_then(|creator| creator.parse::<u64>().ok());
+
+                let Some(creator) = creator.filter(|creator| *creator != user_id) else {
+                    return;
+                };
+
+                let persisted = if blocks {
+                    Persisted::Block(creator)
+                } else {
+                    Persisted::Mute(creator)
+                };
+                let transaction_id = connection11
+                    .borrow()
+                    .send_transaction(vec![(user_id, persisted, 1)]);
+
+                // the posts of the user leave the page until the new page arrives
+                let creator = creator.to_string();
+                let changes = forum_view
+                    .posts()
+                    .into_iter()
+                    .filter(|post| post.creator.as_ref() == Some(&creator))
+                    .map(|post| (QueryResult::PagePost(post.id, post.page, post.time), -1))
+                    .collect();
+                forum_view.apply_optimistic(transaction_id, changes);
+                drop(forum_view);
+
+                if let Some(render) = render5.borrow().as_ref() {
+                    render();
+                }
+            });
+
+            let button_el = button.dyn_ref::<HtmlElement>().unwrap();
+            button_el.set_onclick(Some(button_click.as_ref().unchecked_ref()));
+
+            button_click.forget();
+        }
+        let connection6 = connection4.clone();
+        let forum_view3 = forum_view1.clone();
+        let render3 = render1.clone();
+        let post = new_post.clone();
+
+        let like_button = new_post.query_selector(".post-like").unwrap().unwrap();
+        let like_button_click = Closure::<dyn FnMut()>::new(move || {
+            let val = post.get_attribute("is_liked") != Some("true".to_string());
+
+            log(&("user id: ".to_string() + &user_id.to_string()));
+
+            let transaction_id = connection6.clone().borrow().send_transaction(vec![
+                // (user_id, Persisted::PostLike(post_id, !val), -1),
+                (user_id, Persisted::PostLike(post_id, val), 1),
+            ]);
+
+            let mut forum_view = forum_view3.borrow_mut();
+            if let Some(post) = forum_view.post(post_id) {
+                let mut changes = vec![(QueryResult::PostLikedByUser(post_id, val), 1)];
+                if post.liked_by_user {
+                    changes.push((QueryResult::PostLikedByUser(post_id, true), -1));
+                }
+                if let Some(likes) = post.total_likes {
+                    let new_likes = if val { likes + 1 } else { likes.saturating_sub(1) };
+                    changes.push((QueryResult::PostTotalLikes(post_id, likes), -1));
+                    changes.push((QueryResult::PostTotalLikes(post_id, new_likes), 1));
+                }
+                forum_view.apply_optimistic(transaction_id, changes);
+            }
+            drop(forum_view);
+
+            if let Some(render) = render3.borrow().as_ref() {
+                render();
+            }
+        });
+
+        let like_button_el = like_button.dyn_ref::<HtmlElement>().unwrap();
+        like_button_el.set_onclick(Some(like_button_click.as_ref().unchecked_ref()));
+
+        like_button_click.forget();
+
+        new_post
+    };
+
+    // set after reconnecting, the next results replace the view of the lost session
+    let resync = Rc::new(Cell::new(false));
+    let resync0 = resync.clone();
+    // moderators view the moderation queue once their role arrives, again in every new session
+    let views_moderation_queue = Rc::new(Cell::new(false));
+    let views_moderation_queue0 = views_moderation_queue.clone();
+    // #SPC-forum_minimal.chat_rooms
+    // the chat room the session is in, the backend leaves it when the connection is lost
+    let current_room: Rc<RefCell<Option<String>>> = Rc::default();
+    let current_room0 = current_room.clone();
+
+    connection.borrow().set_onreconnect(Box::new(move || {
+        let (_, root) = document_and_root();
+        let page: u64 = root.get_attribute("page").unwrap().parse().unwrap();
+
+        resync0.set(true);
+        views_moderation_queue0.set(false);
+        connection7.borrow().send_transaction(vec![
+            (user_id, Persisted::Session, 1),
+            (user_id, Persisted::ViewPostsPage(page), 1),
+        ]);
+
+        // rooms are joined by the user of an existing session
+        if let Some(room) = current_room0.borrow().clone() {
+            connection7
+                .borrow()
+                .send_transaction(vec![(user_id, Persisted::JoinRoom(room), 1)]);
+        }
+    }));
+
+    let current_room1 = current_room.clone();
+    let connection12 = connection.clone();
+    let render9 = render.clone();
+
+    let room_name = document.get_element_by_id("room-name").unwrap();
+    let join_room = document.get_element_by_id("join-room").unwrap();
+    let join_room_click = Closure::<dyn FnMut()>::new(move || {
+        let name_el = room_name.dyn_ref::<HtmlInputElement>().unwrap();
+        let name = name_el.value().trim().to_string();
+
+        if name.is_empty() || current_room1.borrow().as_ref() == Some(&name) {
+            return;
+        }
+
+        let mut items = vec![(user_id, Persisted::JoinRoom(name.clone()), 1)];
+        if let Some(room) = current_room1.replace(Some(name)) {
+            items.push((user_id, Persisted::JoinRoom(room), -1));
+        }
+        connection12.borrow().send_transaction(items);
+
+        name_el.set_value("");
+
+        if let Some(render) = render9.borrow().as_ref() {
+            render();
+        }
+    });
+
+    let join_room_el = join_room.dyn_ref::<HtmlElement>().unwrap();
+    join_room_el.set_onclick(Some(join_room_click.as_ref().unchecked_ref()));
+
+    join_room_click.forget();
+
+    let current_room2 = current_room.clone();
+    let connection13 = connection.clone();
+    let forum_view7 = forum_view.clone();
+
+    // typing is sent again once the last one expired, it is shown right away
+    let room_message_body = document.get_element_by_id("room-message-body").unwrap();
+    let room_message_input = Closure::<dyn FnMut()>::new(move || {
+        let Some(room) = current_room2.borrow().clone() else {
+            return;
+        };
+
+        if !forum_view7.borrow().room(&room).typing.contains(&user_id) {
+            let transaction_id = connection13
+                .borrow()
+                .send_transaction(vec![(user_id, Persisted::Typing(room.clone()), 1)]);
+            forum_view7.borrow_mut().apply_optimistic(
+                transaction_id,
+                vec![(QueryResult::RoomTyping(room, user_id), 1)],
+            );
+        }
+    });
+
+    let room_message_body_el = room_message_body.dyn_ref::<HtmlElement>().unwrap();
+    room_message_body_el.set_oninput(Some(room_message_input.as_ref().unchecked_ref()));
+
+    room_message_input.forget();
+
+    let current_room3 = current_room.clone();
+    let connection14 = connection.clone();
+    let forum_view8 = forum_view.clone();
+    let render10 = render.clone();
+
+    let send_room_message = document.get_element_by_id("send-room-message").unwrap();
+    let send_room_message_click = Closure::<dyn FnMut()>::new(move || {
+        let body_el = room_message_body.dyn_ref::<HtmlInputElement>().unwrap();
+        let body = body_el.value();
+
+        let Some(room) = current_room3.borrow().clone() else {
+            return;
+        };
+        if body.trim().is_empty() {
+            return;
+        }
+
+        let message_id = get_random_u64();
+        let mut items = vec![(
+            message_id,
+            Persisted::RoomMessage(room.clone(), user_id, body.clone()),
+            1,
+        )];
+        // the user stops typing with sending the message
+        if forum_view8.borrow().room(&room).typing.contains(&user_id) {
+            items.push((user_id, Persisted::Typing(room.clone()), -1));
+        }
+
+        let transaction_id = connection14.borrow().send_transaction(items);
+        forum_view8.borrow_mut().apply_optimistic(
+            transaction_id,
+            vec![(
+                QueryResult::RoomMessage(room, message_id, user_id, u64::MAX, body),
+                1,
+            )],
+        );
+
+        body_el.set_value("");
+
+        if let Some(render) = render10.borrow().as_ref() {
+            render();
+        }
+    });
+
+    let send_room_message_el = send_room_message.dyn_ref::<HtmlElement>().unwrap();
+    send_room_message_el.set_onclick(Some(send_room_message_click.as_ref().unchecked_ref()));
+
+    send_room_message_click.forget();
+
+    // #SPC-forum_minimal.direct_messages
+    // the conversation whose messages are shown
+    let open_conversation: Rc<Cell<Option<u64>>> = Rc::default();
+    let open_conversation0 = open_conversation.clone();
+    let open_conversation1 = open_conversation.clone();
+    let open_conversation2 = open_conversation.clone();
+    let connection10 = connection.clone();
+    let connection11 = connection.clone();
+    let forum_view5 = forum_view.clone();
+    let render6 = render.clone();
+    let render7 = render.clone();
+
+    let conversation_users = document.get_element_by_id("conversation-users").unwrap();
+    let start_conversation = document.get_element_by_id("start-conversation").unwrap();
+    let start_conversation_click = Closure::<dyn FnMut()>::new(move || {
+        let users_el = conversation_users.dyn_ref::<HtmlInputElement>().unwrap();
+
+        let mut participants: Vec<u64> = users_el
+            .value()
+            .split(',')
+            .filter_map(|user| user.trim().parse().ok())
+            .chain([user_id])
+            .collect();
+        participants.sort();
+        participants.dedup();
+
+        if participants.len() < 2 {
+            return;
+        }
+
+        let conversation_id = get_random_u64();
+        let transaction_id = connection10.borrow().send_transaction(vec![(
+            conversation_id,
+            Persisted::Conversation(participants.clone()),
+            1,
+        )]);
+        forum_view5.borrow_mut().apply_optimistic(
+            transaction_id,
+            vec![(QueryResult::Conversation(conversation_id, participants), 1)],
+        );
+
+        users_el.set_value("");
+        open_conversation0.set(Some(conversation_id));
+
+        if let Some(render) = render6.borrow().as_ref() {
+            render();
+        }
+    });
+
+    let start_conversation_el = start_conversation.dyn_ref::<HtmlElement>().unwrap();
+    start_conversation_el.set_onclick(Some(start_conversation_click.as_ref().unchecked_ref()));
+
+    start_conversation_click.forget();
+
+    let forum_view6 = forum_view.clone();
+    let message_body = document.get_element_by_id("message-body").unwrap();
+    let send_message = document.get_element_by_id("send-message").unwrap();
+    let send_message_click = Closure::<dyn FnMut()>::new(move || {
+        let body_el = message_body.dyn_ref::<HtmlTextAreaElement>().unwrap();
+        let body = body_el.value();
+
+        let Some(conversation_id) = open_conversation1.get() else {
+            return;
+        };
+        if body.trim().is_empty() {
+            return;
+        }
+
+        let message_id = get_random_u64();
+        let transaction_id = connection11.borrow().send_transaction(vec![(
+            message_id,
+            Persisted::DirectMessage(conversation_id, user_id, body.clone()),
+            1,
+        )]);
+        // the time of the message is only known once it is applied, until then it is the newest
+        forum_view6.borrow_mut().apply_optimistic(
+            transaction_id,
+            vec![(
+                QueryResult::DirectMessage(conversation_id, message_id, user_id, u64::MAX, body),
+                1,
+            )],
+        );
+
+        body_el.set_value("");
+
+        if let Some(render) = render7.borrow().as_ref() {
+            render();
+        }
+    });
+
+    let send_message_el = send_message.dyn_ref::<HtmlElement>().unwrap();
+    send_message_el.set_onclick(Some(send_message_click.as_ref().unchecked_ref()));
+
+    send_message_click.forget();
+
+    // #SPC-forum_minimal.notifications
+    let notifications_toggle = document.get_element_by_id("notifications-toggle").unwrap();
+    let notifications_toggle_click = Closure::<dyn FnMut()>::new(move || {
+        let (document, _root) = document_and_root();
+        let container = document.get_element_by_id("notifications").unwrap();
+        let is_hidden = container.get_attribute("style").as_deref() == Some("display: none");
+
+        container
+            .set_attribute("style", if is_hidden { "display: block" } else { "display: none" })
+            .unwrap();
+    });
+
+    let notifications_toggle_el = notifications_toggle.dyn_ref::<HtmlElement>().unwrap();
+    notifications_toggle_el.set_onclick(Some(notifications_toggle_click.as_ref().unchecked_ref()));
+
+    notifications_toggle_click.forget();
+
+    let connection15 = connection.clone();
+    let forum_view9 = forum_view.clone();
+
+    // replaces the read marker of the user with the time of the newest notification
+    let mark_notifications_read = document.get_element_by_id("mark-notifications-read").unwrap();
+    let mark_notifications_read_click = Closure::<dyn FnMut()>::new(move || {
+        let forum_view = forum_view9.borrow();
+        let read_until = forum_view.notifications_read_until();
+        let newest = forum_view
+            .notifications()
+            .first()
+            .map(|notification| notification.time);
+
+        if let Some(newest) = newest.filter(|_| newest > read_until) {
+            let mut items = vec![(user_id, Persisted::NotificationsRead(newest), 1)];
+            if let Some(read_until) = read_until {
+                items.push((user_id, Persisted::NotificationsRead(read_until), -1));
+            }
+            connection15.borrow().send_transaction(items);
+        }
+    });
+
+    let mark_notifications_read_el = mark_notifications_read.dyn_ref::<HtmlElement>().unwrap();
+    mark_notifications_read_el
+        .set_onclick(Some(mark_notifications_read_click.as_ref().unchecked_ref()));
+
+    mark_notifications_read_click.forget();
+
+    let rendered_posts: RefCell<Vec<PostView>> = RefCell::default();
+    let rendered_hidden_posts: RefCell<Vec<HiddenPostView>> = RefCell::default();
+    let rendered_moderation_queue: RefCell<Vec<QueuedPostView>> = RefCell::default();
+    let rendered_spam_flags: RefCell<Vec<SpamFlagView>> = RefCell::default();
+    let rendered_hidden_users: RefCell<Vec<HiddenUserView>> = RefCell::default();
+    let rendered_conversations: RefCell<Vec<ConversationView>> = RefCell::default();
+    let rendered_messages: RefCell<Vec<MessageView>> = RefCell::default();
+    // (conversation id, time) of the last read receipt sent, it is only shown once it is applied
+    let sent_read_time: Cell<Option<(u64, u64)>> = Cell::default();
+    let render8 = render.clone();
+    let rendered_room: RefCell<Option<RoomView>> = RefCell::default();
+    let rendered_notifications: RefCell<Vec<NotificationView>> = RefCell::default();
+    let rendered_mention_feed: RefCell<Vec<PostView>> = RefCell::default();
+    let current_room4 = current_room.clone();
+    let forum_view4 = forum_view.clone();
+    let connection9 = connection.clone();
+
+    // brings the page in line with the forum view, only posts that changed are rendered again
+    *render.borrow_mut() = Some(Box::new(move || {
         let (document, root) = document_and_root();
+        let forum_view = forum_view4.borrow();
+        let posts_before = rendered_posts.replace(forum_view.posts());
+        let posts = rendered_posts.borrow();
+
+        let posts_container = document.query_selector("#post-container").unwrap().unwrap();
+
+        for post in &posts_before {
+            if !posts.iter().any(|other| other.id == post.id) {
+                if let Some(post_el) = document.get_element_by_id(&post.id.to_string()) {
+                    post_el.remove();
+                }
+            }
+        }
+
+        let can_moderate = forum_view.role().is_some_and(|role| role.can_moderate());
+
+        if can_moderate && !views_moderation_queue.replace(true) {
+            connection9
+                .borrow()
+                .send_transaction(vec![(user_id, Persisted::ViewModerationQueue, 1)]);
+        }
+
+        for post in posts.iter() {
+            let post_el = document
+                .get_element_by_id(&post.id.to_string())
+                .unwrap_or_else(|| create_post_element(post.id));
+
+            if !posts_before.contains(post) {
+                render_post(&post_el, post);
+            }
+
+            post_el
+                .query_selector(".post-hide")
+                .unwrap()
+                .unwrap()
+                .set_attribute("style", if can_moderate { "" } else { "display: none" })
+                .unwrap();
+
+            // users do not block themselves
+            let is_own_post = post.creator == Some(user_id.to_string());
+            for selector in [".post-mute", ".post-block"] {
+                post_el
+                    .query_selector(selector)
+                    .unwrap()
+                    .unwrap()
+                    .set_attribute("style", if is_own_post { "display: none" } else { "" })
+                    .unwrap();
+            }
+
+            // appending an existing element moves it, this keeps the order of the view
+            posts_container
+                .append_child(&post_el)
+                .expect("could not append");
+        }
+
+        if let Some((post_count, page_count)) = forum_view.post_aggregates() {
+            root.set_attribute("page_count", &page_count.to_string())
+                .unwrap();
+            update_page_label();
+            document
+                .query_selector("#posts-total")
+                .unwrap()
+                .unwrap()
+                .set_text_content(Some(&post_count.to_string()));
+        }
+
+        if let Some(user_post_count) = forum_view.user_post_count() {
+            document
+                .query_selector("#user-post-count")
+                .unwrap()
+                .unwrap()
+                .set_text_content(Some(&user_post_count.to_string()));
+        }
+
+        if let Some(user_like_count) = forum_view.user_like_count() {
+            document
+                .query_selector("#user-like-count")
+                .unwrap()
+                .unwrap()
+                .set_text_content(Some(&user_like_count.to_string()));
+        }
+
+        let hidden_posts = forum_view.hidden_posts();
+        if *rendered_hidden_posts.borrow() != hidden_posts {
+            render_hidden_posts(&hidden_posts, connection9.clone());
+            rendered_hidden_posts.replace(hidden_posts);
+        }
+
+        let moderation_queue = forum_view.moderation_queue();
+        if *rendered_moderation_queue.borrow() != moderation_queue {
+            render_moderation_queue(&moderation_queue, connection9.clone());
+            rendered_moderation_queue.replace(moderation_queue);
+        }
+
+        let spam_flags = forum_view.spam_flags();
+        if *rendered_spam_flags.borrow() != spam_flags {
+            render_spam_flags(&spam_flags, connection9.clone());
+            rendered_spam_flags.replace(spam_flags);
+        }
+
+        let hidden_users = forum_view.hidden_users();
+        if *rendered_hidden_users.borrow() != hidden_users {
+            render_hidden_users(&hidden_users, user_id, connection9.clone());
+            rendered_hidden_users.replace(hidden_users);
+        }
+
+        let conversations = forum_view.conversations();
+        if *rendered_conversations.borrow() != conversations {
+            render_conversations(&conversations, open_conversation2.clone(), render8.clone());
+            rendered_conversations.replace(conversations);
+        }
+
+        let messages = open_conversation2
+            .get()
+            .map(|conversation_id| forum_view.messages(conversation_id))
+            .unwrap_or_default();
+        if *rendered_messages.borrow() != messages {
+            render_conversation_messages(open_conversation2.get(), &messages, user_id);
+            rendered_messages.replace(messages);
+        }
+
+        let room = current_room4
+            .borrow()
+            .as_ref()
+            .map(|name| forum_view.room(name));
+        if *rendered_room.borrow() != room {
+            render_room(room.as_ref(), user_id);
+            rendered_room.replace(room);
+        }
+
+        let notifications = forum_view.notifications();
+        if *rendered_notifications.borrow() != notifications {
+            render_notifications(&notifications);
+            rendered_notifications.replace(notifications);
+        }
+
+        let mention_feed = forum_view.mention_feed();
+        if *rendered_mention_feed.borrow() != mention_feed {
+            render_mention_feed(&mention_feed);
+            rendered_mention_feed.replace(mention_feed);
+        }
+
+        // messages of others are read once their conversation is open
+        if let Some(conversation_id) = open_conversation2.get() {
+            let read_time = forum_view.read_time(conversation_id, user_id);
+            let latest_time = rendered_messages
+                .borrow()
+                .iter()
+                .filter(|message| message.sender != user_id)
+                .map(|message| message.time)
+                .max();
+            let sent = sent_read_time
+                .get()
+                .filter(|(sent_conversation_id, _time)| *sent_conversation_id == conversation_id)
+                .map(|(_conversation_id, time)| time);
+
+            // `None` sorts before any time
+            if let Some(latest_time) = latest_time.filter(|_| latest_time > read_time.max(sent)) {
+                let mut items = vec![(
+                    user_id,
+                    Persisted::ConversationRead(conversation_id, latest_time),
+                    1,
+                )];
+                if let Some(read_time) = read_time {
+                    items.push((
+                        user_id,
+                        Persisted::ConversationRead(conversation_id, read_time),
+                        -1,
+                    ));
+                }
+                connection9.borrow().send_transaction(items);
+                sent_read_time.set(Some((conversation_id, latest_time)));
+            }
+        }
+    }));
+
+    let on_parsed_message = move |server_message: ServerMessage| {
+        let document = document_and_root().0;
 
         document
             .get_element_by_id("global-root")
@@ -303,187 +1045,46 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
             .unwrap()
             .set_attribute("style", "display: none")
             .unwrap();
-        for item in items {
-            match item {
-                QueryResult::PagePost(post_id, page, time) => {
-                    let posts_container =
-                        document.query_selector("#post-container").unwrap().unwrap();
-
-                    let post_template = document.query_selector("#post-template").unwrap().unwrap();
-                    let new_post = document.create_element("div").unwrap();
-                    new_post.set_attribute("time", &time.to_string()).unwrap();
-                    new_post.set_inner_html(&post_template.inner_html());
-                    new_post.set_id(&post_id.to_string());
-
-                    let mut insert_before = None;
-                    let posts = posts_container.children();
-
-                    log(&format!("inserting time: {}", time));
-
-                    // 1 not 0, we skip the post template
-                    for i in 1..posts.length() {
-                        let post = posts.item(i).unwrap();
-                        let other_time: u64 = post.get_attribute("time").unwrap().parse().unwrap();
-
-                        if time >= other_time {
-                            insert_before = Some(post);
-                            break;
-                        }
-
-                        log(&("found post -- ".to_string() + &other_time.to_string()));
-                    }
-
-                    if let Some(insert_before) = insert_before {
-                        insert_before
-                            .before_with_node_1(&new_post)
-                            .expect("could not insert before");
-                    } else {
-                        posts_container
-                            .append_child(&new_post)
-                            .expect("could not append");
-                    }
-
-                    let connection5 = connection4.clone();
-
-                    // log(&format(!new_post.inner_html());
-
-                    let delete_button = new_post.query_selector(".post-delete").unwrap().unwrap();
-                    let delete_button_click = Closure::<dyn FnMut()>::new(move || {
-                        let mut persisted = vec![(post_id, Persisted::Post, -1)];
-
-                        // the current post, the post template
-                        if posts.length() == 2 && page > 0 {
-                            let (_, root) = document_and_root();
-                            root.set_attribute("page", &((page - 1).to_string()))
-                                .unwrap();
-                            update_page_label();
-
-                            persisted.push((
-                                view_posts_page_id,
-                                Persisted::ViewPostsPage(page),
-                                -1,
-                            ));
-                            persisted.push((
-                                view_posts_page_id,
-                                Persisted::ViewPostsPage(page - 1),
-                                1,
-                            ));
-                        }
-
-                        connection5.clone().borrow().send_transaction(persisted);
-                    });
-
-                    let delete_button_el = delete_button.dyn_ref::<HtmlElement>().unwrap();
-                    delete_button_el
-                        .set_onclick(Some(delete_button_click.as_ref().unchecked_ref()));
-
-                    delete_button_click.forget();
-                    let connection6 = connection4.clone();
-
-                    let like_button = new_post.query_selector(".post-like").unwrap().unwrap();
-                    let like_button_click = Closure::<dyn FnMut()>::new(move || {
-                        let val = new_post.get_attribute("is_liked") != Some("true".to_string());
-
-                        log(&("user id: ".to_string() + &user_id.to_string()));
-
-                        connection6.clone().borrow().send_transaction(vec![
-                            // (user_id, Persisted::PostLike(post_id, !val), -1),
-                            (user_id, Persisted::PostLike(post_id, val), 1),
-                        ]);
-                    });
-
-                    let like_button_el = like_button.dyn_ref::<HtmlElement>().unwrap();
-                    like_button_el.set_onclick(Some(like_button_click.as_ref().unchecked_ref()));
-
-                    like_button_click.forget();
-                }
-                QueryResult::PostTitle(post_id, title) => {
-                    document
-                        .get_element_by_id(&post_id.to_string())
-                        .expect(&format!("could not find post by id - {}", post_id))
-                        .query_selector(".post-title")
-                        .unwrap()
-                        .unwrap()
-                        .set_text_content(Some(&title));
-                }
-                QueryResult::PostBody(post_id, body) => {
-                    document
-                        .get_element_by_id(&post_id.to_string())
-                        .expect(&format!("could not find post by id - {}", post_id))
-                        .query_selector(".post-body")
-                        .unwrap()
-                        .unwrap()
-                        .set_text_content(Some(&body));
-                }
-                QueryResult::PostCreator(post_id, creator) => {
-                    document
-                        .get_element_by_id(&post_id.to_string())
-                        .expect(&format!("could not find post by id - {}", post_id))
-                        .query_selector(".post-creator")
-                        .unwrap()
-                        .unwrap()
-                        .set_text_content(Some(&creator));
-                }
-                QueryResult::DeletePost(post_id) => {
-                    document
-                        .get_element_by_id(&post_id.to_string())
-                        .expect(&format!("could not find post by id - {}", post_id))
-                        .remove();
-                }
-                QueryResult::PostLikedByUser(post_id, is_liked) => {
-                    log(&format!("liked: post - {}, status - {}", post_id, is_liked));
-                    let status = if is_liked { "Unlike" } else { "Like" };
-                    if let Some(post) = document.get_element_by_id(&post_id.to_string()) {
-                        // .expect(&format!("could not find post by id - {}", post_id));
-
-                        post.query_selector(".post-like-status")
-                            .unwrap()
-                            .unwrap()
-                            .set_text_content(Some(status));
-
-                        post.set_attribute("is_liked", if is_liked { "true" } else { "false" })
-                            .unwrap();
-                    } else {
-                        log("FIXME PostLikedByUser unable to find element");
-                    }
-                }
-                QueryResult::PostTotalLikes(post_id, like_count) => {
-                    if let Some(el) = document.get_element_by_id(&post_id.to_string()) {
-                        // .expect("could not find post by id")
-                        el.query_selector(".post-likes")
-                            .unwrap()
-                            .unwrap()
-                            .set_text_content(Some(&like_count.to_string()));
-                    } else {
-                        log("FIXME PostTotalLikes unable to find element");
-                    }
-                }
-                QueryResult::PostAggregates(post_count, page_count) => {
-                    root.set_attribute("page_count", &page_count.to_string())
-                        .unwrap();
-                    update_page_label();
-                    document
-                        .query_selector("#posts-total")
-                        .unwrap()
-                        .unwrap()
-                        .set_text_content(Some(&post_count.to_string()));
-                }
-                QueryResult::UserPostCount(user_post_count) => {
-                    document
-                        .query_selector("#user-post-count")
-                        .unwrap()
-                        .unwrap()
-                        .set_text_content(Some(&user_post_count.to_string()));
-                }
-                QueryResult::UserLikeCount(user_like_count) => {
-                    document
-                        .query_selector("#user-like-count")
-                        .unwrap()
-                        .unwrap()
-                        .set_text_content(Some(&user_like_count.to_string()));
+
+        let mut forum_view_mut = forum_view.borrow_mut();
+        match server_message {
+            ServerMessage::Frame((_time, items)) => {
+                if resync.take() {
+                    forum_view_mut.clear_results();
                 }
-                _ => {}
+                forum_view_mut.ingest(items);
             }
+            ServerMessage::Outcome(outcome) => forum_view_mut.ingest_outcome(outcome),
+            // handled by the connection
+            ServerMessage::Authenticated(..) => {}
+        }
+        let rejections = forum_view_mut.take_rejections();
+        drop(forum_view_mut);
+
+        // the optimistic changes of rejected transactions are rolled back by the render below,
+        // the ones of rate limited transactions stay until they are sent again
+        for (transaction_id, error) in rejections {
+            log(&format!("transaction {} rejected: {:?}", transaction_id, error));
+
+            let message = match error {
+                TransactionError::Invalid(reason) | TransactionError::Unauthorized(reason) => {
+                    format!("Not saved: {}", reason)
+                }
+                // the connection sends it again
+                TransactionError::RateLimited(retry_after_ms) => format!(
+                    "Not saved yet: too many changes, saving again in {}s",
+                    retry_after_ms.div_ceil(1000)
+                ),
+            };
+            let create_post_error = document.get_element_by_id("create-post-error").unwrap();
+            create_post_error.set_text_content(Some(&message));
+            create_post_error
+                .set_attribute("style", "display: block")
+                .unwrap();
+        }
+
+        if let Some(render) = render.borrow().as_ref() {
+            render();
         }
     };
 
@@ -491,3 +1092,544 @@ pub fn render_page_posts(user_id: u64, connection: Rc<RefCell<connection::Fronte
         .borrow_mut()
         .init_on_parsed_message(Box::new(on_parsed_message));
 }
+
+// #SPC-forum_minimal.post_moderation
+/// Lists the soft deleted and hidden posts for moderators, with a button to restore each
+pub fn render_hidden_posts(
+    hidden_posts: &[HiddenPostView],
+    connection: Rc<RefCell<connection::FrontendConnection>>,
+) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("hidden-posts").unwrap();
+    let list = document.get_element_by_id("hidden-post-list").unwrap();
+
+    container
+        .set_attribute(
+            "style",
+            if hidden_posts.is_empty() { "display: none" } else { "display: block" },
+        )
+        .unwrap();
+    list.set_inner_html("");
+
+    for hidden_post in hidden_posts {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("hidden-post");
+
+        let label = document.create_element("span").unwrap();
+        label.set_text_content(Some(&format!(
+            "{} ({})",
+            hidden_post.title.as_deref().unwrap_or_default(),
+            hidden_post.reason.as_deref().unwrap_or("deleted"),
+        )));
+        entry.append_child(&label).unwrap();
+
+        let restore_button = document.create_element("button").unwrap();
+        restore_button.set_text_content(Some("Restore"));
+        entry.append_child(&restore_button).unwrap();
+
+        let post_id = hidden_post.id;
+        let removal = match &hidden_post.reason {
+            Some(reason) => Persisted::PostHidden(reason.clone()),
+            None => Persisted::PostDeleted,
+        };
+        let connection0 = connection.clone();
+        let restore_button_click = Closure::<dyn FnMut()>::new(move || {
+            connection0
+                .borrow()
+                .send_transaction(vec![(post_id, removal.clone(), -1)]);
+        });
+
+        let restore_button_el = restore_button.dyn_ref::<HtmlElement>().unwrap();
+        restore_button_el.set_onclick(Some(restore_button_click.as_ref().unchecked_ref()));
+
+        restore_button_click.forget();
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.post_reports
+/// Lists the reported posts for moderators, a post is kept (reviewed) or hidden with the reasons
+pub fn render_moderation_queue(
+    moderation_queue: &[QueuedPostView],
+    connection: Rc<RefCell<connection::FrontendConnection>>,
+) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("moderation-queue").unwrap();
+    let list = document.get_element_by_id("moderation-queue-list").unwrap();
+
+    container
+        .set_attribute(
+            "style",
+            if moderation_queue.is_empty() { "display: none" } else { "display: block" },
+        )
+        .unwrap();
+    list.set_inner_html("");
+
+    for queued_post in moderation_queue {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("queued-post");
+
+        let label = document.create_element("span").unwrap();
+        label.set_text_content(Some(&format!(
+            "{} - {} reports: {}",
+            queued_post.title.as_deref().unwrap_or_default(),
+            queued_post.report_count,
+            queued_post.reasons.join(", "),
+        )));
+        entry.append_child(&label).unwrap();
+
+        let post_id = queued_post.id;
+        let reasons = queued_post.reasons.join(", ");
+
+        for (text, persisted) in [
+            ("Keep", Persisted::PostReviewed),
+            ("Hide", Persisted::PostHidden(reasons)),
+        ] {
+            let button = document.create_element("button").unwrap();
+            button.set_text_content(Some(text));
+            entry.append_child(&button).unwrap();
+
+            let connection0 = connection.clone();
+            let button_click = Closure::<dyn FnMut()>::new(move || {
+                connection0
+                    .borrow()
+                    .send_transaction(vec![(post_id, persisted.clone(), 1)]);
+            });
+
+            let button_el = button.dyn_ref::<HtmlElement>().unwrap();
+            button_el.set_onclick(Some(button_click.as_ref().unchecked_ref()));
+
+            button_click.forget();
+        }
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+/// Lists the users the session's user blocked or muted, they can be unblocked or unmuted again
+pub fn render_hidden_users(
+    hidden_users: &[HiddenUserView],
+    user_id: u64,
+    connection: Rc<RefCell<connection::FrontendConnection>>,
+) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("hidden-users").unwrap();
+    let list = document.get_element_by_id("hidden-user-list").unwrap();
+
+    container
+        .set_attribute(
+            "style",
+            if hidden_users.is_empty() { "display: none" } else { "display: block" },
+        )
+        .unwrap();
+    list.set_inner_html("");
+
+    for hidden_user in hidden_users {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("hidden-user");
+
+        let label = document.create_element("span").unwrap();
+        label.set_text_content(Some(&format!(
+            "user {} ({})",
+            hidden_user.user_id,
+            if hidden_user.blocked { "blocked" } else { "muted" },
+        )));
+        entry.append_child(&label).unwrap();
+
+        let (text, persisted) = if hidden_user.blocked {
+            ("Unblock", Persisted::Block(hidden_user.user_id))
+        } else {
+            ("Unmute", Persisted::Mute(hidden_user.user_id))
+        };
+
+        let button = document.create_element("button").unwrap();
+        button.set_text_content(Some(text));
+        entry.append_child(&button).unwrap();
+
+        let connection0 = connection.clone();
+        let button_click = Closure::<dyn FnMut()>::new(move || {
+            connection0
+                .borrow()
+                .send_transaction(vec![(user_id, persisted.clone(), -1)]);
+        });
+
+        let button_el = button.dyn_ref::<HtmlElement>().unwrap();
+        button_el.set_onclick(Some(button_click.as_ref().unchecked_ref()));
+
+        button_click.forget();
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.direct_messages
+/// Lists the conversations of the session's user with their unread counts, a conversation can be opened
+pub fn render_conversations(
+    conversations: &[ConversationView],
+    open_conversation: Rc<Cell<Option<u64>>>,
+    render: RenderHandle,
+) {
+    let (document, _root) = document_and_root();
+    let list = document.get_element_by_id("conversation-list").unwrap();
+
+    list.set_inner_html("");
+
+    for conversation in conversations {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("conversation");
+
+        let participants: Vec<String> = conversation
+            .participants
+            .iter()
+            .map(|user_id| user_id.to_string())
+            .collect();
+
+        let label = document.create_element("span").unwrap();
+        label.set_text_content(Some(&match conversation.unread {
+            Some(unread) if unread > 0 => {
+                format!("{} ({} unread)", participants.join(", "), unread)
+            }
+            _ => participants.join(", "),
+        }));
+        entry.append_child(&label).unwrap();
+
+        let open_button = document.create_element("button").unwrap();
+        open_button.set_text_content(Some("Open"));
+        entry.append_child(&open_button).unwrap();
+
+        let conversation_id = conversation.id;
+        let open_conversation0 = open_conversation.clone();
+        let render0 = render.clone();
+        let open_button_click = Closure::<dyn FnMut()>::new(move || {
+            open_conversation0.set(Some(conversation_id));
+
+            if let Some(render) = render0.borrow().as_ref() {
+                render();
+            }
+        });
+
+        let open_button_el = open_button.dyn_ref::<HtmlElement>().unwrap();
+        open_button_el.set_onclick(Some(open_button_click.as_ref().unchecked_ref()));
+
+        open_button_click.forget();
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+/// Shows the messages of the open conversation, each with the participants that read it
+pub fn render_conversation_messages(
+    conversation_id: Option<u64>,
+    messages: &[MessageView],
+    user_id: u64,
+) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("conversation-open").unwrap();
+    let list = document.get_element_by_id("conversation-messages").unwrap();
+
+    container
+        .set_attribute(
+            "style",
+            if conversation_id.is_none() { "display: none" } else { "display: block" },
+        )
+        .unwrap();
+    list.set_inner_html("");
+
+    for message in messages {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("message");
+
+        let sender = if message.sender == user_id {
+            "you".to_string()
+        } else {
+            message.sender.to_string()
+        };
+
+        let text = document.create_element("span").unwrap();
+        text.set_text_content(Some(&format!("{}: {}", sender, message.body)));
+        entry.append_child(&text).unwrap();
+
+        if !message.read_by.is_empty() {
+            let readers: Vec<String> = message
+                .read_by
+                .iter()
+                .map(|reader| reader.to_string())
+                .collect();
+
+            let receipt = document.create_element("span").unwrap();
+            receipt.set_class_name("message-read-by");
+            receipt.set_text_content(Some(&format!("seen by {}", readers.join(", "))));
+            entry.append_child(&receipt).unwrap();
+        }
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.chat_rooms
+/// Shows the chat room the session is in, who is online and typing in it and its newest messages
+pub fn render_room(room: Option<&RoomView>, user_id: u64) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("room-open").unwrap();
+
+    container
+        .set_attribute("style", if room.is_none() { "display: none" } else { "display: block" })
+        .unwrap();
+
+    let Some(room) = room else {
+        return;
+    };
+
+    let user_list = |user_ids: &[u64]| {
+        user_ids
+            .iter()
+            .map(|other| if *other == user_id { "you".to_string() } else { other.to_string() })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    document
+        .get_element_by_id("room-title")
+        .unwrap()
+        .set_text_content(Some(&room.name));
+    document
+        .get_element_by_id("room-online")
+        .unwrap()
+        .set_text_content(Some(&format!("online: {}", user_list(&room.online))));
+
+    let others_typing: Vec<u64> = room
+        .typing
+        .iter()
+        .copied()
+        .filter(|other| *other != user_id)
+        .collect();
+    document
+        .get_element_by_id("room-typing")
+        .unwrap()
+        .set_text_content(Some(&if others_typing.is_empty() {
+            String::new()
+        } else {
+            format!("{} typing...", user_list(&others_typing))
+        }));
+
+    let list = document.get_element_by_id("room-messages").unwrap();
+    list.set_inner_html("");
+
+    for message in &room.messages {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("message");
+        entry.set_text_content(Some(&format!(
+            "{}: {}",
+            user_list(&[message.sender]),
+            message.body
+        )));
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.notifications
+/// Lists the notifications of the session's user, the number of unread ones is shown in the top bar
+pub fn render_notifications(notifications: &[NotificationView]) {
+    let (document, _root) = document_and_root();
+    let list = document.get_element_by_id("notification-list").unwrap();
+
+    let unread = notifications
+        .iter()
+        .filter(|notification| notification.unread)
+        .count();
+    document
+        .get_element_by_id("notifications-toggle")
+        .unwrap()
+        .set_text_content(Some(&if unread > 0 {
+            format!("Notifications ({})", unread)
+        } else {
+            "Notifications".to_string()
+        }));
+
+    list.set_inner_html("");
+
+    for notification in notifications {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name(if notification.unread {
+            "notification notification-unread"
+        } else {
+            "notification"
+        });
+
+        entry.set_text_content(Some(&match &notification.notification {
+            Notification::PostLiked(_post_id, user_id) => format!(
+                "user {} liked your post {}",
+                user_id,
+                notification.title.as_deref().unwrap_or_default()
+            ),
+            Notification::Mentioned(_post_id, creator) => format!(
+                "user {} mentioned you in {}",
+                creator,
+                notification.title.as_deref().unwrap_or_default()
+            ),
+        }));
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.spam_flags
+/// Lists the spam flags for moderators, posts with a repeated body can be hidden as spam
+pub fn render_spam_flags(
+    spam_flags: &[SpamFlagView],
+    connection: Rc<RefCell<connection::FrontendConnection>>,
+) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("spam-flags").unwrap();
+    let list = document.get_element_by_id("spam-flag-list").unwrap();
+
+    container
+        .set_attribute(
+            "style",
+            if spam_flags.is_empty() { "display: none" } else { "display: block" },
+        )
+        .unwrap();
+    list.set_inner_html("");
+
+    for spam_flag in spam_flags {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("spam-flag");
+
+        let label = document.create_element("span").unwrap();
+        label.set_text_content(Some(&match &spam_flag.flag {
+            SpamFlag::RepeatedBody(_post_id, count) => format!(
+                "{} - body posted {} times",
+                spam_flag.title.as_deref().unwrap_or_default(),
+                count
+            ),
+            SpamFlag::PostBurst(user_id, count) => {
+                format!("user {} - {} posts in a short time", user_id, count)
+            }
+            SpamFlag::NewUserFlood(ip, count) => format!("{} - {} new users", ip, count),
+        }));
+        entry.append_child(&label).unwrap();
+
+        if let SpamFlag::RepeatedBody(post_id, _count) = spam_flag.flag {
+            let hide_button = document.create_element("button").unwrap();
+            hide_button.set_text_content(Some("Hide"));
+            entry.append_child(&hide_button).unwrap();
+
+            let connection0 = connection.clone();
+            let hide_button_click = Closure::<dyn FnMut()>::new(move || {
+                connection0
+                    .borrow()
+                    .send_transaction(vec![(post_id, Persisted::PostHidden("spam".into()), 1)]);
+            });
+
+            let hide_button_el = hide_button.dyn_ref::<HtmlElement>().unwrap();
+            hide_button_el.set_onclick(Some(hide_button_click.as_ref().unchecked_ref()));
+
+            hide_button_click.forget();
+        }
+
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.mentions
+/// Lists the posts that mention the session's user
+pub fn render_mention_feed(posts: &[PostView]) {
+    let (document, _root) = document_and_root();
+    let container = document.get_element_by_id("mentions").unwrap();
+    let list = document.get_element_by_id("mention-list").unwrap();
+
+    container
+        .set_attribute(
+            "style",
+            if posts.is_empty() { "display: none" } else { "display: block" },
+        )
+        .unwrap();
+    list.set_inner_html("");
+
+    for post in posts {
+        let entry = document.create_element("div").unwrap();
+        entry.set_class_name("mention-post");
+        entry.set_text_content(Some(&format!(
+            "{}: {}",
+            post.creator.as_deref().unwrap_or_default(),
+            post.title.as_deref().unwrap_or_default()
+        )));
+        list.append_child(&entry).unwrap();
+    }
+}
+
+// #SPC-forum_minimal.markdown
+/// Sets the html of a post body, rendered from markdown and sanitized by `markdown::render`,
+/// mentions of users become links that fill in the conversation users
+pub fn render_post_body(body_el: &Element, body: &str, mentions: &[u64]) {
+    body_el.set_inner_html(body);
+
+    let mention_els = body_el.query_selector_all("[data-mention]").unwrap();
+
+    for index in 0..mention_els.length() {
+        let mention_el: Element = mention_els.item(index).unwrap().unchecked_into();
+        let name = mention_el.get_attribute("data-mention").unwrap_or_default();
+
+        // names are user ids, names that are no user stay text
+        let Some(user_id) = mentions
+            .iter()
+            .copied()
+            .find(|user_id| user_id.to_string() == name)
+        else {
+            continue;
+        };
+
+        mention_el.set_class_name("mention");
+        mention_el
+            .set_attribute("user_id", &user_id.to_string())
+            .unwrap();
+
+        let mention_click = Closure::<dyn FnMut()>::new(move || {
+            let (document, _root) = document_and_root();
+            document
+                .get_element_by_id("conversation-users")
+                .unwrap()
+                .dyn_ref::<HtmlInputElement>()
+                .unwrap()
+                .set_value(&user_id.to_string());
+        });
+
+        let mention_html_el = mention_el.dyn_ref::<HtmlElement>().unwrap();
+        mention_html_el.set_onclick(Some(mention_click.as_ref().unchecked_ref()));
+
+        mention_click.forget();
+    }
+}
+
+pub fn render_post(post_el: &Element, post: &PostView) {
+    let set_text = |selector: &str, text: &str| {
+        post_el
+            .query_selector(selector)
+            .unwrap()
+            .unwrap()
+            .set_text_content(Some(text));
+    };
+
+    post_el.set_attribute("page", &post.page.to_string()).unwrap();
+    post_el.set_attribute("time", &post.time.to_string()).unwrap();
+
+    set_text(".post-title", post.title.as_deref().unwrap_or_default());
+    render_post_body(
+        &post_el.query_selector(".post-body").unwrap().unwrap(),
+        post.body.as_deref().unwrap_or_default(),
+        &post.mentions,
+    );
+    set_text(".post-creator", post.creator.as_deref().unwrap_or_default());
+    set_text(
+        ".post-likes",
+        &post.total_likes.map(|likes| likes.to_string()).unwrap_or_default(),
+    );
+    set_text(
+        ".post-like-status",
+        if post.liked_by_user { "Unlike" } else { "Like" },
+    );
+    post_el
+        .set_attribute("is_liked", if post.liked_by_user { "true" } else { "false" })
+        .unwrap();
+}
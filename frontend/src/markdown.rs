@@ -0,0 +1,315 @@
+//! The markdown subset of post bodies, rendered to html by the backend
+//!
+//! Supported are `*emphasis*`, `**strong**`, `` `code` ``, `[links](https://...)`,
+//! code blocks fenced by ```` ``` ```` lines, `> quotes` and paragraphs split by empty lines.
+//! Underscores are left alone, so names like `zerg_rush` stay intact.
+//!
+//! Every character of the source is escaped and the only tags of the output are the ones
+//! written here, links only point to `http`, `https` and `mailto` urls.
+//! This makes the html safe to set as the inner html of an element.
+
+// bytes a link text and a link url can have, so a `[` is never matched far ahead
+const LINK_TEXT_MAX_LEN: usize = 200;
+const LINK_URL_MAX_LEN: usize = 2000;
+
+/// Characters that can be part of an `@name` mention
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+pub fn render(source: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph_lines: Vec<&str> = vec![];
+    let mut lines = source.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if is_fence(line) {
+            push_paragraphs(&mut html, &std::mem::take(&mut paragraph_lines));
+
+            // an unclosed code block runs until the end of the source
+            let code_lines: Vec<&str> = lines.by_ref().take_while(|line| !is_fence(line)).collect();
+
+            html.push_str("<pre><code>");
+            push_escaped(&mut html, &code_lines.join("\n"));
+            html.push_str("</code></pre>");
+        } else if let Some(quoted) = quoted_line(line) {
+            push_paragraphs(&mut html, &std::mem::take(&mut paragraph_lines));
+
+            let mut quoted_lines = vec![quoted];
+            while let Some(quoted) = lines.peek().and_then(|line| quoted_line(line)) {
+                quoted_lines.push(quoted);
+                lines.next();
+            }
+
+            // quotes are not nested, a second `>` is text
+            html.push_str("<blockquote>");
+            push_paragraphs(&mut html, &quoted_lines);
+            html.push_str("</blockquote>");
+        } else {
+            paragraph_lines.push(line);
+        }
+    }
+
+    push_paragraphs(&mut html, &paragraph_lines);
+    html
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+fn quoted_line(line: &str) -> Option<&str> {
+    let quoted = line.trim_start().strip_prefix('>')?;
+    Some(quoted.strip_prefix(' ').unwrap_or(quoted))
+}
+
+fn push_paragraphs(html: &mut String, lines: &[&str]) {
+    for paragraph in lines.split(|line| line.trim().is_empty()) {
+        if !paragraph.is_empty() {
+            html.push_str("<p>");
+            push_inline(html, &paragraph.join("\n"));
+            html.push_str("</p>");
+        }
+    }
+}
+
+/// Renders emphasis, code, links and mentions
+///
+/// The content of emphasis is rendered again, but it never contains its own delimiter,
+/// so the nesting stays shallow for any input.
+/// Every position is looked ahead of at most once per delimiter and a bounded length for links,
+/// so rendering stays linear in the length of the text.
+fn push_inline(html: &mut String, text: &str) {
+    let mut rest = text;
+    let mut previous = None;
+    // delimiters that do not occur in the rest anymore, the rest only gets shorter
+    let mut unclosed: Vec<&str> = vec![];
+
+    while let Some(c) = rest.chars().next() {
+        let mut delimited = |delimiter: &'static str| {
+            let inner = rest.strip_prefix(delimiter)?;
+            if unclosed.contains(&delimiter) {
+                return None;
+            }
+            let Some(end) = inner.find(delimiter) else {
+                unclosed.push(delimiter);
+                return None;
+            };
+            (end > 0).then(|| (&inner[..end], &inner[end + delimiter.len()..]))
+        };
+
+        let rendered = if c == '`' {
+            delimited("`").map(|(code, after)| {
+                html.push_str("<code>");
+                push_escaped(html, code);
+                html.push_str("</code>");
+                after
+            })
+        } else if rest.starts_with("**") {
+            delimited("**").map(|(inner, after)| {
+                html.push_str("<strong>");
+                push_inline(html, inner);
+                html.push_str("</strong>");
+                after
+            })
+        } else if c == '*' {
+            delimited("*").map(|(inner, after)| {
+                html.push_str("<em>");
+                push_inline(html, inner);
+                html.push_str("</em>");
+                after
+            })
+        } else if c == '[' {
+            link(rest).map(|(text, url, after)| {
+                html.push_str("<a href=\"");
+                push_escaped(html, url);
+                html.push_str("\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">");
+                push_inline(html, text);
+                html.push_str("</a>");
+                after
+            })
+        } else if c == '@' && !previous.is_some_and(is_name_char) {
+            let name_len = rest[1..]
+                .find(|c| !is_name_char(c))
+                .unwrap_or(rest.len() - 1);
+
+            (name_len > 0).then(|| {
+                let name = &rest[1..1 + name_len];
+                html.push_str("<span data-mention=\"");
+                push_escaped(html, name);
+                html.push_str("\">@");
+                push_escaped(html, name);
+                html.push_str("</span>");
+                &rest[1 + name_len..]
+            })
+        } else {
+            None
+        };
+
+        match rendered {
+            Some(after) => {
+                previous = text[..text.len() - after.len()].chars().last();
+                rest = after;
+            }
+            None => {
+                push_escaped(html, &rest[..c.len_utf8()]);
+                previous = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+}
+
+/// (text, url, rest after the link) of a `[text](url)` with a safe url at the start of the text
+///
+/// The text ends at the first `]`, so links are never nested.
+/// Texts and urls longer than `LINK_TEXT_MAX_LEN` and `LINK_URL_MAX_LEN` are not links.
+fn link(text: &str) -> Option<(&str, &str, &str)> {
+    let inner = text.strip_prefix('[')?;
+    let text_end = position_within(inner, b']', LINK_TEXT_MAX_LEN).filter(|end| *end > 0)?;
+    let url_part = inner[text_end + 1..].strip_prefix('(')?;
+    let url_end = position_within(url_part, b')', LINK_URL_MAX_LEN)?;
+    let url = &url_part[..url_end];
+
+    is_safe_url(url).then_some((&inner[..text_end], url, &url_part[url_end + 1..]))
+}
+
+/// Byte position of an ascii character within the first `max_len` bytes of the text
+fn position_within(text: &str, ascii: u8, max_len: usize) -> Option<usize> {
+    text.bytes().take(max_len + 1).position(|byte| byte == ascii)
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let lowercase = url.to_ascii_lowercase();
+
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| lowercase.starts_with(scheme) && lowercase.len() > scheme.len())
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+fn push_escaped(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK_ATTRIBUTES: &str = "\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">";
+
+    /// Panics unless every tag of the html is one `render` writes and every link is safe
+    fn assert_safe(html: &str) {
+        let tags = [
+            "<p>", "</p>", "<em>", "</em>", "<strong>", "</strong>", "<code>", "</code>",
+            "<pre>", "</pre>", "<blockquote>", "</blockquote>", "</a>",
+            "<span data-mention=\"", "</span>", "<a href=\"",
+        ];
+
+        for (start, _) in html.match_indices('<') {
+            let tag = &html[start..];
+            assert!(tags.iter().any(|allowed| tag.starts_with(allowed)), "{}", html);
+
+            if let Some(url) = tag.strip_prefix("<a href=\"") {
+                let url_end = url.find('"').unwrap();
+                assert!(is_safe_url(&url[..url_end]), "{}", html);
+                assert!(url[url_end..].starts_with(LINK_ATTRIBUTES), "{}", html);
+            }
+
+            if let Some(name) = tag.strip_prefix("<span data-mention=\"") {
+                let name_end = name.find('"').unwrap();
+                assert!(name[..name_end].chars().all(is_name_char), "{}", html);
+                assert!(name[name_end..].starts_with("\">"), "{}", html);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_render() {
+        assert_eq!(render("gg"), "<p>gg</p>");
+        assert_eq!(
+            render("*gg* **wp** `a < b` 2 * 3"),
+            "<p><em>gg</em> <strong>wp</strong> <code>a &lt; b</code> 2 * 3</p>"
+        );
+        assert_eq!(render("**gg *wp* gl**"), "<p><strong>gg <em>wp</em> gl</strong></p>");
+        assert_eq!(
+            render("zerg\nrush\n\n> gg\n>wp\n\n```\nfn main() {}\n```\nglhf"),
+            "<p>zerg\nrush</p><blockquote><p>gg\nwp</p></blockquote>\
+             <pre><code>fn main() {}</code></pre><p>glhf</p>"
+        );
+        assert_eq!(
+            render("[zerg](https://zerg.gg) @56, zerg_rush and a@57"),
+            format!(
+                "<p><a href=\"https://zerg.gg{}zerg</a> <span data-mention=\"56\">@56</span>, \
+                 zerg_rush and a@57</p>",
+                LINK_ATTRIBUTES
+            )
+        );
+    }
+
+    #[test]
+    pub fn test_script_injection() {
+        let sources = [
+            "<script>alert(1)</script>",
+            "<img src=x onerror=alert(1)>",
+            "[gg](javascript:alert(1))",
+            "[gg](JaVaScRiPt:alert(1))",
+            "[gg](data:text/html,<script>alert(1)</script>)",
+            "[gg](https://zerg.gg\"onmouseover=\"alert(1))",
+            "[gg](https://zerg.gg onmouseover=alert(1))",
+            "[<script>alert(1)</script>](https://zerg.gg)",
+            "[**<b>gg</b>**](https://zerg.gg)",
+            "`</code><script>alert(1)</script>`",
+            "```\n</code></pre><script>alert(1)</script>\n```",
+            "> <script>alert(1)</script>",
+            "**<script>** *</em><script>* @<script>",
+            "&lt;script&gt;alert(1)&lt;/script&gt;",
+            "[[[gg](https://zerg.gg)](https://zerg.gg)](https://zerg.gg)",
+        ];
+
+        for source in sources {
+            let html = render(source);
+            assert_safe(&html);
+            assert!(!html.contains("<script"), "{}", html);
+        }
+
+        assert_eq!(
+            render("<script>alert(1)</script>"),
+            "<p>&lt;script&gt;alert(1)&lt;/script&gt;</p>"
+        );
+        // unsafe links are text
+        assert_eq!(render("[gg](javascript:alert(1))"), "<p>[gg](javascript:alert(1))</p>");
+        // quotes in urls can not end the attribute
+        assert_eq!(
+            render("[gg](https://zerg.gg\"onmouseover=\"alert(1))"),
+            format!(
+                "<p><a href=\"https://zerg.gg&quot;onmouseover=&quot;alert(1{}gg</a>)</p>",
+                LINK_ATTRIBUTES
+            )
+        );
+        // escaped html stays escaped
+        assert_eq!(render("&lt;b&gt;"), "<p>&amp;lt;b&amp;gt;</p>");
+    }
+
+    #[test]
+    pub fn test_unclosed_delimiters() {
+        // would look ahead to the end of the text for every character without the bounds
+        let source = format!("*`{}{}]", "[".repeat(200_000), "x".repeat(200_000));
+        assert_eq!(render(&source).len(), "<p></p>".len() + source.len());
+
+        let long_text = "x".repeat(LINK_TEXT_MAX_LEN + 1);
+        assert_eq!(
+            render(&format!("[{}](https://zerg.gg)", long_text)),
+            format!("<p>[{}](https://zerg.gg)</p>", long_text)
+        );
+    }
+}
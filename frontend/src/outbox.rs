@@ -0,0 +1,132 @@
+use crate::persisted::{Transaction, TransactionId};
+
+pub const OUTBOX_LOCAL_STORAGE_KEY: &str = "df_forum_outbox";
+
+/// Transactions the backend has not confirmed or rejected yet, oldest first
+///
+/// They are sent again on every new connection until their outcome arrives,
+/// the backend applies a transaction only once.
+/// Only the durable part of a transaction is kept, session state (page views, searches etc.)
+/// is sent again when the connection is back anyway.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outbox {
+    transactions: Vec<Transaction>,
+}
+
+impl Outbox {
+    /// An outbox stored with `to_json`, a missing or invalid one is empty
+    pub fn from_json(json: Option<&str>) -> Self {
+        json.and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Returns false if there was nothing to queue
+    pub fn push(&mut self, mut transaction: Transaction) -> bool {
+        transaction
+            .items
+            .retain(|(_id, persisted, _diff)| !persisted.is_session_state());
+
+        if transaction.items.is_empty()
+            || (transaction.id.is_some()
+                && self
+                    .transactions
+                    .iter()
+                    .any(|queued| queued.id == transaction.id))
+        {
+            return false;
+        }
+
+        self.transactions.push(transaction);
+        true
+    }
+
+    /// Removes the transaction once its outcome arrived, returns false if it was not queued
+    pub fn remove(&mut self, id: TransactionId) -> bool {
+        let len = self.transactions.len();
+        self.transactions
+            .retain(|transaction| transaction.id != Some(id));
+
+        self.transactions.len() != len
+    }
+
+    pub fn get(&self, id: TransactionId) -> Option<&Transaction> {
+        self.transactions
+            .iter()
+            .find(|transaction| transaction.id == Some(id))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persisted::Persisted;
+
+    #[test]
+    pub fn test_outbox_skips_session_state() {
+        let mut outbox = Outbox::default();
+
+        assert!(!outbox.push(Transaction {
+            id: Some(1),
+            items: vec![(55, Persisted::ViewPostsPage(1), 1)],
+        }));
+        assert!(outbox.push(Transaction {
+            id: Some(2),
+            items: vec![
+                (5, Persisted::Post, -1),
+                (55, Persisted::ViewPostsPage(1), -1),
+                (55, Persisted::ViewPostsPage(0), 1),
+            ],
+        }));
+        // the same transaction is only queued once
+        assert!(!outbox.push(Transaction {
+            id: Some(2),
+            items: vec![(5, Persisted::Post, -1)],
+        }));
+
+        assert_eq!(
+            outbox.transactions(),
+            &[Transaction {
+                id: Some(2),
+                items: vec![(5, Persisted::Post, -1)],
+            }]
+        );
+
+        // a transaction stays queued until its outcome arrives
+        assert_eq!(outbox.get(2).map(|transaction| transaction.id), Some(Some(2)));
+        assert!(!outbox.remove(1));
+        assert!(outbox.remove(2));
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    pub fn test_outbox_json() {
+        let mut outbox = Outbox::default();
+        outbox.push(Transaction {
+            id: Some(3),
+            items: vec![
+                (6, Persisted::Post, 1),
+                (6, Persisted::PostTitle("Zerg".into()), 1),
+            ],
+        });
+        outbox.push(Transaction {
+            id: Some(4),
+            items: vec![(55, Persisted::PostLike(6, true), 1)],
+        });
+
+        assert_eq!(Outbox::from_json(Some(&outbox.to_json())), outbox);
+        assert_eq!(Outbox::from_json(Some("not json")), Outbox::default());
+        assert_eq!(Outbox::from_json(None), Outbox::default());
+    }
+}
@@ -8,6 +8,40 @@ pub struct Post {
     pub likes: u64,
 }
 
+#[derive(Abomonation, Hash, Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PostSort {
+    Newest,
+    Oldest,
+    Top,
+    Hot,
+}
+
+/// Describes a live listing of posts, every filter that is `None` matches all posts
+#[derive(Abomonation, Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PostQuery {
+    pub author: Option<u64>, // user id
+    pub board: Option<u64>,
+    // dataflow time range the post was created in, both ends inclusive
+    pub created_from: Option<u64>,
+    pub created_until: Option<u64>,
+    pub sort: PostSort,
+    pub page: u64,
+}
+
+#[derive(Abomonation, Hash, Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    // can hide and restore any post
+    Moderator,
+    // a moderator that can also grant and revoke roles
+    Admin,
+}
+
+impl Role {
+    pub fn can_moderate(&self) -> bool {
+        matches!(self, Role::Moderator | Role::Admin)
+    }
+}
+
 #[derive(Abomonation, Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Persisted {
     // Session { token: String, user_id: u64 },
@@ -16,13 +50,96 @@ pub enum Persisted {
     PostTitle(String),
     PostBody(String),
     PostLike(u64, bool),
+    PostBoard(u64),
+    // soft delete by the creator or a moderator, removing the record restores the post
+    PostDeleted,
+    // hidden by a moderator, with the reason shown to moderators
+    PostHidden(String),
+
+    // a moderator kept the post, its reports no longer hide it
+    PostReviewed,
+    // the id is the user id of the reporter, post id and reason
+    Report(u64, String),
+    // the number of reports that hides a post, only admins can set it
+    ReportHideThreshold(u64),
+
+    // the id is the user id, only admins can grant roles
+    UserRole(Role),
+
+    // the id is the user id of the viewer, the user whose posts and likes the viewer no longer sees,
+    // a blocked user also can not like the viewer's posts
+    Block(u64),
+    Mute(u64),
+
+    // the id is the conversation id, the user ids of its participants
+    Conversation(Vec<u64>),
+    // the id is the message id, the conversation id, the user id of the sender and the message body,
+    // the sender is the user of the session
+    DirectMessage(u64, u64, String),
+    // the id is the user id, the user read the messages of the conversation up to this dataflow time
+    ConversationRead(u64, u64),
+
+    // the id is the user id, the session is in the chat room until it is retracted or disconnects
+    JoinRoom(String),
+    // the id is the message id, the room, the user id of the sender and the message body,
+    // the sender is the user of the session
+    RoomMessage(String, u64, String),
+    // the id is the user id, the user is shown as typing in the room for `TYPING_WINDOW`
+    Typing(String),
+
+    // the id is the user id, the user read the notifications up to this dataflow time
+    NotificationsRead(u64),
 
     // reloads only posts
     ViewPostsPage(u64),
+    // same as ViewPostsPage, but sorted by total likes or by likes decayed by age
+    ViewTopPostsPage(u64),
+    ViewHotPostsPage(u64),
+    // the session's current full-text search query
+    Search(String),
+    // a live query subscription, the id is chosen by the session and tags the results
+    Query(PostQuery),
+    // the reported posts, only sent to moderators
+    ViewModerationQueue,
     
     Session, // user id
-
-    PlusOneDummy,
 }
 
 pub type PersistedItems = Vec<(Id, Persisted, Diff)>;
+
+impl Persisted {
+    /// Whether the record only describes what a session is looking at,
+    /// a new session (ie. after reconnecting) sends its own
+    pub fn is_session_state(&self) -> bool {
+        matches!(
+            self,
+            Persisted::ViewPostsPage(_)
+                | Persisted::ViewTopPostsPage(_)
+                | Persisted::ViewHotPostsPage(_)
+                | Persisted::Search(_)
+                | Persisted::Query(_)
+                | Persisted::ViewModerationQueue
+                | Persisted::Session
+                | Persisted::JoinRoom(_)
+                | Persisted::Typing(_)
+        )
+    }
+}
+
+pub type TransactionId = u64;
+
+/// The items of one client transaction, sent as a single websocket message
+///
+/// The id is chosen by the client and stays the same when the transaction is sent again.
+/// Only transactions with an id are confirmed (or rejected) with a `QueryResult`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Transaction {
+    pub id: Option<TransactionId>,
+    pub items: PersistedItems,
+}
+
+impl From<PersistedItems> for Transaction {
+    fn from(items: PersistedItems) -> Self {
+        Transaction { id: None, items }
+    }
+}
@@ -1,25 +1,108 @@
-use crate::df_tuple_items::Id;
+use crate::df_tuple_items::{Diff, Time};
+use crate::persisted::{Role, TransactionId};
 
 #[derive(Abomonation, Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum QueryResult {
     // AddPost(Id, Post),
     PostCount(u64),
-    DeletePersisted(Id),
 
     PostAggregates(u64, u64), // post count, page count
-    AddPost(u64, String, String),
-
-    DeletePost(u64),
 
     PagePost(u64, u64, u64), // id, page, page_item_index
 
     PostTitle(u64, String), // post id, post title
-    PostBody(u64, String),
+    PostBody(u64, String), // post id, html rendered by `markdown::render` from the markdown body
     PostCreator(u64, String),
+    PostMention(u64, u64), // post id, mentioned user id
 
     PostTotalLikes(u64, u64),
     PostLikedByUser(u64, bool), // post id, whether current user has liked post, total like count
 
     UserPostCount(u64),
     UserLikeCount(u64),
+
+    TopPagePost(u64, u64, u64), // id, page, rank
+    HotPagePost(u64, u64, u64), // id, page, rank
+
+    SearchHit(u64, String), // post id, post title (empty if the post has none)
+
+    QueryPost(u64, u64, u64), // query id, post id, rank
+
+    // only sent to moderators
+    HiddenPost(u64, Option<String>), // post id, reason of the moderator, None if soft deleted
+    UserRole(Role),
+    ModerationQueuePost(u64, u64, u64), // post id, report count, rank
+    PostReport(u64, String), // post id, reason
+    SpamFlag(SpamFlag),
+
+    // users the session's user blocked or muted
+    BlockedUser(u64),
+    MutedUser(u64),
+
+    // only sent to the participants of the conversation
+    Conversation(u64, Vec<u64>), // conversation id, participants
+    DirectMessage(u64, u64, u64, u64, String), // conversation id, message id, sender, time, body
+    ConversationUnread(u64, u64), // conversation id, messages of others the user has not read
+    ConversationReadBy(u64, u64, u64), // conversation id, user id, read up to this time
+
+    // only sent to the sessions in the room
+    RoomPresence(String, u64), // room, user id of a session in the room
+    RoomTyping(String, u64),   // room, user id
+    RoomMessage(String, u64, u64, u64, String), // room, message id, sender, time, body
+
+    // sent to every session of the recipient
+    Notification(Notification, u64, bool), // notification, time, unread
+    NotificationsReadUntil(u64), // time of the user's latest `NotificationsRead`
+    MentionFeedPost(u64, u64), // post id, creation time of a post that mentions the user
+}
+
+/// Content that looks like spam or a flood, found by the spam dataflow
+#[derive(Abomonation, Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SpamFlag {
+    RepeatedBody(u64, u64), // post id, number of posts with the same body
+    PostBurst(u64, u64), // user id, posts created within the spam window
+    NewUserFlood(String, u64), // ip address, new users within the spam window
+}
+
+/// Something that happened to a user's posts, found by the notifications dataflow
+#[derive(Abomonation, Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Notification {
+    PostLiked(u64, u64), // post id, user id of the user that liked it
+    Mentioned(u64, u64), // post id, user id of its creator
+}
+
+#[derive(Abomonation, Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransactionError {
+    // the transaction was not applied, ie. a post without a title
+    Invalid(String),
+    // the user of the session may not change these records, ie. hiding a post without being a moderator
+    Unauthorized(String),
+    // the session sent too many writes, the transaction can be sent again after these milliseconds
+    RateLimited(u64),
+}
+
+// every change of one dataflow time for one session, sent as a single websocket message:
+// a result with a positive diff is added to the session's view, a negative diff retracts it
+pub type QueryResultFrame = (Time, QueryResultChanges);
+
+pub type QueryResultChanges = Vec<(QueryResult, Diff)>;
+
+/// Whether a transaction was applied, sent once to the session that sent it
+#[derive(Hash, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransactionOutcome {
+    Confirmed(TransactionId),
+    Rejected(TransactionId, TransactionError),
+}
+
+/// A websocket message of the backend
+///
+/// Outcomes are not part of the diff stream, they are never retracted.
+/// The outcome of an applied transaction comes after the frame of the time it was applied at.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ServerMessage {
+    // the first message of every connection: the user the connection is bound to
+    // and the token that proves it on the next connection
+    Authenticated(u64, String),
+    Frame(QueryResultFrame),
+    Outcome(TransactionOutcome),
 }
@@ -0,0 +1,7 @@
+//! Methods for graph processing.
+
+pub mod scc;
+pub mod sequential;
+pub mod bijkstra;
+pub mod bfs;
+pub mod propagate;
\ No newline at end of file
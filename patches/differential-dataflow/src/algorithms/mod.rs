@@ -0,0 +1,5 @@
+//! Common algorithms constructed from differential dataflow operators.
+
+pub mod identifiers;
+pub mod prefix_sum;
+pub mod graphs;
\ No newline at end of file
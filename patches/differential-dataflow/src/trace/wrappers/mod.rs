@@ -0,0 +1,9 @@
+//! Wrappers around trace implementations, providing derived views of updates.
+
+pub mod enter;
+pub mod enter_at;
+pub mod frontier;
+pub mod rc;
+
+pub mod filter;
+pub mod freeze;